@@ -0,0 +1,71 @@
+//! luks_benchmark - Compare cipher throughput
+//!
+//! This binary runs `cryptsetup benchmark` and prints the cipher rows, as a
+//! table or as JSON, to help pick a cipher before formatting a volume.
+
+use anyhow::{Context, Result};
+use clap::Command;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::i18n::init_locale;
+use luksctl::luks::{luks_benchmark, BenchmarkRow};
+use luksctl::status::OutputFormat;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_benchmark")
+        .about(t!("help.luks_benchmark.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"));
+    cli::common_args(cli::json_arg(cmd, "luks_benchmark"), "luks_benchmark")
+}
+
+/// Format a throughput column, showing unavailable ciphers as `N/A`
+fn throughput(value: Option<f64>) -> String {
+    value.map_or_else(|| "N/A".to_string(), |mib| format!("{:.1} MiB/s", mib))
+}
+
+/// Print benchmark rows as an aligned table or as JSON
+fn print_rows(rows: &[BenchmarkRow], format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(rows)
+            .context(t!("luks_benchmark.failed_serialize").to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("{:>16}  {:>6}  {:>14}  {:>14}",
+        t!("luks_benchmark.header_cipher"),
+        t!("luks_benchmark.header_key"),
+        t!("luks_benchmark.header_encryption"),
+        t!("luks_benchmark.header_decryption"));
+    for row in rows {
+        println!("{:>16}  {:>5}b  {:>14}  {:>14}",
+            row.cipher, row.key_size, throughput(row.encryption), throughput(row.decryption));
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    let format = OutputFormat::from_json_flag(matches.get_flag("json"));
+
+    // Benchmarks run in memory through the kernel crypto API; no root needed
+    let rows = luks_benchmark()?;
+    print_rows(&rows, format)?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
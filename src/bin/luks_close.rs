@@ -0,0 +1,33 @@
+//! luks_close - Close a LUKS mapper whose filesystem is already unmounted
+//!
+//! This binary closes the dm-crypt mapping of a volume after its
+//! filesystem was unmounted by other means, and cleans up its state. The
+//! logic lives in [`luksctl::cli::close`], shared with `luksctl close`.
+
+use clap::Command;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::i18n::init_locale;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_close")
+        .about(t!("help.luks_close.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"));
+    let cmd = cli::close::args(cmd);
+    cli::common_args(cmd, "luks_close")
+}
+
+fn main() -> ExitCode {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    cli::exit_status(cli::close::run(&matches))
+}
@@ -0,0 +1,216 @@
+//! luks_format - Create a LUKS volume
+//!
+//! This binary wraps `cryptsetup luksFormat`. Formatting destroys whatever
+//! the device holds, so it refuses a device that already carries a LUKS
+//! header or any other signature unless `--force` is given, and asks the
+//! user to type `YES` before going ahead. The new passphrase is read twice
+//! without echo and handed to cryptsetup on stdin.
+
+use anyhow::{bail, Context, Result};
+use clap::{value_parser, Arg, ArgAction, Command};
+use log::{info, warn};
+use rust_i18n::t;
+use secrecy::{ExposeSecret, SecretString};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::command::{is_dry_run, set_dry_run};
+use luksctl::device::{device_signature, resolve_device_spec};
+use luksctl::i18n::init_locale;
+use luksctl::luks::{is_luks_device, luks_format, LuksFormatOptions, LUKS_TYPES, PBKDF_TYPES};
+use luksctl::passphrase::into_secret;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_format")
+        .about(t!("help.luks_format.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .arg(
+            Arg::new("device")
+                .help(t!("help.luks_format.device").to_string())
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .help(t!("help.luks_format.type").to_string())
+                .value_name("TYPE")
+                .value_parser(LUKS_TYPES)
+                .default_value("luks2")
+        )
+        .arg(
+            Arg::new("cipher")
+                .long("cipher")
+                .short('c')
+                .help(t!("help.luks_format.cipher").to_string())
+                .value_name("SPEC")
+        )
+        .arg(
+            Arg::new("key_size")
+                .long("key-size")
+                .short('s')
+                .help(t!("help.luks_format.key_size").to_string())
+                .value_name("BITS")
+                .value_parser(value_parser!(u32))
+        )
+        .arg(
+            Arg::new("pbkdf")
+                .long("pbkdf")
+                .help(t!("help.luks_format.pbkdf").to_string())
+                .value_name("PBKDF")
+                .value_parser(PBKDF_TYPES)
+        )
+        .arg(
+            Arg::new("pbkdf_memory")
+                .long("pbkdf-memory")
+                .help(t!("help.luks_format.pbkdf_memory").to_string())
+                .value_name("KIB")
+                .value_parser(value_parser!(u32))
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help(t!("help.luks_format.force").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help(t!("help.luks_format.yes").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_format.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        );
+    cli::common_args(cmd, "luks_format")
+}
+
+/// Refuse a device that already holds a LUKS header or another signature
+///
+/// With `force` the signature is only reported.
+fn check_device_empty(device: &Path, force: bool) -> Result<()> {
+    let signature = if is_luks_device(device, None)? {
+        Some("LUKS".to_string())
+    } else {
+        device_signature(device)?
+    };
+
+    let Some(signature) = signature else {
+        return Ok(());
+    };
+    let path = device.display().to_string();
+    if !force {
+        bail!("{}", t!("luks_format.device_in_use", path = &path, signature = &signature));
+    }
+    warn!("{}", t!("luks_format.warning_overwriting", path = &path, signature = &signature));
+
+    Ok(())
+}
+
+/// Make the user type `YES` before the device is overwritten
+///
+/// `--yes` and dry-run skip the question; without a terminal to ask on,
+/// `--yes` is required.
+fn confirm_format(device: &Path, assume_yes: bool) -> Result<()> {
+    let path = device.display().to_string();
+    warn!("{}", t!("luks_format.warning_destroys_data", path = &path));
+    if assume_yes || is_dry_run() {
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        bail!("{}", t!("luks_format.needs_yes"));
+    }
+
+    eprint!("{}", t!("luks_format.confirm", path = &path));
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if answer.trim() != "YES" {
+        bail!("{}", t!("luks_format.aborted"));
+    }
+
+    Ok(())
+}
+
+/// Prompt for a passphrase without echo
+///
+/// In dry-run nothing is sent to cryptsetup, so no passphrase is asked for.
+fn prompt_passphrase(prompt_key: &str) -> Result<SecretString> {
+    if is_dry_run() {
+        return Ok(SecretString::from(String::new()));
+    }
+
+    // Moved into a SecretString right away; the raw buffer is zeroized
+    let passphrase = rpassword::prompt_password(t!(prompt_key).to_string())
+        .context(t!("luks_format.failed_read_password").to_string())?;
+    Ok(into_secret(passphrase))
+}
+
+/// Prompt for the new passphrase twice and make sure both entries match
+fn prompt_new_passphrase() -> Result<SecretString> {
+    let passphrase = prompt_passphrase("luks_format.enter_passphrase")?;
+    if is_dry_run() {
+        return Ok(passphrase);
+    }
+    if passphrase.expose_secret().is_empty() {
+        bail!("{}", t!("luks_format.passphrase_empty"));
+    }
+
+    let confirmation = prompt_passphrase("luks_format.verify_passphrase")?;
+    if passphrase.expose_secret() != confirmation.expose_secret() {
+        bail!("{}", t!("luks_format.passphrase_mismatch"));
+    }
+
+    Ok(passphrase)
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_format.program_must_root"));
+    }
+
+    let device = resolve_device_spec(matches.get_one::<String>("device").unwrap())?;
+    let options = LuksFormatOptions {
+        luks_type: matches.get_one::<String>("type").unwrap().clone(),
+        cipher: matches.get_one::<String>("cipher").cloned(),
+        key_size: matches.get_one::<u32>("key_size").copied(),
+        pbkdf: matches.get_one::<String>("pbkdf").cloned(),
+        pbkdf_memory: matches.get_one::<u32>("pbkdf_memory").copied(),
+    };
+
+    // Probing only reads the device, so it runs in dry-run too
+    check_device_empty(&device, matches.get_flag("force"))?;
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    confirm_format(&device, matches.get_flag("yes"))?;
+    let passphrase = prompt_new_passphrase()?;
+
+    luks_format(&device, &passphrase, &options)?;
+    info!("{}", t!("luks_format.formatted", path = device.display().to_string()));
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
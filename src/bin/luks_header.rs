@@ -0,0 +1,206 @@
+//! luks_header - Back up and restore LUKS headers
+//!
+//! This binary wraps `cryptsetup luksHeaderBackup` and `luksHeaderRestore`
+//! so a header can be saved before rotating keys and put back if the live
+//! header is damaged. Backups are written with owner-only permissions and
+//! existing files are never replaced without `--force`. `erase` wraps
+//! `luksErase` for decommissioning a drive: it always needs `--yes`, and
+//! the device path must be typed back when run from a terminal.
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::{info, warn};
+use rust_i18n::t;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::command::{is_dry_run, set_dry_run};
+use luksctl::device::device_uuid;
+use luksctl::i18n::init_locale;
+use luksctl::luks::{luks_erase, luks_header_backup, luks_header_restore};
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_header")
+        .about(t!("help.luks_header.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("backup")
+                .about(t!("help.luks_header.backup").to_string())
+                .arg(
+                    Arg::new("device")
+                        .help(t!("help.luks_header.device").to_string())
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("file")
+                        .help(t!("help.luks_header.backup_file").to_string())
+                        .required(true)
+                        .index(2)
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .short('f')
+                        .help(t!("help.luks_header.force").to_string())
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("restore")
+                .about(t!("help.luks_header.restore").to_string())
+                .arg(
+                    Arg::new("device")
+                        .help(t!("help.luks_header.device").to_string())
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("file")
+                        .help(t!("help.luks_header.restore_file").to_string())
+                        .required(true)
+                        .index(2)
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .help(t!("help.luks_header.yes").to_string())
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("erase")
+                .about(t!("help.luks_header.erase").to_string())
+                .arg(
+                    Arg::new("device")
+                        .help(t!("help.luks_header.device").to_string())
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .help(t!("help.luks_header.erase_yes").to_string())
+                        .action(ArgAction::SetTrue)
+                )
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_header.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+                .global(true)
+        );
+    cli::common_args(cmd, "luks_header")
+}
+
+/// `luks_header backup DEVICE FILE`
+fn backup(matches: &ArgMatches) -> Result<()> {
+    let device = PathBuf::from(matches.get_one::<String>("device").unwrap());
+    let backup_file = PathBuf::from(matches.get_one::<String>("file").unwrap());
+    let force = matches.get_flag("force");
+
+    info!("{}", t!("luks_header.backing_up",
+        device = device.display().to_string(), path = backup_file.display().to_string()));
+    luks_header_backup(&device, &backup_file, force)?;
+    info!("{}", t!("luks_header.backup_complete", path = backup_file.display().to_string()));
+
+    Ok(())
+}
+
+/// `luks_header restore DEVICE FILE`
+fn restore(matches: &ArgMatches) -> Result<()> {
+    let device = PathBuf::from(matches.get_one::<String>("device").unwrap());
+    let backup_file = PathBuf::from(matches.get_one::<String>("file").unwrap());
+    let assume_yes = matches.get_flag("yes");
+
+    // Restoring is destructive: always say what is about to be overwritten
+    warn!("{}", t!("luks_header.restore_warning",
+        device = device.display().to_string(), path = backup_file.display().to_string()));
+
+    // Without --yes cryptsetup asks for confirmation, which needs a terminal
+    if !assume_yes && !std::io::stdin().is_terminal() {
+        bail!("{}", t!("luks_header.restore_needs_yes"));
+    }
+
+    luks_header_restore(&device, &backup_file, assume_yes)?;
+    info!("{}", t!("luks_header.restore_complete", device = device.display().to_string()));
+
+    Ok(())
+}
+
+/// Make the user type the device path back before its keyslots are wiped
+///
+/// Only asked on a terminal; `--yes` is what scripts must pass.
+fn confirm_erase(device: &Path) -> Result<()> {
+    if is_dry_run() || !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+
+    let path = device.display().to_string();
+    eprint!("{}", t!("luks_header.erase_confirm", path = &path));
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if answer.trim_end_matches(['\r', '\n']) != path {
+        bail!("{}", t!("luks_header.erase_aborted"));
+    }
+
+    Ok(())
+}
+
+/// `luks_header erase DEVICE`
+fn erase(matches: &ArgMatches) -> Result<()> {
+    let device = PathBuf::from(matches.get_one::<String>("device").unwrap());
+
+    // Name the volume by UUID too, so the wrong disk is easier to spot
+    let uuid = device_uuid(&device)?;
+    warn!("{}", t!("luks_header.erase_warning",
+        device = device.display().to_string(),
+        uuid = uuid.as_deref().unwrap_or("-")));
+
+    if !matches.get_flag("yes") {
+        bail!("{}", t!("luks_header.erase_needs_yes"));
+    }
+    confirm_erase(&device)?;
+
+    luks_erase(&device)?;
+    info!("{}", t!("luks_header.erase_complete", device = device.display().to_string()));
+
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_header.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    match matches.subcommand() {
+        Some(("backup", sub)) => backup(sub),
+        Some(("restore", sub)) => restore(sub),
+        Some(("erase", sub)) => erase(sub),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
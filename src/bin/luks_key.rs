@@ -0,0 +1,213 @@
+//! luks_key - Manage LUKS passphrases
+//!
+//! This binary wraps `cryptsetup luksAddKey`, `luksChangeKey`,
+//! `luksRemoveKey` and `luksKillSlot` so passphrases can be rotated without
+//! remembering cryptsetup's syntax. Passphrases are read without echo and
+//! handed to cryptsetup on stdin; removing the last keyslot is refused
+//! unless `--force` is given.
+
+use anyhow::{bail, Context, Result};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use log::info;
+use rust_i18n::t;
+use secrecy::{ExposeSecret, SecretString};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::command::{is_dry_run, set_dry_run};
+use luksctl::i18n::init_locale;
+use luksctl::luks::{luks_add_key, luks_change_key, luks_kill_slot, luks_remove_key};
+use luksctl::passphrase::into_secret;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn device_arg() -> Arg {
+    Arg::new("device")
+        .help(t!("help.luks_key.device").to_string())
+        .required(true)
+        .index(1)
+}
+
+fn key_slot_arg() -> Arg {
+    Arg::new("key_slot")
+        .long("key-slot")
+        .short('S')
+        .help(t!("help.luks_key.key_slot").to_string())
+        .value_name("N")
+        .value_parser(value_parser!(u8))
+}
+
+fn force_arg() -> Arg {
+    Arg::new("force")
+        .long("force")
+        .short('f')
+        .help(t!("help.luks_key.force").to_string())
+        .action(ArgAction::SetTrue)
+}
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_key")
+        .about(t!("help.luks_key.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("add")
+                .about(t!("help.luks_key.add").to_string())
+                .arg(device_arg())
+                .arg(key_slot_arg())
+        )
+        .subcommand(
+            Command::new("change")
+                .about(t!("help.luks_key.change").to_string())
+                .arg(device_arg())
+                .arg(key_slot_arg())
+        )
+        .subcommand(
+            Command::new("remove")
+                .about(t!("help.luks_key.remove").to_string())
+                .arg(device_arg())
+                .arg(force_arg())
+        )
+        .subcommand(
+            Command::new("kill")
+                .about(t!("help.luks_key.kill").to_string())
+                .arg(device_arg())
+                .arg(
+                    Arg::new("slot")
+                        .help(t!("help.luks_key.slot").to_string())
+                        .required(true)
+                        .index(2)
+                        .value_parser(value_parser!(u8))
+                )
+                .arg(force_arg())
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_key.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+                .global(true)
+        );
+    cli::common_args(cmd, "luks_key")
+}
+
+/// Prompt for a passphrase without echo
+///
+/// In dry-run nothing is sent to cryptsetup, so no passphrase is asked for.
+fn prompt_passphrase(prompt_key: &str) -> Result<SecretString> {
+    if is_dry_run() {
+        return Ok(SecretString::from(String::new()));
+    }
+
+    // Moved into a SecretString right away; the raw buffer is zeroized
+    let passphrase = rpassword::prompt_password(t!(prompt_key).to_string())
+        .context(t!("luks_key.failed_read_password").to_string())?;
+    Ok(into_secret(passphrase))
+}
+
+/// Prompt for a new passphrase twice and make sure both entries match
+fn prompt_new_passphrase() -> Result<SecretString> {
+    let passphrase = prompt_passphrase("luks_key.enter_new_passphrase")?;
+    if is_dry_run() {
+        return Ok(passphrase);
+    }
+
+    if passphrase.expose_secret().is_empty() {
+        bail!("{}", t!("luks_key.passphrase_empty"));
+    }
+
+    let confirmation = prompt_passphrase("luks_key.verify_new_passphrase")?;
+    if passphrase.expose_secret() != confirmation.expose_secret() {
+        bail!("{}", t!("luks_key.passphrase_mismatch"));
+    }
+
+    Ok(passphrase)
+}
+
+fn device_of(matches: &ArgMatches) -> PathBuf {
+    PathBuf::from(matches.get_one::<String>("device").unwrap())
+}
+
+/// `luks_key add DEVICE`
+fn add(matches: &ArgMatches) -> Result<()> {
+    let device = device_of(matches);
+    let key_slot = matches.get_one::<u8>("key_slot").copied();
+
+    let existing = prompt_passphrase("luks_key.enter_existing_passphrase")?;
+    let new_passphrase = prompt_new_passphrase()?;
+
+    luks_add_key(&device, &existing, &new_passphrase, key_slot)?;
+    info!("{}", t!("luks_key.key_added", path = device.display().to_string()));
+
+    Ok(())
+}
+
+/// `luks_key change DEVICE`
+fn change(matches: &ArgMatches) -> Result<()> {
+    let device = device_of(matches);
+    let key_slot = matches.get_one::<u8>("key_slot").copied();
+
+    let existing = prompt_passphrase("luks_key.enter_current_passphrase")?;
+    let new_passphrase = prompt_new_passphrase()?;
+
+    luks_change_key(&device, &existing, &new_passphrase, key_slot)?;
+    info!("{}", t!("luks_key.key_changed", path = device.display().to_string()));
+
+    Ok(())
+}
+
+/// `luks_key remove DEVICE`
+fn remove(matches: &ArgMatches) -> Result<()> {
+    let device = device_of(matches);
+
+    let passphrase = prompt_passphrase("luks_key.enter_passphrase_to_remove")?;
+
+    luks_remove_key(&device, &passphrase, matches.get_flag("force"))?;
+    info!("{}", t!("luks_key.key_removed", path = device.display().to_string()));
+
+    Ok(())
+}
+
+/// `luks_key kill DEVICE SLOT`
+fn kill(matches: &ArgMatches) -> Result<()> {
+    let device = device_of(matches);
+    let slot = *matches.get_one::<u8>("slot").unwrap();
+
+    let existing = prompt_passphrase("luks_key.enter_remaining_passphrase")?;
+
+    luks_kill_slot(&device, slot, &existing, matches.get_flag("force"))?;
+    info!("{}", t!("luks_key.slot_killed", slot = slot, path = device.display().to_string()));
+
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_key.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    // Passphrases are zeroized when the handlers drop them
+    match matches.subcommand() {
+        Some(("add", sub)) => add(sub),
+        Some(("change", sub)) => change(sub),
+        Some(("remove", sub)) => remove(sub),
+        Some(("kill", sub)) => kill(sub),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
@@ -0,0 +1,33 @@
+//! luks_list - List LUKS volumes managed by luksctl
+//!
+//! This binary enumerates the mappings recorded under /run/luksctl and prints
+//! one row per mapping. The logic lives in [`luksctl::cli::list`], shared
+//! with `luksctl list`.
+
+use clap::Command;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::i18n::init_locale;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_list")
+        .about(t!("help.luks_list.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"));
+    let cmd = cli::json_arg(cli::list::args(cmd), "luks_list");
+    cli::common_args(cmd, "luks_list")
+}
+
+fn main() -> ExitCode {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    cli::exit_status(cli::list::run(&matches))
+}
@@ -0,0 +1,147 @@
+//! luks_probe - Report whether a device is LUKS, and which version
+//!
+//! This binary reads the LUKS header with `cryptsetup luksDump` and prints
+//! the version, UUID and label, as text or as JSON. The device is never
+//! opened and root is not required.
+//!
+//! With `--test` it instead checks a passphrase with `cryptsetup open
+//! --test-passphrase`, which unlocks a keyslot without creating a mapping.
+//! A wrong passphrase exits with status 2, like a failed `luks_mount`.
+
+use anyhow::{Context, Result};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use rust_i18n::t;
+use secrecy::SecretString;
+use serde::Serialize;
+use std::path::Path;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::device::resolve_device_spec;
+use luksctl::i18n::init_locale;
+use luksctl::error::LuksError;
+use luksctl::luks::{luks_probe, luks_test_passphrase, LuksInfo};
+use luksctl::passphrase::{into_secret, read_passphrase_stdin};
+use luksctl::status::OutputFormat;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_probe")
+        .about(t!("help.luks_probe.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .arg(
+            Arg::new("device")
+                .help(t!("help.luks_probe.device").to_string())
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::new("test")
+                .long("test")
+                .help(t!("help.luks_probe.test").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("key_slot")
+                .long("key-slot")
+                .short('S')
+                .help(t!("help.luks_probe.key_slot").to_string())
+                .value_name("N")
+                .value_parser(value_parser!(u8))
+                .requires("test")
+        )
+        .arg(
+            Arg::new("password_stdin")
+                .long("password-stdin")
+                .help(t!("help.luks_probe.password_stdin").to_string())
+                .action(ArgAction::SetTrue)
+                .requires("test")
+        );
+    cli::common_args(cli::json_arg(cmd, "luks_probe"), "luks_probe")
+}
+
+/// Print the header details as text or as JSON
+fn print_info(info: &LuksInfo, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(info)
+            .context(t!("luks_probe.failed_serialize").to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let none = t!("luks_probe.none");
+    println!("{}", t!("luks_probe.label_version", version = info.version));
+    println!("{}", t!("luks_probe.label_uuid", uuid = info.uuid.as_deref().unwrap_or(&none)));
+    println!("{}", t!("luks_probe.label_label", label = info.label.as_deref().unwrap_or(&none)));
+
+    Ok(())
+}
+
+/// Result of `--test`, as printed with `--json`
+#[derive(Serialize)]
+struct PassphraseCheck<'a> {
+    device: &'a Path,
+    passphrase_valid: bool,
+}
+
+/// Read the passphrase to test from stdin or the terminal
+fn read_password(password_stdin: bool) -> Result<SecretString> {
+    if password_stdin {
+        return read_passphrase_stdin();
+    }
+    let password_raw = rpassword::prompt_password(t!("luks_probe.enter_passphrase").to_string())
+        .context(t!("luks_probe.failed_read_password").to_string())?;
+    Ok(into_secret(password_raw))
+}
+
+/// Check a passphrase without opening the device
+/// 
+/// A wrong passphrase is reported as [`LuksError::WrongPassphrase`] so the
+/// exit status tells it apart from a device error.
+fn test_passphrase(matches: &ArgMatches, device: &Path, format: OutputFormat) -> Result<()> {
+    let password = read_password(matches.get_flag("password_stdin"))?;
+    let valid = luks_test_passphrase(device, &password, matches.get_one::<u8>("key_slot").copied())?;
+    // Password is automatically zeroized when dropped
+    drop(password);
+
+    if !valid {
+        return Err(anyhow::Error::new(LuksError::WrongPassphrase)
+            .context(t!("luks_probe.passphrase_rejected", device = device.display().to_string())));
+    }
+
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(&PassphraseCheck { device, passphrase_valid: true })
+            .context(t!("luks_probe.failed_serialize").to_string())?;
+        println!("{}", json);
+    } else {
+        println!("{}", t!("luks_probe.passphrase_accepted", device = device.display().to_string()));
+    }
+
+    Ok(())
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    let format = OutputFormat::from_json_flag(matches.get_flag("json"));
+
+    // Only the header is read, so no root check: reading the device is enough
+    let device = resolve_device_spec(matches.get_one::<String>("device").unwrap())?;
+    if matches.get_flag("test") {
+        return test_passphrase(&matches, &device, format);
+    }
+    let info = luks_probe(&device)?;
+    print_info(&info, format)?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
@@ -0,0 +1,80 @@
+//! luks_resize - Grow an open LUKS volume after its device was enlarged
+//!
+//! This binary wraps `cryptsetup resize` so an active mapping picks up the
+//! new size of its backing device, and with `--grow-fs` also grows the
+//! filesystem inside (`resize2fs` or `xfs_growfs`, by detected type).
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, Command};
+use log::info;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::command::set_dry_run;
+use luksctl::i18n::init_locale;
+use luksctl::luks::luks_resize;
+use luksctl::mapper::get_mapper_path;
+use luksctl::resize::grow_filesystem;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_resize")
+        .about(t!("help.luks_resize.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .arg(
+            Arg::new("mapper")
+                .help(t!("help.luks_resize.mapper").to_string())
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::new("grow_fs")
+                .long("grow-fs")
+                .help(t!("help.luks_resize.grow_fs").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_resize.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        );
+    cli::common_args(cmd, "luks_resize")
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    // Accept both `luks-xxx` and `/dev/mapper/luks-xxx`
+    let mapper_arg = matches.get_one::<String>("mapper").unwrap();
+    let mapper_name = mapper_arg.strip_prefix("/dev/mapper/").unwrap_or(mapper_arg);
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_resize.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    luks_resize(mapper_name)?;
+    info!("{}", t!("luks_resize.resized", name = mapper_name));
+
+    if matches.get_flag("grow_fs") {
+        grow_filesystem(&get_mapper_path(mapper_name))?;
+        info!("{}", t!("luks_resize.fs_grown", name = mapper_name));
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
@@ -0,0 +1,34 @@
+//! luks_status - Report the state of LUKS volumes managed by luksctl
+//!
+//! This binary reconciles the luksctl state file, /proc/mounts and
+//! `cryptsetup status` for a mount point or mapper name and reports any
+//! inconsistencies between them. The logic lives in [`luksctl::cli::status`],
+//! shared with `luksctl status`.
+
+use clap::Command;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::i18n::init_locale;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_status")
+        .about(t!("help.luks_status.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"));
+    let cmd = cli::json_arg(cli::status::args(cmd), "luks_status");
+    cli::common_args(cmd, "luks_status")
+}
+
+fn main() -> ExitCode {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    cli::exit_status(cli::status::run(&matches))
+}
@@ -0,0 +1,118 @@
+//! luks_suspend - Suspend and resume open LUKS volumes
+//!
+//! This binary wraps `cryptsetup luksSuspend` and `luksResume`. Suspending
+//! flushes pending I/O, blocks the volume and wipes its key from kernel
+//! memory, e.g. before suspend-to-disk; resuming asks for the passphrase
+//! again.
+
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, Command};
+use log::{info, warn};
+use rust_i18n::t;
+use secrecy::SecretString;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::command::{is_dry_run, set_dry_run};
+use luksctl::error::LuksError;
+use luksctl::i18n::init_locale;
+use luksctl::luks::{luks_resume, luks_suspend};
+use luksctl::passphrase::into_secret;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+/// Passphrase attempts before `--resume` gives up
+const MAX_TRIES: u32 = 3;
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luks_suspend")
+        .about(t!("help.luks_suspend.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .arg(
+            Arg::new("mapper")
+                .help(t!("help.luks_suspend.mapper").to_string())
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help(t!("help.luks_suspend.resume").to_string())
+                .value_name("DEVICE")
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_suspend.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        );
+    cli::common_args(cmd, "luks_suspend")
+}
+
+/// Prompt for the passphrase without echo
+/// 
+/// In dry-run nothing is sent to cryptsetup, so no passphrase is asked for.
+fn prompt_passphrase() -> Result<SecretString> {
+    if is_dry_run() {
+        return Ok(SecretString::from(String::new()));
+    }
+
+    // Moved into a SecretString right away; the raw buffer is zeroized
+    let passphrase = rpassword::prompt_password(t!("luks_suspend.enter_passphrase").to_string())
+        .context(t!("luks_suspend.failed_read_password").to_string())?;
+    Ok(into_secret(passphrase))
+}
+
+/// Resume `mapper_name`, re-prompting after a wrong passphrase
+fn resume(device: &Path, mapper_name: &str) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        let passphrase = prompt_passphrase()?;
+        match luks_resume(device, mapper_name, &passphrase) {
+            Err(LuksError::WrongPassphrase) if attempt < MAX_TRIES => {
+                warn!("{}", t!("luks_suspend.wrong_passphrase_retry", attempt = attempt, tries = MAX_TRIES));
+                attempt += 1;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    // Accept both `luks-xxx` and `/dev/mapper/luks-xxx`
+    let mapper_arg = matches.get_one::<String>("mapper").unwrap();
+    let mapper_name = mapper_arg.strip_prefix("/dev/mapper/").unwrap_or(mapper_arg);
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_suspend.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    match matches.get_one::<String>("resume") {
+        Some(device) => {
+            resume(&PathBuf::from(device), mapper_name)?;
+            info!("{}", t!("luks_suspend.resumed", name = mapper_name));
+        }
+        None => {
+            luks_suspend(mapper_name)?;
+            info!("{}", t!("luks_suspend.suspended", name = mapper_name));
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    cli::exit_status(run())
+}
@@ -0,0 +1,63 @@
+//! luksctl - Single entry point for managing LUKS volumes
+//!
+//! `luksctl mount`, `umount`, `close`, `status` and `list` run the same
+//! code as the standalone `luks_mount`, `luks_umount`, `luks_close`,
+//! `luks_status` and `luks_list` binaries. `luksctl doctor` checks the
+//! environment and prints a checklist for bug reports. `-v`, `-q`,
+//! `--locale` and `--json` are accepted on the top-level command as well as
+//! after the subcommand.
+
+use clap::Command;
+use rust_i18n::t;
+use std::process::ExitCode;
+
+use luksctl::cli;
+use luksctl::i18n::init_locale;
+
+rust_i18n::i18n!("locales", fallback = "en");
+
+fn build_cli() -> Command {
+    let cmd = Command::new("luksctl")
+        .about(t!("help.luksctl.about").to_string())
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .subcommand_required(true)
+        .subcommand(cli::mount::args(
+            Command::new("mount").about(t!("help.luks_mount.about").to_string())
+        ))
+        .subcommand(cli::umount::args(
+            Command::new("umount").about(t!("help.luks_umount.about").to_string())
+        ))
+        .subcommand(cli::close::args(
+            Command::new("close").about(t!("help.luks_close.about").to_string())
+        ))
+        .subcommand(cli::status::args(
+            Command::new("status").about(t!("help.luks_status.about").to_string())
+        ))
+        .subcommand(cli::list::args(
+            Command::new("list").about(t!("help.luks_list.about").to_string())
+        ))
+        .subcommand(cli::doctor::args(
+            Command::new("doctor").about(t!("help.luksctl.doctor").to_string())
+        ));
+    cli::common_args(cli::json_arg(cmd, "luksctl"), "luksctl")
+}
+
+fn main() -> ExitCode {
+    // Initialize locale from LANG environment variable
+    init_locale(None);
+
+    let matches = build_cli().get_matches();
+    cli::apply_common_args(&matches);
+
+    let result = match matches.subcommand() {
+        Some(("mount", sub)) => cli::mount::run(sub),
+        Some(("umount", sub)) => cli::umount::run(sub),
+        Some(("close", sub)) => cli::close::run(sub),
+        Some(("status", sub)) => cli::status::run(sub),
+        Some(("list", sub)) => cli::list::run(sub),
+        Some(("doctor", sub)) => cli::doctor::run(sub),
+        _ => unreachable!("a subcommand is required"),
+    };
+    cli::exit_status(result)
+}
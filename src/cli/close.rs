@@ -0,0 +1,78 @@
+//! `luks_close` / `luksctl close`: close a mapper without unmounting
+//!
+//! For a filesystem that was already unmounted, by `luks_umount
+//! --keep-open` or outside luksctl: closes the dm-crypt mapping, detaches
+//! the loop device of a container file and removes the state file. The
+//! target is a mapper name or the mount point it was mounted on.
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::info;
+use rust_i18n::t;
+use std::path::Path;
+
+use crate::command::set_dry_run;
+use crate::mapper::resolve_mapper_name;
+use crate::pathsafe::{ensure_absolute, ensure_no_null};
+use crate::volume::close_volume;
+
+const MAPPER_PREFIX: &str = "/dev/mapper/";
+
+/// Add the `close` arguments to `cmd`
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("target")
+                .help(t!("help.luks_close.target").to_string())
+                .required(true)
+                .index(1)
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_close.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        )
+}
+
+/// Find the mapper named by `target`
+///
+/// A target under `/dev/mapper/` or without a leading slash names a mapper;
+/// anything else is a mount point, looked up in the state files and then
+/// /proc/mounts.
+fn resolve_target(target: &str) -> Result<String> {
+    if !target.starts_with('/') || target.starts_with(MAPPER_PREFIX) {
+        return Ok(target.strip_prefix(MAPPER_PREFIX).unwrap_or(target).to_string());
+    }
+
+    let mount_point = Path::new(target);
+    ensure_absolute(mount_point)?;
+    ensure_no_null(mount_point)?;
+    let mount_point = mount_point.canonicalize()
+        .unwrap_or_else(|_| mount_point.to_path_buf());
+
+    match resolve_mapper_name(&mount_point)? {
+        Some(mapper_name) => Ok(mapper_name),
+        None => bail!("{}", t!("luks_close.mapper_not_found", target = target)),
+    }
+}
+
+/// Close the mapper named in `matches`
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let target = matches.get_one::<String>("target").unwrap();
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_close.program_must_root"));
+    }
+
+    let mapper_name = resolve_target(target)?;
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+
+    close_volume(&mapper_name)?;
+    info!("{}", t!("luks_close.closed", name = &mapper_name));
+
+    Ok(())
+}
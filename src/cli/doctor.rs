@@ -0,0 +1,63 @@
+//! `luksctl doctor`: check the environment luksctl runs in
+//!
+//! Prints the cryptsetup version, kernel and TPM2 support, installed tools
+//! and state directory access as a checklist for bug reports. As root it
+//! also looks for stale state files and orphaned mappers left by a crash,
+//! and repairs them with `--fix`.
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::info;
+use rust_i18n::t;
+
+use crate::doctor::{diagnose_mappings, fix_mappings, print_report, run_checks, DoctorReport};
+use crate::status::OutputFormat;
+
+/// Add the `doctor` arguments to `cmd`
+///
+/// `--json` is added by the caller.
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .help(t!("help.luksctl.doctor_fix").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("close_orphans")
+                .long("close-orphans")
+                .help(t!("help.luksctl.doctor_close_orphans").to_string())
+                .action(ArgAction::SetTrue)
+                .requires("fix")
+        )
+}
+
+/// Run the diagnostics, repair the mappings if asked, and print the report
+///
+/// Root is only needed to inspect the mappings; without it the
+/// environment checks still run and the state directory check reports what
+/// the current user may do.
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_json_flag(matches.get_flag("json"));
+    let is_root = nix::unistd::Uid::effective().is_root();
+
+    if matches.get_flag("fix") && !is_root {
+        bail!("{}", t!("doctor.fix_must_root"));
+    }
+
+    let checks = run_checks();
+    let mappings = if is_root {
+        let mut mappings = diagnose_mappings()?;
+        if matches.get_flag("fix") {
+            fix_mappings(&mut mappings, matches.get_flag("close_orphans"))?;
+            let fixed = mappings.iter().filter(|diagnosis| diagnosis.fixed).count();
+            info!("{}", t!("doctor.fixed_count", count = fixed));
+        }
+        Some(mappings)
+    } else {
+        None
+    };
+
+    print_report(&DoctorReport { checks, mappings }, format)
+}
@@ -0,0 +1,51 @@
+//! `luks_list` / `luksctl list`: list the volumes managed by luksctl
+//!
+//! Enumerates the mappings recorded in the state directory and prints one
+//! row per mapping.
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::info;
+use rust_i18n::t;
+
+use crate::mapper::reconcile_state;
+use crate::status::{mapping_entries, print_mappings, OutputFormat};
+
+/// Add the `list` arguments to `cmd`
+/// 
+/// `--json` is added by the caller.
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("reconcile")
+                .long("reconcile")
+                .help(t!("help.luks_list.reconcile").to_string())
+                .action(ArgAction::SetTrue)
+        )
+}
+
+/// List all managed volumes
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let format = OutputFormat::from_json_flag(matches.get_flag("json"));
+
+    // Check if running as root (state files are root-only)
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_list.program_must_root"));
+    }
+
+    // Drop entries of mappers that are gone, e.g. from a boot-time service
+    if matches.get_flag("reconcile") {
+        let report = reconcile_state()?;
+        for mount_point in &report.removed {
+            info!("{}", t!("luks_list.reconcile_removed", path = mount_point.display().to_string()));
+        }
+        for mount_point in &report.restored {
+            info!("{}", t!("luks_list.reconcile_restored", path = mount_point.display().to_string()));
+        }
+    }
+
+    let entries = mapping_entries()?;
+    print_mappings(&entries, format)?;
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! Command-line front ends
+//!
+//! Each submodule holds the arguments and the logic of one command. The
+//! standalone binaries (`luks_mount`, `luks_umount`, ...) and the `luksctl`
+//! umbrella binary build their clap commands from the same `args`
+//! functions and dispatch to the same `run` functions, so both spellings
+//! behave identically.
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use rust_i18n::t;
+use serde::Serialize;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::LuksError;
+use crate::i18n::init_locale;
+use crate::logging::init_logging;
+use crate::redact::redact;
+
+pub mod close;
+pub mod doctor;
+pub mod list;
+pub mod mount;
+pub mod status;
+pub mod umount;
+
+/// Whether failures are reported as JSON on stderr (`--error-json`)
+static ERROR_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Add `-v`, `-q`, `--locale` and `--error-json`, with help text from
+/// `help.<section>`
+/// 
+/// The arguments are global so they are accepted after a subcommand too.
+pub fn common_args(cmd: Command, section: &str) -> Command {
+    cmd
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help(t!(format!("help.{}.verbose", section)).to_string())
+                .action(ArgAction::Count)
+                .global(true)
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .help(t!(format!("help.{}.quiet", section)).to_string())
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .global(true)
+        )
+        .arg(
+            Arg::new("locale")
+                .long("locale")
+                .help(t!(format!("help.{}.locale", section)).to_string())
+                .value_name("CODE")
+                .global(true)
+        )
+        .arg(
+            Arg::new("error_json")
+                .long("error-json")
+                .help(t!(format!("help.{}.error_json", section)).to_string())
+                .action(ArgAction::SetTrue)
+                .global(true)
+        )
+}
+
+/// Add `--json`, with help text from `help.<section>.json`
+pub fn json_arg(cmd: Command, section: &str) -> Command {
+    cmd.arg(
+        Arg::new("json")
+            .long("json")
+            .help(t!(format!("help.{}.json", section)).to_string())
+            .action(ArgAction::SetTrue)
+            .global(true)
+    )
+}
+
+/// Apply the flags added by [`common_args`]
+/// 
+/// Call after [`init_locale`]`(None)` and parsing: an explicit `--locale`
+/// takes precedence over `LANG`.
+pub fn apply_common_args(matches: &ArgMatches) {
+    init_logging(matches.get_count("verbose"), matches.get_flag("quiet"));
+
+    if let Some(locale) = matches.get_one::<String>("locale") {
+        init_locale(Some(locale));
+    }
+
+    ERROR_JSON.store(matches.get_flag("error_json"), Ordering::SeqCst);
+}
+
+/// Exit status for a failure that carries no [`LuksError`]
+pub const EXIT_FAILURE: u8 = 1;
+
+/// The exit status for an error returned by a `run` function
+/// 
+/// The first [`LuksError`] in the error's chain decides the code (see
+/// [`LuksError::exit_code`]), so context added on the way up doesn't hide
+/// it.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<LuksError>())
+        .map_or(EXIT_FAILURE, LuksError::exit_code)
+}
+
+/// An error as printed by `--error-json`
+/// 
+/// `error` is the [`LuksError`] variant name, which doesn't depend on the
+/// locale; `message` is the localized text a person would see.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub error: &'static str,
+    pub message: String,
+    pub code: u8,
+}
+
+impl ErrorReport {
+    /// Describe an error returned by a `run` function
+    /// 
+    /// Like [`exit_code`], the first [`LuksError`] in the chain decides the
+    /// kind. The message is the whole chain on one line, redacted.
+    pub fn new(err: &anyhow::Error) -> Self {
+        let error = err.chain()
+            .find_map(|cause| cause.downcast_ref::<LuksError>())
+            .map_or("Other", LuksError::kind);
+        Self {
+            error,
+            message: redact(&format!("{:#}", err)),
+            code: exit_code(err),
+        }
+    }
+}
+
+/// Turn the result of a `run` function into the process exit status
+/// 
+/// Errors are printed the way `main` returning `anyhow::Result` would
+/// print them, with registered paths redacted if enabled, but the exit
+/// status tells scripts what went wrong. With `--error-json` a single
+/// [`ErrorReport`] object is printed instead.
+pub fn exit_status(result: anyhow::Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let report = ErrorReport::new(&err);
+            // Serializing strings and integers cannot fail
+            if ERROR_JSON.load(Ordering::SeqCst) {
+                eprintln!("{}", serde_json::to_string(&report).unwrap_or_default());
+            } else {
+                eprintln!("Error: {}", redact(&format!("{:?}", err)));
+            }
+            ExitCode::from(report.code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_exit_code_follows_luks_error_through_context() {
+        let not_found: anyhow::Result<()> = Err(LuksError::DeviceNotFound { path: PathBuf::from("/dev/sdz") }.into());
+        let err = not_found.context("while mounting").unwrap_err();
+        assert_eq!(exit_code(&err), 3);
+
+        assert_eq!(exit_code(&LuksError::WrongPassphrase.into()), 2);
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_error_report() {
+        let err = anyhow::Error::from(LuksError::WrongPassphrase).context("while mounting");
+        let report = ErrorReport::new(&err);
+        assert_eq!(report.error, "WrongPassphrase");
+        assert_eq!(report.code, 2);
+        assert!(report.message.starts_with("while mounting: "));
+
+        let json = serde_json::to_value(ErrorReport::new(&anyhow::anyhow!("boom"))).unwrap();
+        assert_eq!(json, serde_json::json!({"error": "Other", "message": "boom", "code": 1}));
+    }
+}
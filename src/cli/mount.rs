@@ -0,0 +1,867 @@
+//! `luks_mount` / `luksctl mount`: open a LUKS device and mount it
+//!
+//! Validates the command line, reads the passphrase and hands the open,
+//! mount and state bookkeeping to [`open_and_mount`], which closes everything
+//! opened so far again if a later step fails.
+
+use anyhow::{bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::{debug, info, warn};
+use nix::unistd::chown;
+use rust_i18n::t;
+use secrecy::{ExposeSecret, SecretString};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::command::set_dry_run;
+use crate::config::{load_config, VolumeConfig};
+use crate::device::{device_uuid, is_device_spec, resolve_device_spec, wait_for_device_spec};
+use crate::error::LuksError;
+use crate::export::{to_crypttab_line, to_fstab_line};
+use crate::loopdev::{attach_loop, detach_loop, loop_backing_file, validate_container_file};
+use crate::luks::{
+    is_luks_device, luks_status, set_cryptsetup_debug, set_cryptsetup_timeout, LuksOpenOptions, PlainOpenOptions,
+};
+use crate::mapper::{custom_mapper_name, get_mapper_path, get_mount_mapping};
+use crate::mount::{
+    detect_fs_type, find_mount_entry, is_dir_empty, mount_namespace_path, parse_bind_spec, parse_dir_mode, resolve_group,
+    resolve_user, HardeningProfile, MountEntry, MountOptions, DEFAULT_MOUNT_POINT_MODE,
+};
+use crate::passphrase::{into_secret, read_passphrase_env, read_passphrase_stdin};
+use crate::pathsafe::ensure_safe_absolute;
+use crate::redact::{register_device, register_mount_point, set_redact};
+use crate::volume::{open_and_mount, OpenMountOptions};
+
+/// Add the `mount` arguments to `cmd`
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("device")
+                .help(t!("help.luks_mount.device").to_string())
+                .required_unless_present("config")
+                .index(1)
+        )
+        .arg(
+            Arg::new("mount_point")
+                .help(t!("help.luks_mount.mount_point").to_string())
+                .required_unless_present("config")
+                .index(2)
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help(t!("help.luks_mount.config").to_string())
+                .value_name("FILE")
+                .conflicts_with_all([
+                    "device", "mount_point", "name", "bind", "key_file", "fs_type", "options", "ro", "rw",
+                    "noatime", "nodiratime", "relatime", "sync", "nofail",
+                ])
+        )
+        .arg(
+            Arg::new("mkdir")
+                .long("mkdir")
+                .help(t!("help.luks_mount.mkdir").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("owner")
+                .long("owner")
+                .help(t!("help.luks_mount.owner").to_string())
+                .value_name("USER")
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .help(t!("help.luks_mount.group").to_string())
+                .value_name("GROUP")
+        )
+        .arg(
+            Arg::new("mode")
+                .long("mode")
+                .help(t!("help.luks_mount.mode").to_string())
+                .value_name("OCTAL")
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .help(t!("help.luks_mount.name").to_string())
+                .value_name("MAPPER")
+        )
+        .arg(
+            Arg::new("ensure")
+                .long("ensure")
+                .help(t!("help.luks_mount.ensure").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help(t!("help.luks_mount.force").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("ro")
+                .long("ro")
+                .short('r')
+                .help(t!("help.luks_mount.ro").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("rw")
+                .long("rw")
+                .help(t!("help.luks_mount.rw").to_string())
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["ro", "crypt_ro"])
+        )
+        .arg(
+            Arg::new("crypt_ro")
+                .long("crypt-ro")
+                .help(t!("help.luks_mount.crypt_ro").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("fs_type")
+                .long("fs-type")
+                .short('t')
+                .help(t!("help.luks_mount.fs_type").to_string())
+                .value_name("TYPE")
+        )
+        .arg(
+            Arg::new("options")
+                .long("options")
+                .short('o')
+                .help(t!("help.luks_mount.options").to_string())
+                .value_name("OPTIONS")
+        )
+        .arg(
+            Arg::new("noatime")
+                .long("noatime")
+                .help(t!("help.luks_mount.noatime").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("nodiratime")
+                .long("nodiratime")
+                .help(t!("help.luks_mount.nodiratime").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("relatime")
+                .long("relatime")
+                .help(t!("help.luks_mount.relatime").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("sync")
+                .long("sync")
+                .help(t!("help.luks_mount.sync").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("nofail")
+                .long("nofail")
+                .help(t!("help.luks_mount.nofail").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("key_file")
+                .long("key-file")
+                .short('k')
+                .help(t!("help.luks_mount.key_file").to_string())
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+        )
+        .arg(
+            Arg::new("password_stdin")
+                .long("password-stdin")
+                .help(t!("help.luks_mount.password_stdin").to_string())
+                .action(ArgAction::SetTrue)
+                .conflicts_with("key_file")
+        )
+        .arg(
+            Arg::new("password_env")
+                .long("password-env")
+                .help(t!("help.luks_mount.password_env").to_string())
+                .value_name("VAR")
+                .conflicts_with_all(["key_file", "password_stdin"])
+        )
+        .arg(
+            Arg::new("key_slot")
+                .long("key-slot")
+                .short('S')
+                .help(t!("help.luks_mount.key_slot").to_string())
+                .value_name("SLOT")
+                .value_parser(clap::value_parser!(u8))
+        )
+        .arg(
+            Arg::new("tries")
+                .long("tries")
+                .help(t!("help.luks_mount.tries").to_string())
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("3")
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help(t!("help.luks_mount.header").to_string())
+                .value_name("PATH")
+        )
+        .arg(
+            Arg::new("crypt_type")
+                .long("type")
+                .help(t!("help.luks_mount.crypt_type").to_string())
+                .value_name("TYPE")
+                .value_parser(["luks", "plain"])
+                .default_value("luks")
+        )
+        .arg(
+            Arg::new("cipher")
+                .long("cipher")
+                .help(t!("help.luks_mount.cipher").to_string())
+                .value_name("SPEC")
+                .required_if_eq("crypt_type", "plain")
+        )
+        .arg(
+            Arg::new("hash")
+                .long("hash")
+                .help(t!("help.luks_mount.hash").to_string())
+                .value_name("HASH")
+                .required_if_eq("crypt_type", "plain")
+        )
+        .arg(
+            Arg::new("plain_key_size")
+                .long("key-size")
+                .help(t!("help.luks_mount.plain_key_size").to_string())
+                .value_name("BITS")
+                .value_parser(clap::value_parser!(u32))
+                .required_if_eq("crypt_type", "plain")
+        )
+        .arg(
+            Arg::new("fsck")
+                .long("fsck")
+                .help(t!("help.luks_mount.fsck").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("fsck_repair")
+                .long("fsck-repair")
+                .help(t!("help.luks_mount.fsck_repair").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("allow_suid")
+                .long("allow-suid")
+                .help(t!("help.luks_mount.allow_suid").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("allow_dev")
+                .long("allow-dev")
+                .help(t!("help.luks_mount.allow_dev").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("allow_exec")
+                .long("allow-exec")
+                .help(t!("help.luks_mount.allow_exec").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("integrity_no_journal")
+                .long("integrity-no-journal")
+                .help(t!("help.luks_mount.integrity_no_journal").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("no_hardening")
+                .long("no-hardening")
+                .help(t!("help.luks_mount.no_hardening").to_string())
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["allow_suid", "allow_dev", "allow_exec"])
+        )
+        .arg(
+            Arg::new("loop")
+                .long("loop")
+                .help(t!("help.luks_mount.loop").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .help(t!("help.luks_mount.bind").to_string())
+                .value_name("SRC:DST")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("namespace")
+                .long("namespace")
+                .help(t!("help.luks_mount.namespace").to_string())
+                .value_name("PID")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .conflicts_with_all(["mkdir", "bind", "ensure", "force"])
+        )
+        .arg(
+            Arg::new("no_state")
+                .long("no-state")
+                .help(t!("help.luks_mount.no_state").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("print_crypttab")
+                .long("print-crypttab")
+                .help(t!("help.luks_mount.print_crypttab").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("print_fstab")
+                .long("print-fstab")
+                .help(t!("help.luks_mount.print_fstab").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_mount.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("wait_for_device")
+                .long("wait-for-device")
+                .help(t!("help.luks_mount.wait_for_device").to_string())
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help(t!("help.luks_mount.timeout").to_string())
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+        )
+        .arg(
+            Arg::new("debug_cryptsetup")
+                .long("debug-cryptsetup")
+                .help(t!("help.luks_mount.debug_cryptsetup").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("redact")
+                .long("redact")
+                .help(t!("help.luks_mount.redact").to_string())
+                .action(ArgAction::SetTrue)
+        )
+}
+
+/// Prompt for the passphrase without echo
+/// 
+/// The passphrase is moved into a SecretString right away and the raw
+/// buffer rpassword returned is zeroized (see `into_secret`).
+fn prompt_passphrase() -> Result<SecretString> {
+    let password_raw = rpassword::prompt_password(t!("luks_mount.enter_passphrase").to_string())
+        .context(t!("luks_mount.failed_read_password").to_string())?;
+    Ok(into_secret(password_raw))
+}
+
+/// Read the passphrase from stdin, the environment or the terminal
+fn read_password(password_stdin: bool, password_env: Option<&String>) -> Result<SecretString> {
+    if password_stdin {
+        read_passphrase_stdin()
+    } else if let Some(name) = password_env {
+        // Environment variables of a process are readable through /proc
+        warn!("{}", t!("luks_mount.warning_password_env", name = name));
+        read_passphrase_env(name)
+    } else {
+        prompt_passphrase()
+    }
+}
+
+/// Whether `entry`, mounted at `mount_point`, is the volume of `device`
+/// 
+/// The mounted mapper is traced back to its backing device through the
+/// state file, or `cryptsetup status` (and the loop device's backing file
+/// for containers) when there is none.
+fn is_mounted_from(entry: &MountEntry, mount_point: &Path, device: &Path) -> Result<bool> {
+    let Some(mapper_name) = entry.device.strip_prefix("/dev/mapper/") else {
+        return Ok(false);
+    };
+    let same = |path: &Path| {
+        path.canonicalize().ok().is_some_and(|path| Some(path) == device.canonicalize().ok())
+    };
+
+    if let Some(state) = get_mount_mapping(mount_point)? {
+        if state.mapper_name == mapper_name {
+            return Ok(same(&state.device));
+        }
+    }
+
+    let Ok(status) = luks_status(mapper_name) else {
+        return Ok(false);
+    };
+    Ok(status.device.is_some_and(|backing| {
+        same(&backing) || loop_backing_file(&backing).is_some_and(|file| same(&file))
+    }))
+}
+
+/// Mount the volume described by `matches`, or every volume of `--config`
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_mount.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(matches.get_flag("dry_run"));
+    if let Some(secs) = matches.get_one::<u64>("timeout") {
+        set_cryptsetup_timeout(Some(Duration::from_secs(*secs)));
+    }
+    set_redact(matches.get_flag("redact"));
+
+    // Verbose cryptsetup output is more revealing than our errors; opt-in only
+    if matches.get_flag("debug_cryptsetup") {
+        warn!("{}", t!("luks.warning_debug_cryptsetup"));
+        set_cryptsetup_debug(true);
+    }
+
+    if let Some(path) = matches.get_one::<String>("config") {
+        return run_config(matches, Path::new(path));
+    }
+
+    let volume = VolumeConfig {
+        device: matches.get_one::<String>("device").unwrap().clone(),
+        mount_point: PathBuf::from(matches.get_one::<String>("mount_point").unwrap()),
+        fs_type: matches.get_one::<String>("fs_type").cloned(),
+        options: matches.get_one::<String>("options").cloned(),
+        ro: matches.get_flag("ro"),
+        noatime: matches.get_flag("noatime"),
+        nodiratime: matches.get_flag("nodiratime"),
+        relatime: matches.get_flag("relatime"),
+        sync: matches.get_flag("sync"),
+        nofail: matches.get_flag("nofail"),
+        key_file: matches.get_many::<String>("key_file")
+            .map(|paths| paths.map(PathBuf::from).collect())
+            .unwrap_or_default(),
+    };
+    mount_volume(matches, &volume, None)
+}
+
+/// Mount every volume of a configuration file
+/// 
+/// Volumes are independent: one failing is reported and the rest are still
+/// mounted, and nothing already mounted is rolled back. Volumes without a
+/// key file share a single passphrase, asked for once.
+fn run_config(matches: &ArgMatches, path: &Path) -> Result<()> {
+    let config = load_config(path)?;
+    let needs_password = config.volumes.iter().any(|volume| volume.key_file.is_empty());
+    let shared_password = if needs_password && !matches.get_flag("dry_run") {
+        Some(read_password(matches.get_flag("password_stdin"), matches.get_one::<String>("password_env"))?)
+    } else {
+        None
+    };
+
+    let total = config.volumes.len();
+    let mut failed = 0;
+    for volume in &config.volumes {
+        let mount_point = volume.mount_point.display().to_string();
+        info!("\n{}", t!("luks_mount.config_volume", device = &volume.device, path = &mount_point));
+        match mount_volume(matches, volume, shared_password.as_ref()) {
+            Ok(()) => info!("{}", t!("luks_mount.config_volume_mounted", path = &mount_point)),
+            Err(e) => {
+                failed += 1;
+                warn!("{}", t!("luks_mount.config_volume_failed", path = &mount_point, error = format!("{:#}", e)));
+            }
+        }
+    }
+    // shared_password is automatically zeroized when dropped
+    drop(shared_password);
+
+    info!("\n{}", t!("luks_mount.config_summary", mounted = total - failed, total = total));
+    if failed > 0 {
+        bail!("{}", t!("luks_mount.config_failed", failed = failed, total = total));
+    }
+    Ok(())
+}
+
+/// Open and mount a single volume
+/// 
+/// `volume` holds the device, mount point and mount options; everything
+/// else comes from the command line. With `shared_password`, it is used
+/// instead of reading a passphrase when the volume has no key file.
+fn mount_volume(matches: &ArgMatches, volume: &VolumeConfig, shared_password: Option<&SecretString>) -> Result<()> {
+    let device_arg = &volume.device;
+    let mount_point = volume.mount_point.clone();
+    register_device(device_arg);
+    register_mount_point(&mount_point);
+    let mkdir = matches.get_flag("mkdir");
+    // Ownership and mode of a mount point created by --mkdir; resolved up
+    // front so a typo fails before anything is changed
+    let dir_owner = matches.get_one::<String>("owner").map(|user| resolve_user(user)).transpose()?;
+    let dir_group = matches.get_one::<String>("group").map(|group| resolve_group(group)).transpose()?;
+    let dir_mode_arg = matches.get_one::<String>("mode");
+    let dir_mode = dir_mode_arg.map(|mode| parse_dir_mode(mode)).transpose()?
+        .unwrap_or(DEFAULT_MOUNT_POINT_MODE);
+    let has_dir_ownership = dir_owner.is_some() || dir_group.is_some() || dir_mode_arg.is_some();
+    let force = matches.get_flag("force");
+    let ensure = matches.get_flag("ensure");
+    let namespace = matches.get_one::<u32>("namespace").copied();
+    // A read-only mapping can only be mounted read-only
+    let crypt_ro = matches.get_flag("crypt_ro");
+    let ro = volume.ro || crypt_ro;
+    let rw = matches.get_flag("rw");
+    let fs_type = volume.fs_type.clone();
+    let options = volume.options.clone();
+    let key_files = volume.key_file.clone();
+    let key_slot = matches.get_one::<u8>("key_slot").copied();
+    let password_stdin = matches.get_flag("password_stdin");
+    let password_env = matches.get_one::<String>("password_env");
+    let header = matches.get_one::<String>("header").map(PathBuf::from);
+    let fsck_repair = matches.get_flag("fsck_repair");
+    let fsck = matches.get_flag("fsck") || fsck_repair;
+    let hardening = if matches.get_flag("no_hardening") {
+        HardeningProfile::permissive()
+    } else {
+        HardeningProfile {
+            nosuid: !matches.get_flag("allow_suid"),
+            nodev: !matches.get_flag("allow_dev"),
+            noexec: !matches.get_flag("allow_exec"),
+            ..HardeningProfile::secure()
+        }
+    };
+    let dry_run = matches.get_flag("dry_run");
+    let mapper_name_arg = matches.get_one::<String>("name")
+        .map(|name| custom_mapper_name(name))
+        .transpose()?;
+    let tries = *matches.get_one::<u32>("tries").unwrap();
+    let wait_for_device = matches.get_one::<u64>("wait_for_device").map(|secs| Duration::from_secs(*secs));
+    let loop_flag = matches.get_flag("loop");
+    let no_state = matches.get_flag("no_state");
+    let print_crypttab = matches.get_flag("print_crypttab");
+    let print_fstab = matches.get_flag("print_fstab");
+    // Plain dm-crypt has no header: every parameter must be spelled out
+    let plain_options = if matches.get_one::<String>("crypt_type").is_some_and(|t| t == "plain") {
+        if !key_files.is_empty() || key_slot.is_some() || header.is_some() || print_crypttab
+            || matches.get_flag("integrity_no_journal")
+        {
+            bail!("{}", t!("luks_mount.plain_unsupported_option"));
+        }
+        warn!("{}", t!("luks_mount.warning_plain_mode"));
+        Some(PlainOpenOptions {
+            cipher: matches.get_one::<String>("cipher").unwrap().clone(),
+            hash: matches.get_one::<String>("hash").unwrap().clone(),
+            key_size: *matches.get_one::<u32>("plain_key_size").unwrap(),
+            read_only: crypt_ro,
+        })
+    } else {
+        if ["cipher", "hash", "plain_key_size"].iter().any(|id| matches.contains_id(id)) {
+            bail!("{}", t!("luks_mount.plain_options_need_type"));
+        }
+        None
+    };
+    let bind_specs = matches.get_many::<String>("bind")
+        .unwrap_or_default()
+        .map(|spec| parse_bind_spec(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Resolve UUID=/LABEL= specifications to a device path, giving a slow
+    // device (e.g. USB at boot) time to appear if asked to
+    let device = match wait_for_device {
+        Some(wait) => wait_for_device_spec(device_arg, wait)?,
+        None => resolve_device_spec(device_arg)?,
+    };
+    if is_device_spec(device_arg) {
+        info!("{}", t!("device.resolved", spec = device_arg, path = device.display().to_string()));
+    }
+
+    // Validate device path is absolute, without traversal attempts
+    ensure_safe_absolute(&device)?;
+
+    // Check if device exists
+    if !device.exists() {
+        return Err(LuksError::DeviceNotFound { path: device }.into());
+    }
+
+    // Work with the real device node from here on (e.g. /dev/sda1 rather
+    // than a /dev/disk/by-uuid symlink) so state matches /proc/mounts
+    let device = device.canonicalize()
+        .with_context(|| t!("luks_mount.failed_canonicalize_device", path = device.display().to_string()).to_string())?;
+    register_device(&device);
+    let device_input = Some(device_arg.clone()).filter(|input| Path::new(input) != device);
+    if let Some(ref input) = device_input {
+        debug!("{}", t!("luks_mount.canonical_device", input = input, path = device.display().to_string()));
+    }
+
+    // Container files are attached to a loop device once we're about to
+    // open them; everything else must already be a LUKS device
+    let use_loop = loop_flag || device.is_file();
+    if use_loop {
+        validate_container_file(&device)?;
+    } else if plain_options.is_none() && !is_luks_device(&device, header.as_deref())? {
+        return Err(LuksError::NotLuks { path: device }.into());
+    }
+
+    // Validate mount point path
+    ensure_safe_absolute(&mount_point)?;
+
+    // Recorded so luks_umount --rmdir knows the directory is ours to remove
+    let mut created_dir = false;
+
+    // In another mount namespace the mount point only exists there;
+    // mount_device checks it as that namespace sees it
+    if let Some(pid) = namespace {
+        mount_namespace_path(pid)?;
+        warn!("{}", t!("luks_mount.warning_namespace", pid = pid));
+    } else {
+        // Create mount point if --mkdir is specified
+        let will_create = mkdir && !mount_point.exists();
+        let pending_mkdir = will_create && dry_run;
+        if pending_mkdir {
+            println!("{}", t!("luks_mount.dry_run_mkdir", path = mount_point.display().to_string()));
+        } else if will_create {
+            fs::create_dir_all(&mount_point)
+                .context(t!("errors.failed_create_mount_dir").to_string())?;
+            // Set secure permissions on created directory (0755 unless --mode)
+            fs::set_permissions(&mount_point, fs::Permissions::from_mode(dir_mode))
+                .context(t!("errors.failed_set_permissions").to_string())?;
+            if dir_owner.is_some() || dir_group.is_some() {
+                chown(&mount_point, dir_owner, dir_group)
+                    .context(t!("errors.failed_set_owner").to_string())?;
+            }
+            info!("{}", t!("luks_mount.created_mount_point", path = mount_point.display().to_string()));
+            created_dir = true;
+        } else if has_dir_ownership {
+            // An existing directory is never modified
+            warn!("{}", t!("luks_mount.warning_ownership_ignored"));
+        }
+
+        // Check if mount point exists
+        if !mount_point.exists() && !pending_mkdir {
+            bail!("{}", t!("luks_mount.mount_point_not_exist", path = mount_point.display().to_string()));
+        }
+
+        // Check if mount point is a directory
+        if mount_point.exists() && !mount_point.is_dir() {
+            bail!("{}", t!("luks_mount.mount_point_not_dir", path = mount_point.display().to_string()));
+        }
+
+        // Bind targets must already exist
+        for (_, target) in &bind_specs {
+            if !target.is_dir() {
+                bail!("{}", t!("luks_mount.bind_target_not_dir", path = target.display().to_string()));
+            }
+        }
+
+        // Refuse an occupied mount point before prompting or opening anything
+        let canonical_mount_point = mount_point.canonicalize()
+            .unwrap_or_else(|_| mount_point.clone());
+        register_mount_point(&canonical_mount_point);
+        if let Some(entry) = find_mount_entry(&canonical_mount_point)? {
+            // --ensure: the right volume already being there is success
+            if ensure {
+                if is_mounted_from(&entry, &canonical_mount_point, &device)? {
+                    debug!("{}", t!("luks_mount.ensure_already_mounted",
+                        path = canonical_mount_point.display().to_string()));
+                    return Ok(());
+                }
+                bail!("{}", t!("luks_mount.ensure_other_device",
+                    path = canonical_mount_point.display().to_string(),
+                    device = &entry.device));
+            }
+            bail!("{}", t!("luks_mount.already_mounted",
+                path = canonical_mount_point.display().to_string(),
+                device = &entry.device,
+                fs_type = &entry.fs_type));
+        }
+
+        // Mounting over existing files hides them; only do so when asked to
+        if !pending_mkdir && !is_dir_empty(&mount_point)? {
+            if !force {
+                bail!("{}", t!("luks_mount.mount_point_not_empty", path = mount_point.display().to_string()));
+            }
+            warn!("{}", t!("luks_mount.warning_mount_point_not_empty", path = mount_point.display().to_string()));
+        }
+    }
+
+    info!("{}", t!("luks_mount.opening_luks_device", path = device.display().to_string()));
+
+    // Read the passphrase up front so the state lock is never held while
+    // waiting on the user
+    let mut password = if !key_files.is_empty() {
+        // Unlock with the key files; no interactive prompt
+        let paths: Vec<String> = key_files.iter().map(|path| path.display().to_string()).collect();
+        info!("{}", t!("luks_mount.using_key_file", path = paths.join(", ")));
+        None
+    } else if dry_run {
+        // The passphrase is never sent anywhere in dry-run, so don't ask for it
+        Some(SecretString::from(String::new()))
+    } else if let Some(shared) = shared_password {
+        Some(SecretString::from(shared.expose_secret().to_string()))
+    } else {
+        Some(read_password(password_stdin, password_env)?)
+    };
+    // Only a passphrase typed at the prompt for this volume can be asked for again
+    let interactive = key_files.is_empty() && shared_password.is_none() && !password_stdin && password_env.is_none();
+
+    // Attach a container file to a loop device and open that instead
+    let loop_device = if use_loop {
+        let loop_device = attach_loop(&device, ro)?;
+        register_device(&loop_device);
+        info!("{}", t!("luks_mount.attached_loop",
+            path = device.display().to_string(), loop_device = loop_device.display().to_string()));
+        // The loop device is only a placeholder in dry-run
+        if !dry_run && plain_options.is_none() && !is_luks_device(&loop_device, header.as_deref())? {
+            let _ = detach_loop(&loop_device);
+            return Err(LuksError::NotLuks { path: device }.into());
+        }
+        Some(loop_device)
+    } else {
+        None
+    };
+
+    // Prepare open and mount options
+    let open_options = LuksOpenOptions {
+        key_slot,
+        header: header.clone(),
+        read_only: crypt_ro,
+        integrity_no_journal: matches.get_flag("integrity_no_journal"),
+    };
+    // Without the journal a crash can leave sectors that fail verification
+    if open_options.integrity_no_journal {
+        warn!("{}", t!("luks_mount.warning_integrity_no_journal"));
+    }
+
+    let mount_options = MountOptions {
+        read_only: ro,
+        fs_type,
+        options,
+        flags: volume.mount_flags(),
+        hardening,
+        namespace,
+    };
+
+    // --rw is a promise of a writable mount; `-o ro` would break it
+    if rw && mount_options.has_user_option("ro") {
+        bail!("{}", t!("luks_mount.rw_conflicts_ro_option"));
+    }
+
+    // Make weakened hardening impossible to miss
+    if mount_options.hardening == HardeningProfile::permissive() {
+        warn!("{}", t!("luks_mount.warning_no_hardening"));
+    } else {
+        if !mount_options.hardening.nosuid {
+            warn!("{}", t!("luks_mount.warning_allow_suid"));
+        }
+        if !mount_options.hardening.nodev {
+            warn!("{}", t!("luks_mount.warning_allow_dev"));
+        }
+    }
+
+    let open_mount_options = OpenMountOptions {
+        mapper_name: mapper_name_arg,
+        key_files: key_files.clone(),
+        open: open_options,
+        plain: plain_options,
+        mount: mount_options.clone(),
+        fsck,
+        fsck_repair,
+        binds: bind_specs,
+        loop_device: loop_device.clone(),
+        device_input,
+        store_state: !no_state,
+        created_dir,
+    };
+
+    // --no-state touches nothing in the state directory, not even the lock.
+    // A rejected passphrase is asked for again, up to --tries times; any
+    // other failure ends the loop straight away
+    let mut attempt = 1;
+    let result = loop {
+        let result = open_and_mount(&device, &mount_point, password.as_ref(), &open_mount_options);
+        let wrong_passphrase = matches!(
+            result.as_ref().err().and_then(|e| e.downcast_ref::<LuksError>()),
+            Some(LuksError::WrongPassphrase)
+        );
+        if !wrong_passphrase || !interactive || attempt >= tries {
+            break result;
+        }
+
+        warn!("{}", t!("luks_mount.wrong_passphrase_retry", attempt = attempt, tries = tries));
+        attempt += 1;
+        // Replacing the rejected passphrase drops, and so zeroizes, it
+        match prompt_passphrase() {
+            Ok(retry) => password = Some(retry),
+            Err(e) => break Err(e),
+        }
+    };
+    let (mapper_name, used_key_file) = match result {
+        Ok(mounted) => (mounted.mapper_name, mounted.key_file),
+        Err(e) => {
+            if let Some(ref loop_device) = loop_device {
+                let _ = detach_loop(loop_device);
+            }
+            return Err(e);
+        }
+    };
+    // password is automatically zeroized when dropped
+    drop(password);
+
+    // Without a state file luks_umount can't know about the loop device, so
+    // detach it now: the kernel defers this until the mapper is closed
+    if no_state {
+        if let Some(ref loop_device) = loop_device {
+            if let Err(e) = detach_loop(loop_device) {
+                warn!("{}", e);
+            }
+        }
+        info!("{}", t!("luks_mount.no_state_note"));
+    }
+
+    if dry_run {
+        info!("\n{}", t!("luks_mount.dry_run_complete"));
+        return Ok(());
+    }
+
+    info!("\n{}", t!("luks_mount.success_mounted"));
+    info!("{}", t!("luks_mount.label_device", path = device.display().to_string()));
+    info!("{}", t!("luks_mount.label_mount_point", path = mount_point.display().to_string()));
+    info!("{}", t!("luks_mount.label_mapper", name = &mapper_name));
+    let hardening = mount_options.hardening_options();
+    if hardening.is_empty() {
+        info!("{}", t!("luks_mount.label_security_disabled"));
+    } else {
+        info!("{}", t!("luks_mount.label_security", options = hardening.join(",")));
+    }
+    // The effective mode is always shown, so logs say how the volume is mounted
+    if crypt_ro {
+        info!("{}", t!("luks_mount.label_mode_crypt_readonly"));
+    } else if mount_options.read_only || mount_options.has_user_option("ro") {
+        info!("{}", t!("luks_mount.label_mode_readonly"));
+    } else {
+        info!("{}", t!("luks_mount.label_mode_readwrite"));
+    }
+
+    // Suggest persistent configuration; nothing is written
+    if print_crypttab {
+        let source = match loop_device {
+            Some(_) => device.display().to_string(),
+            None => device_uuid(&device).ok().flatten()
+                .map(|uuid| format!("UUID={}", uuid))
+                .unwrap_or_else(|| device.display().to_string()),
+        };
+        info!("\n{}", t!("luks_mount.suggested_crypttab"));
+        println!("{}", to_crypttab_line(&mapper_name, &source, used_key_file.as_deref(), header.as_deref(), ro));
+    }
+    if print_fstab {
+        let fs_type = match mount_options.fs_type {
+            Some(ref fs_type) => Some(fs_type.clone()),
+            None => detect_fs_type(&get_mapper_path(&mapper_name)).ok().flatten(),
+        };
+        info!("\n{}", t!("luks_mount.suggested_fstab"));
+        println!("{}", to_fstab_line(&mapper_name, &mount_point, fs_type.as_deref(), &mount_options)?);
+    }
+
+    Ok(())
+}
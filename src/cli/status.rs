@@ -0,0 +1,40 @@
+//! `luks_status` / `luksctl status`: report the state of one volume
+//!
+//! Reconciles the luksctl state file, /proc/mounts and `cryptsetup status`
+//! for a mount point or mapper name and reports any inconsistencies.
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgMatches, Command};
+use rust_i18n::t;
+
+use crate::status::{mapping_status, print_status, OutputFormat};
+
+/// Add the `status` arguments to `cmd`
+/// 
+/// `--json` is not included: it is added by the caller so `luksctl` can
+/// offer it on the top-level command.
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("target")
+                .help(t!("help.luks_status.target").to_string())
+                .required(true)
+                .index(1)
+        )
+}
+
+/// Report the status of the volume named in `matches`
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let target = matches.get_one::<String>("target").unwrap();
+    let format = OutputFormat::from_json_flag(matches.get_flag("json"));
+
+    // Check if running as root (state files and dm status are root-only)
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_status.program_must_root"));
+    }
+
+    let status = mapping_status(target)?;
+    print_status(&status, format)?;
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+//! `luks_umount` / `luksctl umount`: unmount a volume and lock its device
+//!
+//! Validates the command line, confirms a lazy unmount and hands the
+//! teardown of bind mounts, filesystem, mapper and loop device to
+//! [`unmount_and_close_with`].
+
+use anyhow::{bail, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use log::{info, warn};
+use rust_i18n::t;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::command::{is_dry_run, set_dry_run};
+use crate::luks::{set_cryptsetup_debug, set_cryptsetup_timeout};
+use crate::mapper::{get_mapper_path, get_mount_mapping, list_mappings, resolve_mapper_name};
+use crate::mount::is_mounted;
+use crate::pathsafe::{ensure_absolute, ensure_no_null};
+use crate::redact::{register_device, register_mount_point, set_redact};
+use crate::volume::{unmount_and_close_with, UnmountCloseOptions};
+
+/// Confirm that `--force` may lazily unmount `mount_point`
+/// 
+/// A lazy unmount detaches the tree while processes may still be writing
+/// into it, so it is only done after a warning and an explicit yes: either
+/// `--yes`, or an answer at the terminal.
+fn confirm_lazy_unmount(mount_point: &Path, assume_yes: bool) -> Result<()> {
+    warn!("{}", t!("luks_umount.warning_lazy_unmount", path = mount_point.display().to_string()));
+    if assume_yes || is_dry_run() {
+        return Ok(());
+    }
+    
+    if !std::io::stdin().is_terminal() {
+        bail!("{}", t!("luks_umount.force_needs_yes"));
+    }
+    
+    eprint!("{}", t!("luks_umount.confirm_lazy_unmount", path = mount_point.display().to_string()));
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        bail!("{}", t!("luks_umount.force_aborted"));
+    }
+    
+    Ok(())
+}
+
+/// Add the `umount` arguments to `cmd`
+pub fn args(cmd: Command) -> Command {
+    cmd
+        .arg(
+            Arg::new("mount_point")
+                .help(t!("help.luks_umount.mount_point").to_string())
+                .required_unless_present("all")
+                .index(1)
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help(t!("help.luks_umount.all").to_string())
+                .action(ArgAction::SetTrue)
+                .conflicts_with("mount_point")
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help(t!("help.luks_umount.force").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help(t!("help.luks_umount.yes").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("retry")
+                .long("retry")
+                .help(t!("help.luks_umount.retry").to_string())
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("no_sync")
+                .long("no-sync")
+                .help(t!("help.luks_umount.no_sync").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("keep_open")
+                .long("keep-open")
+                .visible_alias("umount-only")
+                .help(t!("help.luks_umount.keep_open").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("owner_only")
+                .long("owner-only")
+                .help(t!("help.luks_umount.owner_only").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("rmdir")
+                .long("rmdir")
+                .help(t!("help.luks_umount.rmdir").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("if_mounted")
+                .long("if-mounted")
+                .help(t!("help.luks_umount.if_mounted").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .help(t!("help.luks_umount.dry_run").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help(t!("help.luks_umount.timeout").to_string())
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64))
+        )
+        .arg(
+            Arg::new("debug_cryptsetup")
+                .long("debug-cryptsetup")
+                .help(t!("help.luks_umount.debug_cryptsetup").to_string())
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("redact")
+                .long("redact")
+                .help(t!("help.luks_umount.redact").to_string())
+                .action(ArgAction::SetTrue)
+        )
+}
+
+/// Order mount points so nested mounts come before their parents
+/// 
+/// Deeper mount points go first; ties are broken by path, in reverse, so the
+/// order is stable.
+fn unmount_order(mut mount_points: Vec<PathBuf>) -> Vec<PathBuf> {
+    mount_points.sort_by(|a, b| {
+        b.components().count().cmp(&a.components().count()).then_with(|| b.cmp(a))
+    });
+    mount_points
+}
+
+/// Unmount and lock the volume described by `matches`, or every volume
+/// with a state file for `--all`
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let dry_run = matches.get_flag("dry_run");
+    let timeout = matches.get_one::<u64>("timeout").map(|secs| Duration::from_secs(*secs));
+
+    // Check if running as root
+    if !nix::unistd::Uid::effective().is_root() {
+        bail!("{}", t!("luks_umount.program_must_root"));
+    }
+
+    // Only print the commands that change system state from here on
+    set_dry_run(dry_run);
+    if let Some(timeout) = timeout {
+        set_cryptsetup_timeout(Some(timeout));
+    }
+    set_redact(matches.get_flag("redact"));
+
+    // Verbose cryptsetup output is more revealing than our errors; opt-in only
+    if matches.get_flag("debug_cryptsetup") {
+        warn!("{}", t!("luks.warning_debug_cryptsetup"));
+        set_cryptsetup_debug(true);
+    }
+
+    if matches.get_flag("all") {
+        return run_all(matches);
+    }
+
+    let mount_point = PathBuf::from(matches.get_one::<String>("mount_point").unwrap());
+    unmount_volume(matches, &mount_point, matches.get_flag("if_mounted"))
+}
+
+/// Unmount and lock every volume recorded in the state directory
+/// 
+/// Nested mounts are unmounted before the volumes they live on. A failing
+/// volume is reported and the rest are still unmounted; volumes whose
+/// mount point is already gone only have their teardown finished.
+fn run_all(matches: &ArgMatches) -> Result<()> {
+    let mount_points = unmount_order(
+        list_mappings()?.into_iter().map(|(mount_point, _, _)| mount_point).collect(),
+    );
+    if mount_points.is_empty() {
+        info!("{}", t!("luks_umount.all_nothing_mounted"));
+        return Ok(());
+    }
+
+    let total = mount_points.len();
+    let mut failed = Vec::new();
+    for mount_point in &mount_points {
+        info!("\n{}", t!("luks_umount.all_volume", path = mount_point.display().to_string()));
+        if let Err(e) = unmount_volume(matches, mount_point, true) {
+            warn!("{}", t!("luks_umount.all_volume_failed",
+                path = mount_point.display().to_string(), error = format!("{:#}", e)));
+            failed.push(mount_point);
+        }
+    }
+
+    info!("\n{}", t!("luks_umount.all_summary", unmounted = total - failed.len(), total = total));
+    if !failed.is_empty() {
+        let paths: Vec<String> = failed.iter().map(|path| path.display().to_string()).collect();
+        bail!("{}", t!("luks_umount.all_failed", count = failed.len(), paths = paths.join(", ")));
+    }
+    Ok(())
+}
+
+/// Unmount and lock the volume mounted at `mount_point_arg`
+/// 
+/// With `if_mounted`, a mount point that is no longer mounted is not an
+/// error: whatever teardown is left is finished instead.
+fn unmount_volume(matches: &ArgMatches, mount_point_arg: &Path, if_mounted: bool) -> Result<()> {
+    let force = matches.get_flag("force");
+    let assume_yes = matches.get_flag("yes");
+    let no_sync = matches.get_flag("no_sync");
+    let keep_open = matches.get_flag("keep_open");
+    let owner_only = matches.get_flag("owner_only");
+    let rmdir = matches.get_flag("rmdir");
+    let retries = *matches.get_one::<u32>("retry").unwrap();
+    let dry_run = matches.get_flag("dry_run");
+
+    // Validate mount point path is absolute, without null bytes; `..` is
+    // resolved by canonicalization below
+    ensure_absolute(mount_point_arg)?;
+    ensure_no_null(mount_point_arg)?;
+
+    // Canonicalize the mount point path (resolves symlinks, removes ..)
+    let mount_point = mount_point_arg.canonicalize()
+        .unwrap_or_else(|_| mount_point_arg.to_path_buf());
+    register_mount_point(mount_point_arg);
+    register_mount_point(&mount_point);
+
+    // Double-check after canonicalization
+    ensure_absolute(&mount_point)?;
+
+    let state = get_mount_mapping(&mount_point)?;
+    if let Some(ref state) = state {
+        register_device(&state.device);
+        if let Some(ref loop_device) = state.loop_device {
+            register_device(loop_device);
+        }
+    }
+
+    // Ask before anything is torn down, so declining leaves it all mounted
+    if force && is_mounted(&mount_point)? {
+        confirm_lazy_unmount(&mount_point, assume_yes)?;
+    }
+
+    // Looked up before the unmount, while /proc/mounts still shows it
+    let kept_mapper = if keep_open { resolve_mapper_name(&mount_point)? } else { None };
+
+    unmount_and_close_with(&mount_point, &UnmountCloseOptions {
+        force,
+        retries,
+        sync: !no_sync,
+        if_mounted,
+        keep_open,
+        owner_only,
+        // With --all, only the mount points luksctl created are removed
+        rmdir: rmdir && (!matches.get_flag("all") || state.as_ref().is_some_and(|state| state.created_dir)),
+        ..Default::default()
+    })?;
+
+    if dry_run {
+        info!("\n{}", t!("luks_umount.dry_run_complete"));
+        return Ok(());
+    }
+
+    info!("\n{}", t!("luks_umount.success_unmounted"));
+    info!("{}", t!("luks_umount.label_mount_point", path = mount_point.display().to_string()));
+    if let Some(mapper_name) = kept_mapper {
+        // Printed even with --quiet: acting on the open mapper is the point
+        println!("{}", get_mapper_path(&mapper_name).display());
+        info!("{}", t!("luks_umount.kept_open_note", name = &mapper_name));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmount_order_nested_first() {
+        let order = unmount_order(vec![
+            PathBuf::from("/mnt/data"),
+            PathBuf::from("/mnt/data/archive/2024"),
+            PathBuf::from("/srv"),
+            PathBuf::from("/mnt/data/archive"),
+            PathBuf::from("/mnt/backup"),
+        ]);
+        assert_eq!(order, [
+            PathBuf::from("/mnt/data/archive/2024"),
+            PathBuf::from("/mnt/data/archive"),
+            PathBuf::from("/mnt/data"),
+            PathBuf::from("/mnt/backup"),
+            PathBuf::from("/srv"),
+        ]);
+    }
+}
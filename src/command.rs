@@ -0,0 +1,311 @@
+//! External command execution helpers
+//!
+//! Every external program luksctl runs goes through [`run`], which hands the
+//! fully-built `Command` to a [`CommandRunner`]. Production code uses
+//! [`SystemRunner`]; tests substitute a runner that records the arguments
+//! instead of spawning anything.
+//!
+//! Commands can be given a timeout; a child still running when it expires
+//! is killed and an error is returned instead of blocking forever. Commands
+//! run inside [`with_progress`] show a spinner while they are waited for.
+//!
+//! This module also holds the process-wide dry-run switch. In dry-run mode
+//! the functions that change system state build their command exactly as
+//! they normally would, print it, and return success without running it.
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use rust_i18n::t;
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::LuksError;
+use crate::progress::Spinner;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// How often a child with a timeout is polled for exit
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    /// Runner override for the current thread (used by tests)
+    static RUNNER: RefCell<Option<Rc<dyn CommandRunner>>> = const { RefCell::new(None) };
+
+    /// Label of the spinner shown while waiting for commands (see [`with_progress`])
+    static PROGRESS: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Executes a fully-constructed command
+pub trait CommandRunner {
+    /// Run `cmd` to completion, writing `input` to its stdin if given, and
+    /// capture its output
+    /// 
+    /// With a `timeout`, the child is killed and an error returned if it
+    /// has not exited in time.
+    fn run(&self, cmd: &mut Command, input: Option<&[u8]>, timeout: Option<Duration>) -> Result<Output>;
+}
+
+/// Runs commands as real child processes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, cmd: &mut Command, input: Option<&[u8]>, timeout: Option<Duration>) -> Result<Output> {
+        if input.is_none() && timeout.is_none() {
+            let _spinner = progress_spinner();
+            return cmd.output().map_err(|err| spawn_error(cmd, err));
+        }
+
+        let mut child = cmd
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| spawn_error(cmd, err))?;
+
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            if let Err(err) = stdin.write_all(input) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err).context(t!("command.failed_write_stdin").to_string());
+            }
+            // stdin is dropped here, closing the pipe
+        }
+
+        // Only now: any passphrase has been read and handed over
+        let _spinner = progress_spinner();
+        match timeout {
+            Some(timeout) => wait_with_timeout(child, cmd, timeout),
+            None => child.wait_with_output()
+                .context(t!("command.failed_wait").to_string()),
+        }
+    }
+}
+
+/// Turn a failure to start `cmd` into an error
+/// 
+/// A program that doesn't exist becomes [`LuksError::ToolNotFound`], so it
+/// reads as a missing installation rather than a generic failure.
+fn spawn_error(cmd: &Command, err: std::io::Error) -> anyhow::Error {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        return LuksError::tool_not_found(Path::new(cmd.get_program())).into();
+    }
+    err.into()
+}
+
+/// Start the spinner for the current [`with_progress`] label, if any
+fn progress_spinner() -> Option<Spinner> {
+    PROGRESS.with(|progress| progress.borrow().as_deref().and_then(Spinner::start))
+}
+
+/// Wait for `child` for at most `timeout`, collecting its output
+/// 
+/// stdout and stderr are drained on helper threads so a chatty child
+/// cannot block on a full pipe while we poll for its exit.
+fn wait_with_timeout(mut child: Child, cmd: &Command, timeout: Duration) -> Result<Output> {
+    fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    }
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().context(t!("command.failed_wait").to_string())? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!(
+                "{}",
+                t!("command.timed_out", command = format_command(cmd), secs = timeout.as_secs_f32())
+            );
+        }
+        thread::sleep(TIMEOUT_POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Run a command with the current thread's runner
+/// 
+/// Uses [`SystemRunner`] unless a runner was installed with [`with_runner`].
+pub fn run(cmd: &mut Command, input: Option<&[u8]>) -> Result<Output> {
+    run_with_timeout(cmd, input, None)
+}
+
+/// Run a command like [`run`], killing it if it outlives `timeout`
+pub fn run_with_timeout(cmd: &mut Command, input: Option<&[u8]>, timeout: Option<Duration>) -> Result<Output> {
+    debug!("{}", t!("command.running", command = format_command(cmd)));
+    match RUNNER.with(|runner| runner.borrow().clone()) {
+        Some(runner) => runner.run(cmd, input, timeout),
+        None => SystemRunner.run(cmd, input, timeout),
+    }
+}
+
+/// Run `f` with `runner` executing all commands on this thread
+pub fn with_runner<T>(runner: Rc<dyn CommandRunner>, f: impl FnOnce() -> T) -> T {
+    let previous = RUNNER.with(|current| current.replace(Some(runner)));
+    let result = f();
+    RUNNER.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Run `f`, showing a spinner labelled `message` while any command it
+/// runs on this thread is waited for
+pub fn with_progress<T>(message: impl Into<String>, f: impl FnOnce() -> T) -> T {
+    let previous = PROGRESS.with(|progress| progress.replace(Some(message.into())));
+    let result = f();
+    PROGRESS.with(|progress| *progress.borrow_mut() = previous);
+    result
+}
+
+/// Enable or disable dry-run mode for the whole process
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether commands are only printed instead of executed
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Quote an argument for display if it would not survive a shell as-is
+fn quote_arg(arg: &str) -> String {
+    let safe = !arg.is_empty() && arg.chars().all(|c| {
+        c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.' | ',' | '=' | ':' | '@' | '+')
+    });
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Render a command line for display
+///
+/// Only the program and its arguments are shown; data written to stdin
+/// (such as passphrases) is never part of the rendered command.
+pub fn format_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| quote_arg(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Print the command that would be executed in dry-run mode
+pub fn print_dry_run(cmd: &Command) {
+    println!("{}", t!("command.dry_run", command = format_command(cmd)));
+}
+
+/// A command recorded by [`MockRunner`]
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub input: Option<Vec<u8>>,
+}
+
+/// Test runner that records commands and reports a fixed exit code
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct MockRunner {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub commands: RefCell<Vec<RecordedCommand>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    /// Run `f` with this mock installed and return everything it executed
+    pub fn capture<T>(self, f: impl FnOnce() -> T) -> (T, Vec<RecordedCommand>) {
+        let runner = Rc::new(self);
+        let result = with_runner(runner.clone(), f);
+        let commands = runner.commands.borrow().clone();
+        (result, commands)
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockRunner {
+    fn run(&self, cmd: &mut Command, input: Option<&[u8]>, _timeout: Option<Duration>) -> Result<Output> {
+        use std::os::unix::process::ExitStatusExt;
+
+        self.commands.borrow_mut().push(RecordedCommand {
+            // Tools run by absolute path; record just the program name
+            program: std::path::Path::new(cmd.get_program())
+                .file_name()
+                .unwrap_or(cmd.get_program())
+                .to_string_lossy()
+                .into_owned(),
+            args: cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            input: input.map(<[u8]>::to_vec),
+        });
+
+        Ok(Output {
+            status: std::process::ExitStatus::from_raw(self.exit_code << 8),
+            stdout: self.stdout.clone().into_bytes(),
+            stderr: self.stderr.clone().into_bytes(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_command_quotes_unsafe_args() {
+        let mut cmd = Command::new("mount");
+        cmd.args(["-o", "nosuid,nodev", "/dev/mapper/luks-test", "/mnt/my data", "it's"]);
+        assert_eq!(
+            format_command(&cmd),
+            "mount -o nosuid,nodev /dev/mapper/luks-test '/mnt/my data' 'it'\\''s'"
+        );
+    }
+
+    #[test]
+    fn test_system_runner_reports_missing_program() {
+        let err = SystemRunner.run(&mut Command::new("/nonexistent/cryptsetup"), None, None).unwrap_err();
+        let missing = err.downcast_ref::<LuksError>();
+        assert!(matches!(missing, Some(LuksError::ToolNotFound { program, .. }) if program == "cryptsetup"));
+
+        // Context added on the way up doesn't hide it from library callers
+        let wrapped = LuksError::from(err.context("Failed to execute cryptsetup command"));
+        assert!(matches!(wrapped, LuksError::ToolNotFound { .. }));
+        assert_eq!(wrapped.exit_code(), 6);
+    }
+
+    #[test]
+    fn test_system_runner_kills_on_timeout() {
+        let start = Instant::now();
+        let result = SystemRunner.run(Command::new("sleep").arg("10"), None, Some(Duration::from_millis(100)));
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let output = SystemRunner
+            .run(Command::new("cat").arg("-"), Some(b"data"), Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(output.stdout, b"data");
+    }
+}
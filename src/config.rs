@@ -0,0 +1,209 @@
+//! Multi-volume mount configuration
+//!
+//! This module reads the TOML file given to `luks_mount --config`, a list of
+//! volumes mounted independently of each other:
+//!
+//! ```toml
+//! [[volume]]
+//! device = "UUID=0f3c..."
+//! mount_point = "/mnt/data"
+//! fs_type = "ext4"
+//! options = "noatime"
+//! ro = false
+//! key_file = "/etc/luks/data.key"
+//! ```
+//!
+//! `key_file` may also be a list, tried in order until one unlocks the
+//! volume: `key_file = ["/etc/luks/new.key", "/etc/luks/old.key"]`.
+//!
+//! Every field is validated as the corresponding command-line argument is,
+//! before anything is mounted.
+
+use anyhow::{bail, Context, Result};
+use rust_i18n::t;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::device::is_device_spec;
+use crate::mount::{validate_fs_type, MountFlags, MountOptions};
+use crate::pathsafe::ensure_safe_absolute;
+
+/// The contents of a mount configuration file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MountConfig {
+    #[serde(rename = "volume", default)]
+    pub volumes: Vec<VolumeConfig>,
+}
+
+/// One volume to open and mount
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeConfig {
+    /// Device path, or a `UUID=`/`LABEL=` specification
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: Option<String>,
+    /// Additional mount options, as for `--options`
+    pub options: Option<String>,
+    #[serde(default)]
+    pub ro: bool,
+    /// Mount options with their own flag, as for `--noatime` and so on
+    #[serde(default)]
+    pub noatime: bool,
+    #[serde(default)]
+    pub nodiratime: bool,
+    #[serde(default)]
+    pub relatime: bool,
+    #[serde(default)]
+    pub sync: bool,
+    #[serde(default)]
+    pub nofail: bool,
+    /// Key files unlocking this volume, tried in order; the shared
+    /// passphrase is used without any
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub key_file: Vec<PathBuf>,
+}
+
+/// Deserialize a single value or a list of them into a list
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// Whether a path is absolute and free of `..` components and null bytes
+fn is_safe_absolute(path: &Path) -> bool {
+    ensure_safe_absolute(path).is_ok()
+}
+
+impl VolumeConfig {
+    /// The mount flags set for this volume
+    pub fn mount_flags(&self) -> MountFlags {
+        MountFlags {
+            noatime: self.noatime,
+            nodiratime: self.nodiratime,
+            relatime: self.relatime,
+            sync: self.sync,
+            nofail: self.nofail,
+        }
+    }
+
+    /// Validate every field as the command line would
+    fn validate(&self) -> Result<()> {
+        if !is_device_spec(&self.device) && !is_safe_absolute(Path::new(&self.device)) {
+            bail!("{}", t!("config.invalid_device", device = &self.device));
+        }
+        if !is_safe_absolute(&self.mount_point) {
+            bail!("{}", t!("config.invalid_mount_point", path = self.mount_point.display().to_string()));
+        }
+        if let Some(ref fs_type) = self.fs_type {
+            validate_fs_type(fs_type)?;
+        }
+        // Builds the option string the mount would use, which rejects
+        // malformed and contradictory options
+        MountOptions {
+            read_only: self.ro,
+            fs_type: self.fs_type.clone(),
+            options: self.options.clone(),
+            flags: self.mount_flags(),
+            ..Default::default()
+        }.option_string()?;
+        for key_file in &self.key_file {
+            if !is_safe_absolute(key_file) {
+                bail!("{}", t!("config.invalid_key_file", path = key_file.display().to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse and validate a mount configuration
+///
+/// # Security
+/// - Unknown keys are rejected so a misspelled option is never ignored
+/// - Mount points may not repeat, so no volume hides another
+pub fn parse_config(content: &str) -> Result<MountConfig> {
+    let config: MountConfig = toml::from_str(content)?;
+    if config.volumes.is_empty() {
+        bail!("{}", t!("config.no_volumes"));
+    }
+    for (index, volume) in config.volumes.iter().enumerate() {
+        volume.validate()
+            .with_context(|| t!("config.invalid_volume", index = index + 1).to_string())?;
+        if config.volumes[..index].iter().any(|other| other.mount_point == volume.mount_point) {
+            bail!("{}", t!("config.duplicate_mount_point", path = volume.mount_point.display().to_string()));
+        }
+    }
+    Ok(config)
+}
+
+/// Read, parse and validate a mount configuration file
+pub fn load_config(path: &Path) -> Result<MountConfig> {
+    let content = fs::read_to_string(path)
+        .with_context(|| t!("config.failed_read", path = path.display().to_string()).to_string())?;
+    parse_config(&content)
+        .with_context(|| t!("config.failed_parse", path = path.display().to_string()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(r#"
+            [[volume]]
+            device = "/dev/sdb1"
+            mount_point = "/mnt/data"
+            fs_type = "ext4"
+            options = "noatime"
+
+            [[volume]]
+            device = "UUID=0f3c2d1e-1111-2222-3333-444455556666"
+            mount_point = "/mnt/backup"
+            ro = true
+            key_file = "/etc/luks/backup.key"
+        "#).unwrap();
+        assert_eq!(config.volumes.len(), 2);
+        assert_eq!(config.volumes[0].fs_type.as_deref(), Some("ext4"));
+        assert!(!config.volumes[0].ro);
+        assert!(config.volumes[1].ro);
+        assert!(config.volumes[0].key_file.is_empty());
+        assert_eq!(config.volumes[1].key_file, [Path::new("/etc/luks/backup.key")]);
+
+        let volume = |fields: &str| parse_config(&format!("[[volume]]\n{}", fields));
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"relative\"").is_err());
+        assert!(volume("device = \"/dev/../sdb1\"\nmount_point = \"/mnt/data\"").is_err());
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nfs_type = \"proc\"").is_err());
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\noptions = \"noatime;reboot\"").is_err());
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nro = true\noptions = \"rw\"").is_err());
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nkey_file = \"data.key\"").is_err());
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nkey_file = [\"/etc/a.key\", \"b.key\"]").is_err());
+        let rotating = volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nkey_file = [\"/etc/new.key\", \"/etc/old.key\"]").unwrap();
+        assert_eq!(rotating.volumes[0].key_file, [Path::new("/etc/new.key"), Path::new("/etc/old.key")]);
+        assert!(volume("device = \"/dev/sdb1\"\nmount_point = \"/mnt/data\"\nreadonly = true").is_err());
+        assert!(parse_config("").is_err());
+        assert!(parse_config(r#"
+            [[volume]]
+            device = "/dev/sdb1"
+            mount_point = "/mnt/data"
+            [[volume]]
+            device = "/dev/sdc1"
+            mount_point = "/mnt/data"
+        "#).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Block device specification resolution
+//!
+//! This module turns user-supplied device specifications into validated
+//! device paths with security hardening:
+//! - `UUID=<uuid>` and `LABEL=<label>` are resolved via blkid
+//! - Symlinks under /dev (`/dev/disk/by-id/...`) are resolved to the node
+//! - Resolution must end at a single block device under /dev/
+//! - Ambiguous specifications are rejected rather than guessed
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command::run;
+use crate::error::LuksError;
+use crate::luks::validate_device_path;
+use crate::pathsafe::ensure_safe_absolute;
+use crate::tools::{tool_command, Tool};
+
+/// Directory whose symlinks are resolved to the device node they name
+const DEV_DIR: &str = "/dev";
+
+/// Maximum length of a UUID or label value
+const MAX_SPEC_VALUE_LEN: usize = 256;
+
+/// How often to look for a device that hasn't appeared yet
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Look up the UUID of a device with blkid
+/// 
+/// For a LUKS device this is the LUKS header UUID. Returns `Ok(None)` if
+/// blkid reports no UUID.
+/// 
+/// # Security
+/// - Device path is validated before being passed to blkid
+pub fn device_uuid(device: &Path) -> Result<Option<String>> {
+    validate_device_path(device)?;
+
+    let mut cmd = tool_command(Tool::Blkid);
+    cmd.args(["-s", "UUID", "-o", "value"]).arg(device);
+    let output = run(&mut cmd, None)
+        .context(t!("device.failed_execute_blkid").to_string())?;
+
+    let uuid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || uuid.is_empty() {
+        return Ok(None);
+    }
+
+    validate_uuid(&uuid)?;
+    Ok(Some(uuid))
+}
+
+/// Look for an existing signature (filesystem, LUKS header, RAID member,
+/// swap, ...) on a device
+/// 
+/// Runs `blkid -p`, which probes the device itself rather than trusting the
+/// udev cache, and returns the reported type, or `Ok(None)` if nothing was
+/// found.
+/// 
+/// # Security
+/// - Device path is validated before being passed to blkid
+pub fn device_signature(device: &Path) -> Result<Option<String>> {
+    validate_device_path(device)?;
+
+    let mut cmd = tool_command(Tool::Blkid);
+    cmd.args(["-p", "-s", "TYPE", "-o", "value"]).arg(device);
+    let output = run(&mut cmd, None)
+        .context(t!("device.failed_execute_blkid").to_string())?;
+
+    // blkid exits with 2 when no signature is found
+    let signature = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || signature.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(signature))
+}
+
+/// Resolve a symlink under /dev to the device node it points at
+/// 
+/// Stable names such as `/dev/disk/by-id/...` are symlinks that udev
+/// repoints; the node they name right now is what gets passed to
+/// cryptsetup and recorded in the state file. Other paths, including ones
+/// that don't exist yet and container files elsewhere, are returned
+/// unchanged.
+/// 
+/// # Security
+/// - The target must be a block device under /dev, checked with the same
+///   validation as `luks_open`
+pub fn canonical_device_path(device: &Path) -> Result<PathBuf> {
+    ensure_safe_absolute(device)?;
+    if !device.starts_with(DEV_DIR) || !device.is_symlink() {
+        return Ok(device.to_path_buf());
+    }
+
+    resolve_device_symlink(device)
+}
+
+/// Resolve a device symlink and validate the node it leads to
+fn resolve_device_symlink(link: &Path) -> Result<PathBuf> {
+    resolve_device_symlink_with(link, |target| Ok(validate_device_path(target)?))
+}
+
+/// [`resolve_device_symlink`] with the validation of the target injected
+fn resolve_device_symlink_with(link: &Path, validate: impl FnOnce(&Path) -> Result<()>) -> Result<PathBuf> {
+    let Ok(target) = link.canonicalize() else {
+        return Err(LuksError::DeviceNotFound { path: link.to_path_buf() }.into());
+    };
+    validate(&target)?;
+    Ok(target)
+}
+
+/// Resolve a device specification to a device path
+/// 
+/// Accepts an absolute device path, `UUID=<uuid>` or `LABEL=<label>`.
+/// Symlinks under /dev are resolved to their node and other paths are
+/// returned unchanged; UUID and LABEL specifications are looked up with
+/// `blkid` and must match exactly one device.
+/// 
+/// # Security
+/// - UUID and label values are validated before being passed to blkid
+/// - The resolved device is validated with the same checks as `luks_open`
+pub fn resolve_device_spec(spec: &str) -> Result<PathBuf> {
+    let (tag, value) = if let Some(uuid) = spec.strip_prefix("UUID=") {
+        validate_uuid(uuid)?;
+        ("UUID", uuid)
+    } else if let Some(label) = spec.strip_prefix("LABEL=") {
+        validate_label(label)?;
+        ("LABEL", label)
+    } else {
+        return canonical_device_path(Path::new(spec));
+    };
+
+    let mut cmd = tool_command(Tool::Blkid);
+    cmd.arg("-t")
+        .arg(format!("{}={}", tag, value))
+        .args(["-o", "device"]);
+    let output = run(&mut cmd, None)
+        .context(t!("device.failed_execute_blkid").to_string())?;
+
+    // blkid exits with 2 when nothing matches
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let devices: Vec<&str> = stdout.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let device = match devices.as_slice() {
+        [] => bail!("{}", t!("device.spec_not_found", spec = spec)),
+        [device] => PathBuf::from(device),
+        _ => bail!("{}", t!("device.spec_ambiguous", spec = spec, devices = devices.join(", "))),
+    };
+
+    validate_device_path(&device)?;
+
+    canonical_device_path(&device)
+}
+
+/// Resolve a device specification, waiting up to `timeout` for the device
+/// to appear
+/// 
+/// UUID and LABEL specifications are looked up again on every poll, since
+/// blkid only finds a device once it is present. When the timeout elapses,
+/// the result of the last attempt is returned: an error for a specification
+/// that still doesn't resolve, or the path of a device that still doesn't
+/// exist, for the caller to report.
+pub fn wait_for_device_spec(spec: &str, timeout: Duration) -> Result<PathBuf> {
+    let deadline = Instant::now() + timeout;
+    let mut announced = false;
+    
+    loop {
+        let result = resolve_device_spec(spec);
+        let present = result.as_ref().is_ok_and(|device| device.exists());
+        if present || Instant::now() >= deadline {
+            return result;
+        }
+        
+        if !announced {
+            info!("{}", t!("device.waiting_for_device", spec = spec, secs = timeout.as_secs()));
+            announced = true;
+        }
+        thread::sleep(DEVICE_POLL_INTERVAL);
+    }
+}
+
+/// Check whether a device argument is a UUID= or LABEL= specification
+pub fn is_device_spec(spec: &str) -> bool {
+    spec.starts_with("UUID=") || spec.starts_with("LABEL=")
+}
+
+/// Validate a filesystem/LUKS UUID value
+fn validate_uuid(uuid: &str) -> Result<()> {
+    if uuid.is_empty() || uuid.len() > MAX_SPEC_VALUE_LEN {
+        bail!("{}", t!("device.invalid_uuid", uuid = uuid));
+    }
+
+    // LUKS and most filesystems use hex digits and dashes (e.g. FAT: ABCD-1234)
+    if !uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        bail!("{}", t!("device.invalid_uuid", uuid = uuid));
+    }
+
+    Ok(())
+}
+
+/// Validate a filesystem label value
+fn validate_label(label: &str) -> Result<()> {
+    if label.is_empty() || label.len() > MAX_SPEC_VALUE_LEN {
+        bail!("{}", t!("device.invalid_label"));
+    }
+
+    // Labels are passed to blkid as a single token; refuse anything that could
+    // break its NAME=value parsing or escape /dev/disk/by-label
+    if label.chars().any(|c| c.is_control() || c == '/' || c == '"') {
+        bail!("{}", t!("device.invalid_label"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_value_validation() {
+        assert!(validate_uuid("0b4c5a7e-1d2f-4c3b-9a8e-7f6d5c4b3a21").is_ok());
+        assert!(validate_uuid("ABCD-1234").is_ok());
+        assert!(validate_uuid("").is_err());
+        assert!(validate_uuid("../../etc").is_err());
+        assert!(validate_label("backup disk").is_ok());
+        assert!(validate_label("a/b").is_err());
+        assert!(validate_label("x\"y").is_err());
+    }
+
+    #[test]
+    fn test_device_symlink_resolves_to_node() {
+        let dir = std::env::temp_dir().join(format!("luksctl-by-id-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let link = |name: &str, target: &str| {
+            let path = dir.join(name);
+            let _ = std::fs::remove_file(&path);
+            std::os::unix::fs::symlink(target, &path).unwrap();
+            path
+        };
+
+        // A by-id style link resolves through a chain of links to the node,
+        // which is what gets validated
+        let node = dir.join("loop-node");
+        std::fs::write(&node, "").unwrap();
+        let by_id = link("usb-Disk_1234-0:0", &link("by-path", &node.to_string_lossy()).to_string_lossy());
+        let mut validated = None;
+        let resolved = resolve_device_symlink_with(&by_id, |target| {
+            validated = Some(target.to_path_buf());
+            Ok(())
+        }).unwrap();
+        assert_eq!(resolved, node.canonicalize().unwrap());
+        assert_eq!(validated, Some(resolved));
+        assert!(resolve_device_symlink_with(&by_id, |_| Err(anyhow::anyhow!("not a block device"))).is_err());
+
+        // Links leading out of /dev, to a non-device or nowhere are refused
+        assert!(resolve_device_symlink(&link("to-file", "/etc/passwd")).is_err());
+        assert!(resolve_device_symlink(&link("to-dev-dir", "/dev")).is_err());
+        assert!(resolve_device_symlink(&link("dangling", "/dev/nonexistent-luksctl")).is_err());
+
+        // Paths outside /dev and plain nodes are left as they are
+        assert_eq!(canonical_device_path(&dir.join("to-file")).unwrap(), dir.join("to-file"));
+        assert_eq!(canonical_device_path(Path::new("/dev/null")).unwrap(), PathBuf::from("/dev/null"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,487 @@
+//! Environment diagnostics
+//!
+//! `luksctl doctor` checks what luksctl depends on and prints a checklist
+//! that can be pasted into a bug report:
+//! - The cryptsetup version, and whether it is new enough for LUKS2
+//! - Whether the dm-crypt, dm-integrity and TPM2 kernel support is present
+//! - Whether `mount`, `umount` and the other tools are installed
+//! - Whether the state directory can be written by the current user
+//!
+//! Every check runs even if an earlier one failed. A failed check means
+//! luksctl cannot work; a warning means an optional feature is unavailable.
+//!
+//! As root, it also compares the state files with the open mappers and the
+//! mount table, which is how leftovers from a crash are found. Nothing is
+//! changed unless [`fix_mappings`] is called (`--fix`).
+
+use anyhow::{bail, Context, Result};
+use nix::unistd::{access, AccessFlags};
+use rust_i18n::t;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::command::run;
+use crate::loopdev::{detach_loop, is_loop_attached};
+use crate::luks::luks_close;
+use crate::mapper::{
+    find_state_by_mapper, get_mapper_path, list_managed_mappers, list_mappings, mapper_exists,
+    remove_mount_mapping, state_dir, with_state_lock,
+};
+use crate::mount::{find_mount_by_device, is_mounted};
+use crate::status::OutputFormat;
+use crate::tools::{tool_available, tool_command, tool_path, Tool};
+
+/// Tools luksctl cannot work without, besides cryptsetup
+const REQUIRED_TOOLS: [Tool; 3] = [Tool::Mount, Tool::Umount, Tool::Blkid];
+/// Tools only some commands use
+const OPTIONAL_TOOLS: [Tool; 5] = [Tool::Losetup, Tool::Fsck, Tool::Resize2fs, Tool::XfsGrowfs, Tool::Nsenter];
+/// Device nodes of a TPM2, resource manager first
+const TPM_DEVICES: [&str; 2] = ["/dev/tpmrm0", "/dev/tpm0"];
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// An optional feature is unavailable
+    Warn,
+    /// luksctl cannot work until this is fixed
+    Fail,
+}
+
+/// One line of the checklist
+///
+/// `name` and `detail` are locale-independent and make up the JSON output;
+/// `message` is the localized line shown to people.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: Option<String>,
+    #[serde(skip)]
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, detail: Option<String>, message: String) -> Self {
+        Self { name: name.to_string(), status, detail, message }
+    }
+}
+
+/// Health of one luksctl mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingHealth {
+    /// State file, mapper and mount all agree
+    Healthy,
+    /// State file exists but its mapper is gone
+    StaleState,
+    /// Mapper is open but nothing is mounted from it
+    OrphanedMapper,
+    /// Mapper is mounted but no state file records it
+    MountedNoState,
+}
+
+/// One mapping found by [`diagnose_mappings`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MappingDiagnosis {
+    pub mapper_name: String,
+    /// From the state file, or from the mount table if there is none
+    pub mount_point: Option<PathBuf>,
+    pub health: MappingHealth,
+    /// Whether [`fix_mappings`] repaired it
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub fixed: bool,
+}
+
+/// Everything `luksctl doctor` found
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+    /// `None` when the mappings could not be inspected (not running as root)
+    pub mappings: Option<Vec<MappingDiagnosis>>,
+}
+
+/// Run every check, in checklist order
+pub fn run_checks() -> Vec<Check> {
+    let mut checks = cryptsetup_checks();
+    checks.push(module_check("dm_crypt", CheckStatus::Fail));
+    checks.push(module_check("dm_integrity", CheckStatus::Warn));
+    checks.push(tpm2_check());
+    checks.extend(REQUIRED_TOOLS.iter().map(|&tool| tool_check(tool, CheckStatus::Fail)));
+    checks.extend(OPTIONAL_TOOLS.iter().map(|&tool| tool_check(tool, CheckStatus::Warn)));
+    checks.push(state_dir_check());
+    checks
+}
+
+/// Extract the version from `cryptsetup --version` output
+///
+/// The output looks like `cryptsetup 2.6.1 flags: UDEV BLKID ...`; older
+/// releases print only the first two words.
+pub fn parse_cryptsetup_version(output: &str) -> Option<String> {
+    let mut words = output.split_whitespace();
+    if words.next()? != "cryptsetup" {
+        return None;
+    }
+    let version = words.next()?;
+    version.starts_with(|c: char| c.is_ascii_digit()).then(|| version.to_string())
+}
+
+/// Whether a cryptsetup version supports LUKS2 (2.0 and later)
+fn supports_luks2(version: &str) -> bool {
+    version.split('.').next()
+        .and_then(|major| major.parse::<u32>().ok())
+        .is_some_and(|major| major >= 2)
+}
+
+/// Check the cryptsetup version and, from it, LUKS2 support
+fn cryptsetup_checks() -> Vec<Check> {
+    let path = tool_path(Tool::Cryptsetup).display().to_string();
+    let version = run(tool_command(Tool::Cryptsetup).arg("--version"), None).ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_cryptsetup_version(&String::from_utf8_lossy(&output.stdout)));
+
+    let Some(version) = version else {
+        return vec![Check::new("cryptsetup", CheckStatus::Fail, Some(path.clone()),
+            t!("doctor.cryptsetup_fail", path = &path, var = Tool::Cryptsetup.env_var()).to_string())];
+    };
+
+    let luks2 = if supports_luks2(&version) {
+        Check::new("luks2", CheckStatus::Ok, None, t!("doctor.luks2_ok").to_string())
+    } else {
+        Check::new("luks2", CheckStatus::Warn, None, t!("doctor.luks2_warn", version = &version).to_string())
+    };
+    vec![
+        Check::new("cryptsetup", CheckStatus::Ok, Some(version.clone()),
+            t!("doctor.cryptsetup_ok", version = &version, path = &path).to_string()),
+        luks2,
+    ]
+}
+
+/// Whether a kernel module is loaded or built in
+///
+/// Both show up under /sys/module once the code is in the kernel.
+fn module_present(module: &str) -> bool {
+    Path::new("/sys/module").join(module).exists()
+}
+
+/// Check for a device-mapper target, reporting `missing` when it is absent
+///
+/// A module that isn't loaded yet may still be loaded on demand, so the
+/// message says so rather than claiming the feature is unavailable.
+fn module_check(module: &str, missing: CheckStatus) -> Check {
+    let label = module.replace('_', "-");
+    if module_present(module) {
+        Check::new(module, CheckStatus::Ok, None, t!("doctor.module_ok", module = &label).to_string())
+    } else {
+        Check::new(module, missing, None, t!("doctor.module_missing", module = &label).to_string())
+    }
+}
+
+/// Check for a TPM2 device node
+fn tpm2_check() -> Check {
+    match TPM_DEVICES.iter().find(|device| Path::new(device).exists()) {
+        Some(device) => Check::new("tpm2", CheckStatus::Ok, Some(device.to_string()),
+            t!("doctor.tpm2_ok", path = device).to_string()),
+        None => Check::new("tpm2", CheckStatus::Warn, None, t!("doctor.tpm2_missing").to_string()),
+    }
+}
+
+/// Check that `tool` is installed, reporting `missing` when it is not
+fn tool_check(tool: Tool, missing: CheckStatus) -> Check {
+    let path = tool_path(tool).display().to_string();
+    if tool_available(tool) {
+        Check::new(tool.name(), CheckStatus::Ok, Some(path.clone()),
+            t!("doctor.tool_ok", tool = tool.name(), path = &path).to_string())
+    } else {
+        Check::new(tool.name(), missing, None,
+            t!("doctor.tool_missing", tool = tool.name(), var = tool.env_var()).to_string())
+    }
+}
+
+/// Check that the state directory, or the directory it will be created in,
+/// is writable
+fn state_dir_check() -> Check {
+    let dir = match state_dir() {
+        Ok(dir) => dir,
+        Err(e) => return Check::new("state_dir", CheckStatus::Fail, None, e.to_string()),
+    };
+    let path = dir.display().to_string();
+
+    // The directory is created on first mount; until then its parent counts
+    let existing = dir.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(Path::new("/"));
+    let writable = access(existing, AccessFlags::W_OK | AccessFlags::X_OK).is_ok();
+    let (status, key) = match (writable, existing == dir) {
+        (true, true) => (CheckStatus::Ok, "doctor.state_dir_ok"),
+        (true, false) => (CheckStatus::Ok, "doctor.state_dir_creatable"),
+        (false, _) => (CheckStatus::Fail, "doctor.state_dir_not_writable"),
+    };
+    Check::new("state_dir", status, Some(path.clone()), t!(key, path = &path).to_string())
+}
+
+/// Classify a mapping from what the state file, dm and the mount table say
+///
+/// Returns `None` for a mapping none of them knows about.
+fn classify(has_state: bool, active: bool, mounted: bool) -> Option<MappingHealth> {
+    match (has_state, active, mounted) {
+        (true, false, _) => Some(MappingHealth::StaleState),
+        (_, true, false) => Some(MappingHealth::OrphanedMapper),
+        (true, true, true) => Some(MappingHealth::Healthy),
+        (false, true, true) => Some(MappingHealth::MountedNoState),
+        (false, false, _) => None,
+    }
+}
+
+/// Compare the state files with the open mappers and the mount table
+///
+/// Every state file is checked against its mapper and mount point; then
+/// every open luksctl mapper without a state file is checked against the
+/// mount table. Nothing is changed.
+pub fn diagnose_mappings() -> Result<Vec<MappingDiagnosis>> {
+    let mut diagnoses = Vec::new();
+    let mut recorded = HashSet::new();
+
+    for (mount_point, mapper_name, _) in list_mappings()? {
+        let active = mapper_exists(&mapper_name);
+        let mounted = is_mounted(&mount_point)?;
+        if let Some(health) = classify(true, active, mounted) {
+            diagnoses.push(MappingDiagnosis {
+                mapper_name: mapper_name.clone(),
+                mount_point: Some(mount_point),
+                health,
+                fixed: false,
+            });
+        }
+        recorded.insert(mapper_name);
+    }
+
+    for mapper_name in list_managed_mappers()? {
+        if recorded.contains(&mapper_name) {
+            continue;
+        }
+        let entry = find_mount_by_device(&get_mapper_path(&mapper_name))?;
+        if let Some(health) = classify(false, true, entry.is_some()) {
+            diagnoses.push(MappingDiagnosis {
+                mapper_name,
+                mount_point: entry.map(|entry| entry.mount_point),
+                health,
+                fixed: false,
+            });
+        }
+    }
+
+    Ok(diagnoses)
+}
+
+/// Repair what [`diagnose_mappings`] found
+///
+/// Stale state files are removed. Orphaned mappers are closed only with
+/// `close_orphans`: a mapper with nothing mounted may still be in use,
+/// e.g. as a VM disk. Mounted volumes without state are left alone, since
+/// unmounting is the user's call. The loop device a state file records is
+/// detached before the state goes, as nothing else remembers it. Repaired
+/// entries are marked `fixed`.
+pub fn fix_mappings(diagnoses: &mut [MappingDiagnosis], close_orphans: bool) -> Result<()> {
+    fix_mappings_with(diagnoses, close_orphans, is_loop_attached)
+}
+
+/// [`fix_mappings`] with the check for an attached loop device injected
+fn fix_mappings_with(
+    diagnoses: &mut [MappingDiagnosis],
+    close_orphans: bool,
+    is_attached: impl Fn(&Path) -> bool,
+) -> Result<()> {
+    for diagnosis in diagnoses.iter_mut() {
+        match diagnosis.health {
+            MappingHealth::StaleState => {}
+            MappingHealth::OrphanedMapper if close_orphans => luks_close(&diagnosis.mapper_name)?,
+            _ => continue,
+        }
+        with_state_lock(|| {
+            let Some(state) = find_state_by_mapper(&diagnosis.mapper_name)? else {
+                return Ok(());
+            };
+            if let Some(loop_device) = state.loop_device.as_deref().filter(|device| is_attached(device)) {
+                detach_loop(loop_device)?;
+            }
+            remove_mount_mapping(&state.mount_point)?;
+            Ok::<_, anyhow::Error>(())
+        })?;
+        diagnosis.fixed = true;
+    }
+
+    Ok(())
+}
+
+/// Localized description of a mapping's health
+fn describe_mapping(diagnosis: &MappingDiagnosis) -> String {
+    let name = &diagnosis.mapper_name;
+    let path = diagnosis.mount_point.as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+    let description = match diagnosis.health {
+        MappingHealth::Healthy => t!("doctor.mapping_healthy", name = name, path = &path),
+        MappingHealth::StaleState => t!("doctor.mapping_stale_state", name = name, path = &path),
+        MappingHealth::OrphanedMapper => t!("doctor.mapping_orphaned", name = name),
+        MappingHealth::MountedNoState => t!("doctor.mapping_mounted_no_state", name = name, path = &path),
+    };
+    if diagnosis.fixed {
+        format!("{} ({})", description, t!("doctor.mapping_fixed"))
+    } else {
+        description.to_string()
+    }
+}
+
+/// Checklist mark for a status
+fn mark(status: CheckStatus) -> &'static str {
+    match status {
+        CheckStatus::Ok => "✓",
+        CheckStatus::Warn => "⚠",
+        CheckStatus::Fail => "✗",
+    }
+}
+
+/// Write the report to stdout in the requested format
+///
+/// Fails if any check failed, so scripts can rely on the exit status.
+/// Mapping problems are only reported; they don't stop luksctl working.
+pub fn print_report(report: &DoctorReport, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(report)
+            .context(t!("status.failed_serialize").to_string())?;
+        println!("{}", json);
+    } else {
+        println!("{}", t!("doctor.header", version = env!("CARGO_PKG_VERSION")));
+        for check in &report.checks {
+            println!("  [{}] {}", mark(check.status), check.message);
+        }
+
+        println!("\n{}", t!("doctor.mappings_header"));
+        match report.mappings {
+            None => println!("  [{}] {}", mark(CheckStatus::Warn), t!("doctor.mappings_need_root")),
+            Some(ref mappings) if mappings.is_empty() => println!("  {}", t!("status.no_mappings")),
+            Some(ref mappings) => {
+                for diagnosis in mappings {
+                    let status = if diagnosis.health == MappingHealth::Healthy || diagnosis.fixed {
+                        CheckStatus::Ok
+                    } else {
+                        CheckStatus::Warn
+                    };
+                    println!("  [{}] {}", mark(status), describe_mapping(diagnosis));
+                }
+                if mappings.iter().any(|d| d.health != MappingHealth::Healthy && !d.fixed) {
+                    println!("\n{}", t!("doctor.fix_hint"));
+                }
+            }
+        }
+    }
+
+    let failed = report.checks.iter().filter(|check| check.status == CheckStatus::Fail).count();
+    if failed > 0 {
+        bail!("{}", t!("doctor.failed_checks", count = failed));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+    use crate::mapper::{get_mount_mapping, store_mount_mapping, with_state_dir, MountState, STATE_VERSION};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_parse_cryptsetup_version() {
+        let output = "cryptsetup 2.6.1 flags: UDEV BLKID KEYRING FIPS KERNEL_CAPI PWQUALITY\n";
+        assert_eq!(parse_cryptsetup_version(output).as_deref(), Some("2.6.1"));
+        assert_eq!(parse_cryptsetup_version("cryptsetup 1.7.5\n").as_deref(), Some("1.7.5"));
+        assert_eq!(parse_cryptsetup_version(""), None);
+        assert_eq!(parse_cryptsetup_version("veritysetup 2.6.1"), None);
+        assert_eq!(parse_cryptsetup_version("cryptsetup: unknown option"), None);
+    }
+
+    #[test]
+    fn test_supports_luks2() {
+        assert!(supports_luks2("2.0.0"));
+        assert!(supports_luks2("2.6.1"));
+        assert!(!supports_luks2("1.7.5"));
+        assert!(!supports_luks2("garbage"));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(true, true, true), Some(MappingHealth::Healthy));
+        assert_eq!(classify(true, false, false), Some(MappingHealth::StaleState));
+        assert_eq!(classify(true, false, true), Some(MappingHealth::StaleState));
+        assert_eq!(classify(true, true, false), Some(MappingHealth::OrphanedMapper));
+        assert_eq!(classify(false, true, false), Some(MappingHealth::OrphanedMapper));
+        assert_eq!(classify(false, true, true), Some(MappingHealth::MountedNoState));
+        assert_eq!(classify(false, false, false), None);
+    }
+
+    #[test]
+    fn test_fix_stale_state_detaches_loop_device() {
+        let state_dir = std::env::temp_dir().join(format!("luksctl-doctor-state-{}", std::process::id()));
+        std::fs::create_dir_all(&state_dir).unwrap();
+        std::fs::set_permissions(&state_dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let mount_point = PathBuf::from("/mnt/luksctl-doctor-test");
+
+        with_state_dir(state_dir.clone(), || {
+            store_mount_mapping(&MountState {
+                version: STATE_VERSION,
+                mapper_name: "luks-doctor-test".to_string(),
+                device: PathBuf::from("/dev/loop7"),
+                device_input: None,
+                mount_point: mount_point.clone(),
+                header: None,
+                mount_options: None,
+                loop_device: Some(PathBuf::from("/dev/loop7")),
+                binds: Vec::new(),
+                crypt_read_only: false,
+                fs_type: None,
+                mounted_at: None,
+                uid: None,
+                owner: None,
+                created_dir: false,
+            }).unwrap();
+
+            let mut diagnoses = [MappingDiagnosis {
+                mapper_name: "luks-doctor-test".to_string(),
+                mount_point: Some(mount_point.clone()),
+                health: MappingHealth::StaleState,
+                fixed: false,
+            }];
+            let (result, commands) = MockRunner::default()
+                .capture(|| fix_mappings_with(&mut diagnoses, false, |_| true));
+
+            result.unwrap();
+            assert_eq!(commands.len(), 1);
+            assert_eq!(commands[0].program, "losetup");
+            assert_eq!(commands[0].args, ["--detach", "/dev/loop7"]);
+            assert!(get_mount_mapping(&mount_point).unwrap().is_none());
+            assert!(diagnoses[0].fixed);
+        });
+
+        std::fs::remove_dir_all(&state_dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_field_names_are_stable() {
+        let check = Check::new("luks2", CheckStatus::Warn, None, "localized".to_string());
+        let json = serde_json::to_value(&check).unwrap();
+        assert_eq!(json, serde_json::json!({"name": "luks2", "status": "warn", "detail": null}));
+
+        let diagnosis = MappingDiagnosis {
+            mapper_name: "luks-test".to_string(),
+            mount_point: Some(PathBuf::from("/mnt/test")),
+            health: MappingHealth::StaleState,
+            fixed: false,
+        };
+        let json = serde_json::to_value(&diagnosis).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "mapper_name": "luks-test", "mount_point": "/mnt/test", "health": "stale_state"
+        }));
+    }
+}
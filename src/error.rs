@@ -0,0 +1,167 @@
+//! Error type for library consumers
+//!
+//! The public functions in [`crate::luks`], [`crate::mount`] and
+//! [`crate::mapper`] return [`LuksError`], so callers can tell a wrong
+//! passphrase from a missing device by matching on the variant instead of
+//! parsing localized messages. Failures without a dedicated variant are
+//! carried in [`LuksError::Other`]. Every variant displays the same
+//! localized message the binaries print.
+
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+
+/// Result type returned by the luks, mount and mapper modules
+pub type Result<T, E = LuksError> = std::result::Result<T, E>;
+
+/// Errors returned by the luks, mount and mapper modules
+#[derive(Debug, thiserror::Error)]
+pub enum LuksError {
+    /// cryptsetup rejected the passphrase or key file
+    #[error("{}", t!("luks.failed_open_luks_incorrect"))]
+    WrongPassphrase,
+
+    /// The device (or mapper device) does not exist
+    #[error("{}", t!("luks.device_not_exist", path = .path.display().to_string()))]
+    DeviceNotFound { path: PathBuf },
+
+    /// The device does not contain a LUKS header
+    #[error("{}", t!("luks.device_not_luks", path = .path.display().to_string()))]
+    NotLuks { path: PathBuf },
+
+    /// The filesystem could not be unmounted because it is in use
+    ///
+    /// `blockers` lists the `(pid, command name)` of processes found using
+    /// the mount.
+    #[error("{message}")]
+    MountBusy {
+        mount_point: PathBuf,
+        blockers: Vec<(u32, String)>,
+        message: String,
+    },
+
+    /// The mapper name failed validation
+    #[error("{reason}")]
+    InvalidMapperName { name: String, reason: String },
+
+    /// No mount table could be read (/proc is not mounted)
+    #[error("{}", t!("mount.mount_table_unavailable"))]
+    MountTableUnavailable,
+
+    /// An external command ran but exited unsuccessfully
+    #[error("{message}")]
+    CommandFailed {
+        program: String,
+        stderr: String,
+        message: String,
+    },
+
+    /// An external program is not installed where luksctl looked for it
+    #[error("{message}")]
+    ToolNotFound {
+        program: String,
+        path: PathBuf,
+        message: String,
+    },
+
+    /// Any other failure
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for LuksError {
+    /// A missing tool keeps its own variant, whatever context was added
+    /// around it on the way up
+    fn from(err: anyhow::Error) -> Self {
+        match err.root_cause().downcast_ref::<LuksError>() {
+            Some(LuksError::ToolNotFound { program, path, message }) => LuksError::ToolNotFound {
+                program: program.clone(),
+                path: path.clone(),
+                message: message.clone(),
+            },
+            _ => LuksError::Other(err),
+        }
+    }
+}
+
+impl LuksError {
+    /// Process exit status reported by the binaries for this error
+    /// 
+    /// | Code | Meaning |
+    /// |------|---------|
+    /// | 1 | Any other failure |
+    /// | 2 | Wrong passphrase or key file |
+    /// | 3 | Device not found |
+    /// | 4 | Mount point busy |
+    /// | 5 | Device is not LUKS |
+    /// | 6 | A required tool is not installed |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            LuksError::WrongPassphrase => 2,
+            LuksError::DeviceNotFound { .. } => 3,
+            LuksError::MountBusy { .. } => 4,
+            LuksError::NotLuks { .. } => 5,
+            LuksError::ToolNotFound { .. } => 6,
+            _ => 1,
+        }
+    }
+
+    /// Name of the variant, as reported by `--error-json`
+    /// 
+    /// Stable across releases and locales, unlike the message.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LuksError::WrongPassphrase => "WrongPassphrase",
+            LuksError::DeviceNotFound { .. } => "DeviceNotFound",
+            LuksError::NotLuks { .. } => "NotLuks",
+            LuksError::MountBusy { .. } => "MountBusy",
+            LuksError::InvalidMapperName { .. } => "InvalidMapperName",
+            LuksError::MountTableUnavailable => "MountTableUnavailable",
+            LuksError::CommandFailed { .. } => "CommandFailed",
+            LuksError::ToolNotFound { .. } => "ToolNotFound",
+            LuksError::Other(_) => "Other",
+        }
+    }
+
+    /// Build a [`LuksError::InvalidMapperName`] with a localized reason
+    pub(crate) fn invalid_mapper_name(name: &str, reason: impl Into<String>) -> Self {
+        LuksError::InvalidMapperName { name: name.to_string(), reason: reason.into() }
+    }
+
+    /// Build a [`LuksError::ToolNotFound`] for the program at `path`
+    /// 
+    /// For one of luksctl's tools the message names the environment
+    /// variable that points luksctl at another copy.
+    pub(crate) fn tool_not_found(path: &Path) -> Self {
+        let program = path.file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        let path_str = path.display().to_string();
+        let message = match crate::tools::tool_by_path(path) {
+            Some(tool) => t!("command.tool_not_found", program = &program, path = &path_str, var = tool.env_var()),
+            None => t!("command.program_not_found", program = &program, path = &path_str),
+        };
+        LuksError::ToolNotFound { program, path: path.to_path_buf(), message: message.into() }
+    }
+
+    /// Build a [`LuksError::CommandFailed`] with a localized message
+    pub(crate) fn command_failed(program: &str, stderr: &str, message: impl Into<String>) -> Self {
+        LuksError::CommandFailed {
+            program: program.to_string(),
+            stderr: stderr.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Return early with an error built from a format string
+///
+/// Drop-in replacement for `anyhow::bail!` that works in functions
+/// returning either `anyhow::Result` or [`Result`]; the message ends up in
+/// [`LuksError::Other`] for the latter.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(anyhow::anyhow!($($arg)*).into())
+    };
+}
+pub(crate) use bail;
@@ -0,0 +1,106 @@
+//! Persistent configuration export
+//!
+//! This module formats suggested `/etc/crypttab` and `/etc/fstab` lines for
+//! a volume mounted by luksctl. It only produces text; it never edits
+//! system files.
+
+use anyhow::Result;
+use std::path::Path;
+
+use crate::mount::MountOptions;
+
+/// Escape a field for crypttab/fstab, which split on whitespace
+///
+/// Whitespace and backslashes are written as octal escapes (`\040`), as
+/// understood by mount and systemd.
+fn escape_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format an `/etc/crypttab` line
+///
+/// `source` is the encrypted device, preferably as `UUID=<uuid>`. Without a
+/// key file the password field is `none`, so the passphrase is asked for at
+/// boot.
+pub fn to_crypttab_line(
+    mapper_name: &str,
+    source: &str,
+    key_file: Option<&Path>,
+    header: Option<&Path>,
+    read_only: bool,
+) -> String {
+    let key = key_file
+        .map(|path| escape_field(&path.to_string_lossy()))
+        .unwrap_or_else(|| "none".to_string());
+
+    let mut options = vec!["luks".to_string()];
+    if let Some(header) = header {
+        options.push(format!("header={}", escape_field(&header.to_string_lossy())));
+    }
+    if read_only {
+        options.push("readonly".to_string());
+    }
+
+    format!("{} {} {} {}", mapper_name, escape_field(source), key, options.join(","))
+}
+
+/// Format an `/etc/fstab` line for the opened mapper
+///
+/// Uses the same hardened option string `mount_device` passes to mount.
+/// The filesystem type falls back to `auto` when unknown.
+pub fn to_fstab_line(
+    mapper_name: &str,
+    mount_point: &Path,
+    fs_type: Option<&str>,
+    options: &MountOptions,
+) -> Result<String> {
+    let mut opts = options.option_string()?;
+    if opts.is_empty() {
+        opts = "defaults".to_string();
+    }
+
+    Ok(format!(
+        "/dev/mapper/{} {} {} {} 0 2",
+        mapper_name,
+        escape_field(&mount_point.to_string_lossy()),
+        fs_type.unwrap_or("auto"),
+        opts,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crypttab_and_fstab_lines() {
+        assert_eq!(
+            to_crypttab_line("luks-test", "UUID=0a1b-2c3d", None, None, false),
+            "luks-test UUID=0a1b-2c3d none luks"
+        );
+        assert_eq!(
+            to_crypttab_line(
+                "luks-test", "/dev/sda1",
+                Some(Path::new("/etc/keys/data.key")), Some(Path::new("/boot/hdr.img")), true,
+            ),
+            "luks-test /dev/sda1 /etc/keys/data.key luks,header=/boot/hdr.img,readonly"
+        );
+
+        let options = MountOptions {
+            read_only: true,
+            options: Some("noatime".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            to_fstab_line("luks-test", Path::new("/mnt/my data"), Some("ext4"), &options).unwrap(),
+            "/dev/mapper/luks-test /mnt/my\\040data ext4 nosuid,nodev,noexec,ro,noatime 0 2"
+        );
+    }
+}
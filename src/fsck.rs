@@ -0,0 +1,80 @@
+//! Filesystem consistency checks
+//!
+//! This module runs fsck against an opened LUKS mapper before mounting with
+//! security hardening:
+//! - Only devices under /dev/mapper are accepted
+//! - Check-only (`-n`) unless repair is explicitly requested (`-p`)
+//! - Safe command execution
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+use rust_i18n::t;
+use std::path::Path;
+
+use crate::command::{is_dry_run, print_dry_run, run};
+use crate::pathsafe::ensure_safe_absolute;
+use crate::tools::{tool_command, Tool};
+
+/// fsck exit code bit: filesystem errors were corrected
+const FSCK_ERRORS_CORRECTED: i32 = 1;
+
+/// Validate that a path names a device-mapper device
+fn validate_mapper_path(mapper_path: &Path) -> Result<()> {
+    ensure_safe_absolute(mapper_path)?;
+
+    let path_str = mapper_path.to_string_lossy();
+
+    if !path_str.starts_with("/dev/mapper/") {
+        bail!("{}", t!("fsck.mapper_path_not_mapper", path = mapper_path.display().to_string()));
+    }
+
+    // The mapper is never opened in dry-run
+    if !is_dry_run() && !mapper_path.exists() {
+        bail!("{}", t!("fsck.mapper_path_not_exist", path = mapper_path.display().to_string()));
+    }
+
+    Ok(())
+}
+
+/// Run fsck against a mapper device
+/// 
+/// Without `repair` the filesystem is only checked (`fsck -n`) and any
+/// reported problem is an error. With `repair`, safe automatic repairs are
+/// allowed (`fsck -p`) and corrected errors are treated as success.
+/// 
+/// # Security
+/// - Mapper path is validated to be under /dev/mapper
+pub fn run_fsck(mapper_path: &Path, repair: bool) -> Result<()> {
+    validate_mapper_path(mapper_path)?;
+
+    let mode = if repair { "-p" } else { "-n" };
+    let mut cmd = tool_command(Tool::Fsck);
+    cmd.arg(mode).arg(mapper_path);
+
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+
+    let output = run(&mut cmd, None)
+        .context(t!("fsck.failed_execute_fsck").to_string())?;
+
+    let code = match output.status.code() {
+        Some(code) => code,
+        None => bail!("{}", t!("fsck.fsck_terminated")),
+    };
+
+    if code == 0 {
+        return Ok(());
+    }
+
+    if repair && code == FSCK_ERRORS_CORRECTED {
+        warn!("{}", t!("fsck.warning_errors_corrected"));
+        return Ok(());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = format!("{}\n{}", stdout.trim(), stderr.trim());
+    bail!("{}", t!("fsck.fsck_failed", code = code, error = detail.trim()));
+}
@@ -1,35 +1,61 @@
 //! Internationalization (i18n) support
 //!
 //! This module handles locale detection and initialization for multi-language support.
-//! Supported languages: English (en), Korean (ko), Japanese (ja)
+//! The supported languages are whatever translation files rust_i18n loaded
+//! from `locales/` at build time; adding a language only requires a new
+//! `locales/<code>.yml` file.
 //!
-//! The locale is detected from the LANG environment variable.
+//! The locale is detected from the LANG environment variable unless an
+//! explicit override (`--locale`) is given.
 
-use rust_i18n::set_locale;
+use log::warn;
+use rust_i18n::{set_locale, t};
 use sys_locale::get_locale;
 
-/// Supported locales
-const SUPPORTED_LOCALES: &[&str] = &["en", "ko", "ja"];
-
 /// Default locale when system locale is not supported
 const DEFAULT_LOCALE: &str = "en";
 
+/// Locale codes with a translation file compiled into the binary
+pub fn supported_locales() -> Vec<&'static str> {
+    let mut locales = rust_i18n::available_locales!();
+    locales.sort_unstable();
+    locales
+}
+
+/// Whether a translation is available for `locale`
+pub fn is_supported_locale(locale: &str) -> bool {
+    supported_locales().contains(&locale)
+}
+
 /// Initialize the locale based on system settings (LANG environment variable)
 /// 
-/// Detects the system locale and sets the appropriate language.
-/// Falls back to English if the system locale is not supported.
-pub fn init_locale() {
-    let locale = detect_locale();
+/// With `locale_override` set to a supported code, that language is used
+/// directly. An unsupported override is reported as a warning and the
+/// system locale is detected instead. Falls back to English if the system
+/// locale is not supported.
+pub fn init_locale(locale_override: Option<&str>) {
+    let locale = resolve_locale(locale_override);
     set_locale(&locale);
 }
 
+/// Pick the locale to use, preferring a supported override
+fn resolve_locale(locale_override: Option<&str>) -> String {
+    if let Some(locale) = locale_override {
+        if is_supported_locale(locale) {
+            return locale.to_string();
+        }
+        warn!("{}", t!("i18n.unsupported_locale", code = locale, available = supported_locales().join(", ")));
+    }
+    detect_locale()
+}
+
 /// Detect the system locale and return a supported locale code
 fn detect_locale() -> String {
     if let Some(locale) = get_locale() {
         // Extract language code (e.g., "en-US" -> "en", "ko-KR" -> "ko")
         let lang = locale.split(&['-', '_'][..]).next().unwrap_or(DEFAULT_LOCALE);
         
-        if SUPPORTED_LOCALES.contains(&lang) {
+        if is_supported_locale(lang) {
             return lang.to_string();
         }
     }
@@ -44,6 +70,21 @@ mod tests {
     #[test]
     fn test_detect_locale() {
         let locale = detect_locale();
-        assert!(SUPPORTED_LOCALES.contains(&locale.as_str()) || locale == DEFAULT_LOCALE);
+        assert!(is_supported_locale(&locale) || locale == DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn test_supported_locales_from_files() {
+        for locale in ["en", "ko", "ja", "fr"] {
+            assert!(is_supported_locale(locale), "missing locale {}", locale);
+        }
+        assert!(!is_supported_locale("xx"));
+    }
+
+    #[test]
+    fn test_resolve_locale_override() {
+        assert_eq!(resolve_locale(Some("fr")), "fr");
+        assert_eq!(resolve_locale(Some("xx")), detect_locale());
+        assert_eq!(resolve_locale(None), detect_locale());
     }
 }
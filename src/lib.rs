@@ -2,5 +2,24 @@ rust_i18n::i18n!("locales", fallback = "en");
 
 pub mod luks;
 pub mod mount;
+pub mod mountinfo;
 pub mod mapper;
 pub mod i18n;
+pub mod status;
+pub mod device;
+pub mod fsck;
+pub mod resize;
+pub mod command;
+pub mod tools;
+pub mod logging;
+pub mod redact;
+pub mod loopdev;
+pub mod export;
+pub mod config;
+pub mod volume;
+pub mod error;
+pub mod cli;
+pub mod passphrase;
+pub mod pathsafe;
+pub mod progress;
+pub mod doctor;
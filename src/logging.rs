@@ -0,0 +1,70 @@
+//! Console logging
+//!
+//! All user-facing progress and diagnostic output goes through the `log`
+//! crate so that `-v`/`-q` can control it uniformly. Informational messages
+//! are written to stdout unadorned; warnings and errors go to stderr.
+//! Everything above debug level passes through [`redact`].
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::redact::redact;
+
+/// Logger writing plain, localized messages to the console
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Debug output keeps the real values for local troubleshooting
+        match record.level() {
+            Level::Error | Level::Warn => eprintln!("{}", redact(&record.args().to_string())),
+            Level::Info => println!("{}", redact(&record.args().to_string())),
+            Level::Debug | Level::Trace => eprintln!("[{}] {}", record.level().as_str().to_lowercase(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// Map `-v` occurrences and `-q` to a log level
+///
+/// The default shows progress and warnings; each `-v` adds a level of
+/// detail and `-q` limits output to errors.
+pub fn level_for(verbosity: u8, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::Error;
+    }
+    match verbosity {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Install the console logger for this process
+pub fn init_logging(verbosity: u8, quiet: bool) {
+    // Only fails if a logger is already installed, in which case keep it
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level_for(verbosity, quiet));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for(0, false), LevelFilter::Info);
+        assert_eq!(level_for(1, false), LevelFilter::Debug);
+        assert_eq!(level_for(5, false), LevelFilter::Trace);
+        assert_eq!(level_for(3, true), LevelFilter::Error);
+    }
+}
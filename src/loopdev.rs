@@ -0,0 +1,151 @@
+//! Loop device management for LUKS container files
+//!
+//! This module attaches regular files holding a LUKS volume to loop devices
+//! with security hardening:
+//! - Container files must be absolute, traversal-free regular files
+//! - Only `/dev/loop*` devices reported by losetup are accepted
+//! - Safe command execution
+
+use anyhow::{bail, Context, Result};
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::command::{is_dry_run, print_dry_run, run};
+use crate::pathsafe::ensure_safe_absolute;
+use crate::tools::{tool_command, Tool};
+
+const LOOP_DEVICE_PREFIX: &str = "/dev/loop";
+
+/// Validate that a path names a LUKS container file
+///
+/// # Security
+/// - Path must be absolute without traversal or null bytes
+/// - Target must be a regular file (symlinks are followed)
+pub fn validate_container_file(path: &Path) -> Result<()> {
+    ensure_safe_absolute(path)?;
+
+    let metadata = std::fs::metadata(path)
+        .with_context(|| t!("loopdev.container_not_exist", path = path.display().to_string()).to_string())?;
+    if !metadata.is_file() {
+        bail!("{}", t!("loopdev.container_not_regular", path = path.display().to_string()));
+    }
+
+    Ok(())
+}
+
+/// Validate a loop device path reported by losetup
+fn validate_loop_device(loop_device: &Path) -> Result<()> {
+    let path_str = loop_device.to_string_lossy();
+    let suffix = path_str.strip_prefix(LOOP_DEVICE_PREFIX).unwrap_or_default();
+    if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_digit()) {
+        bail!("{}", t!("loopdev.invalid_loop_device", path = path_str));
+    }
+    Ok(())
+}
+
+/// Run losetup and return the loop device it prints
+fn run_losetup_for_device(cmd: &mut Command) -> Result<PathBuf> {
+    let output = run(cmd, None)
+        .context(t!("loopdev.failed_execute_losetup").to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{}", t!("loopdev.failed_attach", error = stderr.trim()));
+    }
+
+    let loop_device = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    validate_loop_device(&loop_device)?;
+    Ok(loop_device)
+}
+
+/// Attach a container file to the first free loop device
+///
+/// Returns the loop device path. With `read_only` the loop device is
+/// attached read-only. In dry-run mode the file is not attached; the next
+/// free loop device is returned instead so the rest of the flow can be shown.
+///
+/// # Security
+/// - Container file is validated before use
+/// - The reported loop device is validated before it is returned
+pub fn attach_loop(container: &Path, read_only: bool) -> Result<PathBuf> {
+    validate_container_file(container)?;
+
+    let mut cmd = tool_command(Tool::Losetup);
+    cmd.args(["--find", "--show"]);
+    if read_only {
+        cmd.arg("--read-only");
+    }
+    cmd.arg(container);
+
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return run_losetup_for_device(tool_command(Tool::Losetup).arg("--find"));
+    }
+
+    run_losetup_for_device(&mut cmd)
+}
+
+/// Whether a loop device currently has a backing file
+pub fn is_loop_attached(loop_device: &Path) -> bool {
+    let Some(name) = loop_device.file_name() else {
+        return false;
+    };
+    Path::new("/sys/block").join(name).join("loop/backing_file").exists()
+}
+
+/// The file a loop device is attached to, if any
+pub fn loop_backing_file(loop_device: &Path) -> Option<PathBuf> {
+    let name = loop_device.file_name()?;
+    let backing = std::fs::read_to_string(Path::new("/sys/block").join(name).join("loop/backing_file")).ok()?;
+    Some(PathBuf::from(backing.trim_end_matches('\n')))
+}
+
+/// Detach a loop device
+///
+/// # Security
+/// - Only `/dev/loop<N>` paths are accepted
+pub fn detach_loop(loop_device: &Path) -> Result<()> {
+    validate_loop_device(loop_device)?;
+
+    let mut cmd = tool_command(Tool::Losetup);
+    cmd.arg("--detach").arg(loop_device);
+
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+
+    let output = run(&mut cmd, None)
+        .context(t!("loopdev.failed_execute_losetup").to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{}", t!("loopdev.failed_detach", error = stderr.trim()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+
+    #[test]
+    fn test_attach_loop_validates_reported_device() {
+        let container = std::env::temp_dir().join(format!("luksctl-loop-{}", std::process::id()));
+        std::fs::write(&container, b"").unwrap();
+
+        let runner = MockRunner { stdout: "/dev/loop7\n".to_string(), ..Default::default() };
+        let (result, commands) = runner.capture(|| attach_loop(&container, true));
+        assert_eq!(result.unwrap(), PathBuf::from("/dev/loop7"));
+        assert_eq!(commands[0].args[..3], ["--find", "--show", "--read-only"]);
+
+        let runner = MockRunner { stdout: "/etc/passwd\n".to_string(), ..Default::default() };
+        let (result, _) = runner.capture(|| attach_loop(&container, false));
+        assert!(result.is_err());
+
+        std::fs::remove_file(&container).unwrap();
+    }
+}
@@ -5,13 +5,27 @@
 //! - Input validation and sanitization
 //! - Safe process execution
 
-use anyhow::{bail, Context, Result};
+use anyhow::Context;
+use log::{debug, warn};
 use rust_i18n::t;
 use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use nix::sys::stat::{umask, Mode};
+use std::fs::{self, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::io::Write;
-use std::os::unix::fs::MetadataExt;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+use crate::command::{is_dry_run, print_dry_run, run, run_with_timeout, with_progress};
+use crate::device::canonical_device_path;
+use crate::error::{bail, LuksError, Result};
+use crate::mapper::{find_mapper_by_device, wait_for_mapper_gone};
+use crate::pathsafe::{ensure_safe_absolute, ensure_under_dev};
+use crate::tools::{tool_command, Tool};
 
 /// Maximum allowed mapper name length (Linux dm-crypt limit)
 const MAX_MAPPER_NAME_LEN: usize = 128;
@@ -19,53 +33,252 @@ const MAX_MAPPER_NAME_LEN: usize = 128;
 /// Allowed characters in mapper names (alphanumeric, dash, underscore)
 const ALLOWED_MAPPER_CHARS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_";
 
+/// Highest keyslot index supported by LUKS2 (LUKS1 only has 0-7)
+const MAX_KEY_SLOT: u8 = 31;
+
+/// Smallest and largest key size, in bits, accepted for plain dm-crypt
+const PLAIN_KEY_SIZE_RANGE: std::ops::RangeInclusive<u32> = 64..=1024;
+/// Maximum length of a plain dm-crypt cipher or hash specification
+const MAX_PLAIN_SPEC_LEN: usize = 64;
+
+/// LUKS versions `luksFormat` can create
+pub const LUKS_TYPES: [&str; 2] = ["luks1", "luks2"];
+/// Key derivation functions accepted by `luksFormat --pbkdf`
+pub const PBKDF_TYPES: [&str; 3] = ["pbkdf2", "argon2i", "argon2id"];
+
+/// Permission bits granting group/other access, which key files should not have
+const KEY_FILE_FOREIGN_PERMS: u32 = 0o077;
+
+/// Header backups contain the (encrypted) volume keys: owner read/write only
+const HEADER_BACKUP_PERMS: u32 = 0o600;
+
+/// cryptsetup's exit status for "out of memory", the same in every locale
+const CRYPTSETUP_EXIT_NO_MEMORY: i32 = 3;
+/// English stderr fragments of memory failures, for older cryptsetup releases
+const MEMORY_ERROR_PATTERNS: [&str; 3] = ["not enough available memory", "cannot allocate memory", "out of memory"];
+/// Smallest and largest Argon2 memory cost, in KiB, cryptsetup accepts
+const PBKDF_MEMORY_RANGE: std::ops::RangeInclusive<u32> = 32..=4_194_304;
+
+/// Default limit on how long a single cryptsetup invocation may run
+pub const DEFAULT_CRYPTSETUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Current cryptsetup timeout in milliseconds (0 disables the timeout)
+static CRYPTSETUP_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_CRYPTSETUP_TIMEOUT.as_millis() as u64);
+
+/// Whether cryptsetup is run with `--debug -v` and its output passed through
+static CRYPTSETUP_DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Options for opening a LUKS device
+#[derive(Debug, Default, Clone)]
+pub struct LuksOpenOptions {
+    /// Only try this keyslot (`--key-slot N`)
+    pub key_slot: Option<u8>,
+    /// Detached LUKS header (`--header <path>`)
+    pub header: Option<PathBuf>,
+    /// Create a read-only dm-crypt mapping (`--readonly`)
+    pub read_only: bool,
+    /// Activate dm-integrity without its journal (`--integrity-no-journal`)
+    pub integrity_no_journal: bool,
+}
+
+/// Parameters for opening a plain (headerless) dm-crypt mapping
+/// 
+/// Plain mode has no header to read these from or to verify the passphrase
+/// against, so every parameter must be given and must match the ones the
+/// volume was created with; otherwise the mapping silently yields garbage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainOpenOptions {
+    /// Cipher specification (`--cipher`, e.g. `aes-xts-plain64`)
+    pub cipher: String,
+    /// Passphrase hash (`--hash`, e.g. `sha512`)
+    pub hash: String,
+    /// Key size in bits (`--key-size`)
+    pub key_size: u32,
+    /// Create a read-only dm-crypt mapping (`--readonly`)
+    pub read_only: bool,
+}
+
+/// Parameters for creating a LUKS volume with `cryptsetup luksFormat`
+/// 
+/// Unset parameters are left to cryptsetup's compiled-in defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LuksFormatOptions {
+    /// LUKS version (`--type`, `luks1` or `luks2`)
+    pub luks_type: String,
+    /// Cipher specification (`--cipher`, e.g. `aes-xts-plain64`)
+    pub cipher: Option<String>,
+    /// Key size in bits (`--key-size`)
+    pub key_size: Option<u32>,
+    /// Key derivation function (`--pbkdf`, e.g. `argon2id`)
+    pub pbkdf: Option<String>,
+    /// Argon2 memory cost in KiB (`--pbkdf-memory`); every system that opens
+    /// the volume needs this much free memory
+    pub pbkdf_memory: Option<u32>,
+}
+
+impl Default for LuksFormatOptions {
+    fn default() -> Self {
+        Self {
+            luks_type: "luks2".to_string(),
+            cipher: None,
+            key_size: None,
+            pbkdf: None,
+            pbkdf_memory: None,
+        }
+    }
+}
+
+/// Details of an active dm-crypt mapping as reported by `cryptsetup status`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LuksStatus {
+    /// Mapping type (e.g. `LUKS1`, `LUKS2`, `PLAIN`)
+    pub luks_type: Option<String>,
+    /// Cipher specification (e.g. `aes-xts-plain64`)
+    pub cipher: Option<String>,
+    /// Key size in bits
+    pub keysize: Option<u32>,
+    /// Backing device
+    pub device: Option<PathBuf>,
+    /// Data offset in 512-byte sectors
+    pub offset: Option<u64>,
+    /// Mapping size in 512-byte sectors
+    pub size: Option<u64>,
+    /// Whether the mapping is read-only
+    pub read_only: bool,
+    /// dm-integrity protection (e.g. `hmac(sha256)` or `aead`), if any
+    pub integrity: Option<String>,
+}
+
+/// Identity of a LUKS header as reported by `cryptsetup luksDump`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LuksInfo {
+    /// LUKS format version (1 or 2)
+    pub version: u8,
+    /// Header UUID
+    pub uuid: Option<String>,
+    /// Header label; LUKS1 has none
+    pub label: Option<String>,
+}
+
+/// One cipher row of `cryptsetup benchmark`
+/// 
+/// Throughput is in MiB/s as measured in memory, without storage I/O.
+/// `None` means cryptsetup reported the cipher as unavailable (`N/A`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkRow {
+    /// Cipher and mode (e.g. `aes-xts`)
+    pub cipher: String,
+    /// Key size in bits
+    pub key_size: u32,
+    /// Encryption throughput in MiB/s
+    pub encryption: Option<f64>,
+    /// Decryption throughput in MiB/s
+    pub decryption: Option<f64>,
+}
+
+/// Set the limit for each cryptsetup invocation for the whole process
+/// 
+/// `None` (or a zero duration) lets cryptsetup run without a limit.
+pub fn set_cryptsetup_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map_or(0, |timeout| timeout.as_millis().min(u64::MAX as u128) as u64);
+    CRYPTSETUP_TIMEOUT_MS.store(millis, Ordering::SeqCst);
+}
+
+/// The limit applied to each cryptsetup invocation, if any
+pub fn cryptsetup_timeout() -> Option<Duration> {
+    match CRYPTSETUP_TIMEOUT_MS.load(Ordering::SeqCst) {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    }
+}
+
+/// Run cryptsetup in `luks_open` and `luks_close` with `--debug -v` and
+/// copy its output to stderr, for the whole process
+/// 
+/// # Security
+/// - The debug output carries device, header and keyslot details that the
+///   sanitized errors leave out, so this must only follow an explicit flag
+/// - The passphrase is still written to stdin and zeroized; it never
+///   appears in the output
+pub fn set_cryptsetup_debug(enabled: bool) {
+    CRYPTSETUP_DEBUG.store(enabled, Ordering::SeqCst);
+}
+
+/// Add `--debug -v` to a cryptsetup command when debugging is enabled
+fn add_debug_args(cmd: &mut Command) {
+    if CRYPTSETUP_DEBUG.load(Ordering::SeqCst) {
+        cmd.args(["--debug", "-v"]);
+    }
+}
+
+/// Copy cryptsetup's output to stderr when debugging is enabled
+fn pass_through_debug_output(output: &Output) {
+    if CRYPTSETUP_DEBUG.load(Ordering::SeqCst) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = stderr.write_all(&output.stdout);
+        let _ = stderr.write_all(&output.stderr);
+    }
+}
+
+/// Run cryptsetup with the configured timeout
+/// 
+/// A hung cryptsetup (e.g. a stuck token or unresponsive device) is killed
+/// once the timeout expires, so callers holding a passphrase get an error
+/// back and drop (zeroizing) it instead of waiting indefinitely.
+fn run_cryptsetup(cmd: &mut Command, input: Option<&[u8]>) -> Result<Output> {
+    Ok(run_with_timeout(cmd, input, cryptsetup_timeout())
+        .context(t!("luks.failed_execute_cryptsetup").to_string())?)
+}
+
 /// Validate a mapper name for safety
-fn validate_mapper_name(name: &str) -> Result<()> {
+pub(crate) fn validate_mapper_name(name: &str) -> Result<()> {
     if name.is_empty() {
-        bail!("{}", t!("luks.mapper_name_empty"));
+        return Err(LuksError::invalid_mapper_name(name, t!("luks.mapper_name_empty")));
     }
     
     if name.len() > MAX_MAPPER_NAME_LEN {
-        bail!("{}", t!("luks.mapper_name_too_long", max = MAX_MAPPER_NAME_LEN));
+        return Err(LuksError::invalid_mapper_name(name, t!("luks.mapper_name_too_long", max = MAX_MAPPER_NAME_LEN)));
     }
     
     // Check for path traversal attempts
     if name.contains("..") || name.contains('/') || name.contains('\0') {
-        bail!("{}", t!("luks.mapper_name_forbidden_chars"));
+        return Err(LuksError::invalid_mapper_name(name, t!("luks.mapper_name_forbidden_chars")));
     }
     
     // Only allow safe characters
     if !name.chars().all(|c| ALLOWED_MAPPER_CHARS.contains(c)) {
-        bail!("{}", t!("luks.mapper_name_forbidden_chars"));
+        return Err(LuksError::invalid_mapper_name(name, t!("luks.mapper_name_forbidden_chars")));
+    }
+    
+    // A leading dash would be parsed as a cryptsetup option
+    if name.starts_with('-') {
+        return Err(LuksError::invalid_mapper_name(name, t!("luks.mapper_name_forbidden_chars")));
     }
     
     Ok(())
 }
 
 /// Validate that a device path is safe to use
-fn validate_device_path(device: &Path) -> Result<()> {
-    // Must be an absolute path
-    if !device.is_absolute() {
-        bail!("{}", t!("luks.device_path_must_absolute"));
-    }
-    
-    // Check for path traversal
-    let path_str = device.to_string_lossy();
-    if path_str.contains("..") {
-        bail!("{}", t!("luks.device_path_invalid_components"));
-    }
+pub(crate) fn validate_device_path(device: &Path) -> Result<()> {
+    // Must be an absolute path without traversal
+    ensure_safe_absolute(device)?;
     
     // Must exist and be a block device or in /dev/
     if !device.exists() {
-        bail!("{}", t!("luks.device_not_exist", path = device.display().to_string()));
+        return Err(LuksError::DeviceNotFound { path: device.to_path_buf() });
     }
     
     // Verify it's under /dev/ hierarchy
-    if !path_str.starts_with("/dev/") {
-        bail!("{}", t!("luks.device_must_in_dev"));
+    ensure_under_dev(device)?;
+    
+    // Symlinks (by-id, by-uuid, LVM) must lead to a node under /dev too
+    if device.is_symlink() {
+        let target = device.canonicalize()
+            .map_err(|_| LuksError::DeviceNotFound { path: device.to_path_buf() })?;
+        ensure_under_dev(&target)?;
     }
     
-    // Check that it's a block device (type check)
+    // Check that it's a block device (type check); metadata follows symlinks
     let metadata = std::fs::metadata(device)
         .context(t!("luks.failed_get_device_metadata").to_string())?;
     
@@ -74,100 +287,1670 @@ fn validate_device_path(device: &Path) -> Result<()> {
     let file_type = metadata.mode() & 0o170000;
     let is_block_device = file_type == 0o60000;
     
-    // Also allow symlinks to block devices (common for LVM)
-    let is_symlink = device.is_symlink();
-    
-    if !is_block_device && !is_symlink {
+    if !is_block_device {
         bail!("{}", t!("luks.path_not_block_device", path = device.display().to_string()));
     }
     
     Ok(())
 }
 
+/// Validate that a key file is safe to hand to cryptsetup
+///
+/// Key files must be absolute paths to existing regular files. Files that are
+/// accessible by users other than the owner are accepted, but a warning is
+/// printed because 0400 or 0600 is expected.
+fn validate_key_file(keyfile: &Path) -> Result<()> {
+    ensure_safe_absolute(keyfile)?;
+    
+    if !keyfile.exists() {
+        bail!("{}", t!("luks.key_file_not_exist", path = keyfile.display().to_string()));
+    }
+    
+    let metadata = std::fs::metadata(keyfile)
+        .context(t!("luks.failed_get_key_file_metadata").to_string())?;
+    
+    if !metadata.is_file() {
+        bail!("{}", t!("luks.key_file_not_regular", path = keyfile.display().to_string()));
+    }
+    
+    let mode = metadata.mode() & 0o777;
+    if mode & KEY_FILE_FOREIGN_PERMS != 0 {
+        warn!("{}", t!("luks.warning_key_file_permissions",
+            path = keyfile.display().to_string(),
+            mode = format!("{:o}", mode)));
+    }
+    
+    Ok(())
+}
+
+/// Validate a detached LUKS header path
+/// 
+/// Headers may be stored in a regular file or on a separate block device.
+fn validate_header_path(header: &Path) -> Result<()> {
+    ensure_safe_absolute(header)?;
+    
+    if !header.exists() {
+        bail!("{}", t!("luks.header_not_exist", path = header.display().to_string()));
+    }
+    
+    let metadata = std::fs::metadata(header)
+        .context(t!("luks.failed_get_header_metadata").to_string())?;
+    
+    if metadata.is_file() {
+        return Ok(());
+    }
+    
+    validate_device_path(header)
+        .map_err(|_| anyhow::anyhow!("{}", t!("luks.header_not_file_or_device", path = header.display().to_string())).into())
+}
+
+/// Validate a keyslot index
+fn validate_key_slot(key_slot: u8) -> Result<()> {
+    if key_slot > MAX_KEY_SLOT {
+        bail!("{}", t!("luks.key_slot_out_of_range", slot = key_slot, max = MAX_KEY_SLOT));
+    }
+    
+    Ok(())
+}
+
+/// Whether cryptsetup's error output is about dm-integrity (a kernel
+/// without dm-integrity, a damaged journal, checksum failures...)
+fn is_integrity_error(stderr: &str) -> bool {
+    stderr.to_ascii_lowercase().contains("integrity")
+}
+
+/// Whether cryptsetup failed for lack of memory
+/// 
+/// Argon2 keyslots need their full memory cost to open, which a container
+/// with a small memory limit may not have. The exit status identifies this
+/// regardless of the locale cryptsetup's messages are in.
+fn is_memory_error(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_ascii_lowercase();
+    output.status.code() == Some(CRYPTSETUP_EXIT_NO_MEMORY)
+        || MEMORY_ERROR_PATTERNS.iter().any(|pattern| stderr.contains(pattern))
+}
+
+/// Classify a failed `cryptsetup open`
+fn open_failure(device: &Path, output: &Output) -> LuksError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Checked first: the passphrase may be right, there just wasn't
+    // enough memory to derive the key
+    if is_memory_error(output) {
+        return LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_open_memory", error = stderr.trim()),
+        );
+    }
+    // Don't expose detailed error messages that might leak information
+    if stderr.contains("No key available") || stderr.contains("wrong") {
+        return LuksError::WrongPassphrase;
+    }
+    if stderr.contains("is not a valid LUKS device") {
+        return LuksError::NotLuks { path: device.to_path_buf() };
+    }
+    // Integrity failures would otherwise read like any other open error
+    if is_integrity_error(&stderr) {
+        return LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_open_integrity", error = stderr.trim()),
+        );
+    }
+    LuksError::command_failed(
+        "cryptsetup",
+        stderr.trim(),
+        t!("luks.failed_open_luks", error = stderr.trim()),
+    )
+}
+
+/// Run `cryptsetup open` with optional extra arguments and stdin passphrase
+fn run_cryptsetup_open(
+    device: &Path,
+    mapper_name: &str,
+    options: &LuksOpenOptions,
+    extra_args: &[&std::ffi::OsStr],
+    password: Option<&SecretString>,
+) -> Result<()> {
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["open", "--type", "luks"]);
+    
+    if let Some(slot) = options.key_slot {
+        validate_key_slot(slot)?;
+        cmd.arg("--key-slot").arg(slot.to_string());
+    }
+    
+    if let Some(ref header) = options.header {
+        validate_header_path(header)?;
+        cmd.arg("--header").arg(header);
+    }
+    
+    if options.read_only {
+        cmd.arg("--readonly");
+    }
+    
+    if options.integrity_no_journal {
+        cmd.arg("--integrity-no-journal");
+    }
+    
+    add_debug_args(&mut cmd);
+    cmd.args(extra_args).arg(device).arg(mapper_name);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    // Password is written to stdin - exposed only momentarily
+    let input = password.map(|password| password.expose_secret().as_bytes());
+    let output = with_progress(t!("luks.progress_opening", device = device.display().to_string()),
+        || run_cryptsetup(&mut cmd, input))?;
+    pass_through_debug_output(&output);
+
+    if !output.status.success() {
+        return Err(open_failure(device, &output));
+    }
+
+    Ok(())
+}
+
+/// Check a cipher or hash name: lowercase alphanumerics, `-` and any of
+/// `extra`, not starting with `-`
+fn is_valid_crypto_spec(spec: &str, extra: &str) -> bool {
+    !spec.is_empty()
+        && spec.len() <= MAX_PLAIN_SPEC_LEN
+        && !spec.starts_with('-')
+        && spec.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || extra.contains(c))
+}
+
+/// Validate the crypto parameters of a plain dm-crypt mapping
+/// 
+/// Cipher specifications may contain `-` and `:` (`aes-cbc-essiv:sha256`);
+/// hashes only `-`. Neither may start with `-`, which cryptsetup would
+/// parse as an option.
+fn validate_plain_options(options: &PlainOpenOptions) -> Result<()> {
+    if !is_valid_crypto_spec(&options.cipher, ":") {
+        bail!("{}", t!("luks.plain_invalid_cipher", cipher = &options.cipher));
+    }
+    if !is_valid_crypto_spec(&options.hash, "") {
+        bail!("{}", t!("luks.plain_invalid_hash", hash = &options.hash));
+    }
+    if !PLAIN_KEY_SIZE_RANGE.contains(&options.key_size) || !options.key_size.is_multiple_of(8) {
+        bail!("{}", t!("luks.plain_invalid_key_size", size = options.key_size,
+            min = PLAIN_KEY_SIZE_RANGE.start(), max = PLAIN_KEY_SIZE_RANGE.end()));
+    }
+    
+    Ok(())
+}
+
+/// Run `cryptsetup open --type plain` with the passphrase on stdin
+fn run_plain_open(
+    device: &Path,
+    mapper_name: &str,
+    password: &SecretString,
+    options: &PlainOpenOptions,
+) -> Result<()> {
+    validate_plain_options(options)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["open", "--type", "plain"])
+        .arg("--cipher").arg(&options.cipher)
+        .arg("--hash").arg(&options.hash)
+        .arg("--key-size").arg(options.key_size.to_string());
+    
+    if options.read_only {
+        cmd.arg("--readonly");
+    }
+    
+    add_debug_args(&mut cmd);
+    cmd.arg(device).arg(mapper_name);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    // Password is written to stdin - exposed only momentarily
+    let output = with_progress(t!("luks.progress_opening", device = device.display().to_string()),
+        || run_cryptsetup(&mut cmd, Some(password.expose_secret().as_bytes())))?;
+    pass_through_debug_output(&output);
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_open_plain", error = stderr.trim()),
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Open a plain (headerless) dm-crypt mapping with the given password
+/// 
+/// Kept apart from [`luks_open`] on purpose: nothing verifies the
+/// passphrase or parameters, so a typo opens a mapping of random data
+/// instead of failing. Mounting it then fails, or worse, writing to it
+/// destroys the volume.
+/// 
+/// # Security
+/// - Password is handled via SecretString and zeroized after use
+/// - Cipher, hash and key size must all be given and are validated
+/// - Mapper name is validated to prevent injection attacks
+/// - Device path is validated to prevent path traversal
+pub fn plain_open(
+    device: &Path,
+    mapper_name: &str,
+    password: &SecretString,
+    options: &PlainOpenOptions,
+) -> Result<()> {
+    // Validate inputs
+    validate_device_path(device)?;
+    validate_mapper_name(mapper_name)?;
+    
+    run_plain_open(device, mapper_name, password, options)
+}
+
 /// Open a LUKS device with the given password
 /// 
 /// # Security
 /// - Password is handled via SecretString and zeroized after use
 /// - Mapper name is validated to prevent injection attacks
 /// - Device path is validated to prevent path traversal
-pub fn luks_open(device: &Path, mapper_name: &str, password: &SecretString) -> Result<()> {
+/// - Keyslot and header options are validated before use
+pub fn luks_open(
+    device: &Path,
+    mapper_name: &str,
+    password: &SecretString,
+    options: &LuksOpenOptions,
+) -> Result<()> {
     // Validate inputs
     validate_device_path(device)?;
     validate_mapper_name(mapper_name)?;
     
-    let mut child = Command::new("cryptsetup")
-        .args(["open", "--type", "luks"])
-        .arg(device)
-        .arg(mapper_name)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .context(t!("luks.failed_execute_cryptsetup").to_string())?;
+    run_cryptsetup_open(device, mapper_name, options, &[], Some(password))
+}
 
-    // Write password to stdin - exposed only momentarily
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(password.expose_secret().as_bytes())
-            .context(t!("luks.failed_write_password").to_string())?;
-        // stdin is dropped here, closing the pipe
+/// Run `cryptsetup open --test-passphrase` with the passphrase on stdin
+fn run_test_passphrase(device: &Path, password: &SecretString, key_slot: Option<u8>) -> Result<bool> {
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["open", "--type", "luks", "--test-passphrase"]);
+    
+    if let Some(slot) = key_slot {
+        validate_key_slot(slot)?;
+        cmd.arg("--key-slot").arg(slot.to_string());
+    }
+    
+    add_debug_args(&mut cmd);
+    cmd.arg(device);
+    
+    // Password is written to stdin - exposed only momentarily
+    let output = with_progress(t!("luks.progress_testing", device = device.display().to_string()),
+        || run_cryptsetup(&mut cmd, Some(password.expose_secret().as_bytes())))?;
+    pass_through_debug_output(&output);
+    
+    if output.status.success() {
+        return Ok(true);
+    }
+    
+    match open_failure(device, &output) {
+        LuksError::WrongPassphrase => Ok(false),
+        err => Err(err),
     }
+}
+
+/// Check a passphrase against a LUKS device without opening it
+/// 
+/// Runs `cryptsetup open --test-passphrase`, which unlocks a keyslot (only
+/// `key_slot`, if given) but creates no mapping. Returns `Ok(false)` when
+/// the passphrase is wrong; every other failure (not LUKS, unreadable
+/// device, not enough memory for the key derivation) is an error.
+/// 
+/// # Security
+/// - Password is handled via SecretString and zeroized after use
+/// - It is written to cryptsetup's stdin, never to the command line
+/// - Device path and keyslot are validated before use
+pub fn luks_test_passphrase(device: &Path, password: &SecretString, key_slot: Option<u8>) -> Result<bool> {
+    validate_device_path(device)?;
+    
+    run_test_passphrase(device, password, key_slot)
+}
 
-    let output = child.wait_with_output()
-        .context(t!("luks.failed_wait_cryptsetup").to_string())?;
+/// Open a LUKS device using a key file instead of a passphrase
+/// 
+/// # Security
+/// - Key file path is validated (absolute, regular file, permissions checked)
+/// - Key material is read by cryptsetup directly and never enters this process
+/// - Mapper name is validated to prevent injection attacks
+/// - Device path is validated to prevent path traversal
+pub fn luks_open_with_keyfile(
+    device: &Path,
+    mapper_name: &str,
+    keyfile: &Path,
+    options: &LuksOpenOptions,
+) -> Result<()> {
+    // Validate inputs
+    validate_device_path(device)?;
+    validate_mapper_name(mapper_name)?;
+    validate_key_file(keyfile)?;
+    
+    run_cryptsetup_open(
+        device,
+        mapper_name,
+        options,
+        &["--key-file".as_ref(), keyfile.as_os_str()],
+        None,
+    )
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        // Don't expose detailed error messages that might leak information
-        if stderr.contains("No key available") || stderr.contains("wrong") {
-            bail!("{}", t!("luks.failed_open_luks_incorrect"));
+/// Open a LUKS device with the first of several key files that unlocks it
+/// 
+/// The candidates are tried in order, e.g. the new and the old key file
+/// while rotating keys; the one that worked is returned. Only a rejected
+/// key moves on to the next candidate: any other failure is returned
+/// straight away. If every candidate is rejected the result is
+/// [`LuksError::WrongPassphrase`].
+/// 
+/// # Security
+/// - Every candidate is validated before the first attempt, so a missing or
+///   unsafe path never costs an unlock attempt
+/// - Key material is read by cryptsetup directly and never enters this process
+/// - Mapper name and device path are validated as for [`luks_open_with_keyfile`]
+pub fn luks_open_with_keyfiles(
+    device: &Path,
+    mapper_name: &str,
+    keyfiles: &[PathBuf],
+    options: &LuksOpenOptions,
+) -> Result<PathBuf> {
+    // Validate inputs
+    validate_device_path(device)?;
+    validate_mapper_name(mapper_name)?;
+    
+    run_open_with_keyfiles(device, mapper_name, keyfiles, options)
+}
+
+/// Validate every key file, then try them in order with `cryptsetup open`
+fn run_open_with_keyfiles(
+    device: &Path,
+    mapper_name: &str,
+    keyfiles: &[PathBuf],
+    options: &LuksOpenOptions,
+) -> Result<PathBuf> {
+    for keyfile in keyfiles {
+        validate_key_file(keyfile)?;
+    }
+    
+    for keyfile in keyfiles {
+        let result = run_cryptsetup_open(
+            device,
+            mapper_name,
+            options,
+            &["--key-file".as_ref(), keyfile.as_os_str()],
+            None,
+        );
+        match result {
+            Ok(()) => {
+                debug!("{}", t!("luks.key_file_unlocked", path = keyfile.display().to_string()));
+                return Ok(keyfile.clone());
+            }
+            Err(LuksError::WrongPassphrase) => {
+                debug!("{}", t!("luks.key_file_rejected", path = keyfile.display().to_string()));
+            }
+            Err(e) => return Err(e),
         }
-        bail!("{}", t!("luks.failed_open_luks", error = stderr.trim()));
     }
-
-    Ok(())
+    
+    Err(LuksError::WrongPassphrase)
 }
 
 /// Close a LUKS device
 /// 
+/// Only returns once the mapper's device node is gone, so the name can be
+/// reused immediately.
+/// 
 /// # Security
 /// - Mapper name is validated to prevent injection attacks
 pub fn luks_close(mapper_name: &str) -> Result<()> {
     // Validate mapper name
     validate_mapper_name(mapper_name)?;
     
-    let output = Command::new("cryptsetup")
-        .args(["close", mapper_name])
-        .output()
-        .context(t!("luks.failed_execute_cryptsetup").to_string())?;
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("close");
+    add_debug_args(&mut cmd);
+    cmd.arg(mapper_name);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    let output = with_progress(t!("luks.progress_closing", name = mapper_name), || run_cryptsetup(&mut cmd, None))?;
+    pass_through_debug_output(&output);
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("{}", t!("luks.failed_close_luks", error = stderr.trim()));
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_close_luks", error = stderr.trim()),
+        ));
     }
 
+    wait_for_mapper_gone(mapper_name)
+}
+
+/// Validate the path a header backup is written to
+fn validate_backup_path(backup_file: &Path) -> Result<()> {
+    ensure_safe_absolute(backup_file)?;
+    
+    if !backup_file.parent().is_some_and(Path::is_dir) {
+        bail!("{}", t!("luks.backup_dir_not_exist", path = backup_file.display().to_string()));
+    }
+    
     Ok(())
 }
 
-/// Check if a device is a LUKS device
+/// Temporary file next to `backup_file` that a replacement backup is
+/// written to before it is renamed into place
+fn backup_temp_path(backup_file: &Path) -> PathBuf {
+    let name = backup_file.file_name().unwrap_or_default().to_string_lossy();
+    backup_file.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+}
+
+/// Run `cryptsetup luksHeaderBackup` for an already validated device
+fn run_header_backup(device: &Path, backup_file: &Path, overwrite: bool) -> Result<()> {
+    validate_backup_path(backup_file)?;
+    
+    let existing = fs::symlink_metadata(backup_file).ok();
+    if let Some(ref metadata) = existing {
+        if !overwrite {
+            bail!("{}", t!("luks.backup_exists", path = backup_file.display().to_string()));
+        }
+        if !metadata.is_file() {
+            bail!("{}", t!("luks.backup_not_regular", path = backup_file.display().to_string()));
+        }
+    }
+    
+    // cryptsetup refuses to overwrite, so a replacement is written next to
+    // the old backup and only renamed over it once complete
+    let target = match existing {
+        Some(_) => backup_temp_path(backup_file),
+        None => backup_file.to_path_buf(),
+    };
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksHeaderBackup").arg(device).arg("--header-backup-file").arg(&target);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    // Left over from an interrupted run of this process ID
+    if target != backup_file {
+        let _ = fs::remove_file(&target);
+    }
+    
+    // cryptsetup creates the file itself; keep it private from the start
+    let previous_umask = umask(Mode::from_bits_truncate(KEY_FILE_FOREIGN_PERMS));
+    let output = run_cryptsetup(&mut cmd, None);
+    umask(previous_umask);
+    
+    let result = output.and_then(|output| {
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(LuksError::command_failed(
+                "cryptsetup",
+                stderr.trim(),
+                t!("luks.failed_header_backup", error = stderr.trim()),
+            ));
+        }
+        fs::set_permissions(&target, Permissions::from_mode(HEADER_BACKUP_PERMS))
+            .context(t!("luks.failed_set_backup_perms").to_string())?;
+        if target != backup_file {
+            fs::rename(&target, backup_file)
+                .context(t!("luks.failed_replace_backup", path = backup_file.display().to_string()).to_string())?;
+        }
+        Ok(())
+    });
+    
+    // The previous backup is untouched; don't leave a partial one beside it
+    if result.is_err() && target != backup_file {
+        let _ = fs::remove_file(&target);
+    }
+    
+    result
+}
+
+/// Back up a device's LUKS header to a file
+/// 
+/// Runs `cryptsetup luksHeaderBackup`. An existing file is only replaced
+/// when `overwrite` is set, and only once the new backup has been written.
 /// 
 /// # Security
-/// - Device path is validated before use
-pub fn is_luks_device(device: &Path) -> Result<bool> {
-    // Basic path validation (existence check is done separately)
-    if !device.is_absolute() {
-        bail!("{}", t!("luks.device_path_must_absolute"));
+/// - Device path is validated to prevent path traversal
+/// - The backup is created with 0600 permissions; it holds the keyslots,
+///   so anyone with a copy and an old passphrase can decrypt the volume
+/// - Only a regular file is ever replaced, never a symlink or device
+/// - A failed backup never costs the previous one
+pub fn luks_header_backup(device: &Path, backup_file: &Path, overwrite: bool) -> Result<()> {
+    validate_device_path(device)?;
+    
+    run_header_backup(device, backup_file, overwrite)
+}
+
+/// Validate that a header backup exists, is a regular file and is non-empty
+fn validate_restore_file(backup_file: &Path) -> Result<()> {
+    let path = backup_file.display().to_string();
+    let Ok(metadata) = fs::metadata(backup_file) else {
+        bail!("{}", t!("luks.restore_file_not_exist", path = path));
+    };
+    
+    if !metadata.is_file() {
+        bail!("{}", t!("luks.restore_not_regular", path = path));
     }
     
-    let path_str = device.to_string_lossy();
-    if path_str.contains("..") || !path_str.starts_with("/dev/") {
-        bail!("{}", t!("luks.invalid_device_path"));
+    if metadata.len() == 0 {
+        bail!("{}", t!("luks.restore_file_empty", path = path));
     }
     
-    let output = Command::new("cryptsetup")
-        .args(["isLuks"])
-        .arg(device)
-        .output()
-        .context(t!("luks.failed_execute_isluks").to_string())?;
+    Ok(())
+}
 
-    Ok(output.status.success())
+/// Restore a device's LUKS header from a backup file
+/// 
+/// Runs `cryptsetup luksHeaderRestore`. With `assume_yes` the command runs
+/// in `--batch-mode`; otherwise stdin and stdout are left attached to the
+/// terminal so cryptsetup can ask for confirmation itself, and no timeout
+/// applies while it waits for an answer.
+/// 
+/// # Security
+/// - Device path is validated to prevent path traversal
+/// - Restoring replaces every keyslot on the device; passphrases added
+///   since the backup was taken stop working
+pub fn luks_header_restore(device: &Path, backup_file: &Path, assume_yes: bool) -> Result<()> {
+    validate_device_path(device)?;
+    validate_restore_file(backup_file)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    if assume_yes {
+        cmd.arg("--batch-mode");
+    }
+    cmd.arg("luksHeaderRestore").arg(device).arg("--header-backup-file").arg(backup_file);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    let output = if assume_yes {
+        run_cryptsetup(&mut cmd, None)?
+    } else {
+        cmd.stdin(Stdio::inherit()).stdout(Stdio::inherit());
+        run(&mut cmd, None).context(t!("luks.failed_execute_cryptsetup").to_string())?
+    };
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_header_restore", error = stderr.trim()),
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Build the stdin for a keyslot operation: one passphrase per line
+/// 
+/// Without `--key-file`, cryptsetup reads each passphrase it needs from
+/// stdin up to the next newline, so a passphrase containing one cannot be
+/// passed this way. The buffer is zeroized when dropped.
+fn passphrase_input(passphrases: &[&SecretString]) -> Result<Zeroizing<Vec<u8>>> {
+    let mut input = Zeroizing::new(Vec::new());
+    
+    for passphrase in passphrases {
+        let passphrase = passphrase.expose_secret();
+        if passphrase.contains('\n') {
+            bail!("{}", t!("luks.passphrase_contains_newline"));
+        }
+        input.extend_from_slice(passphrase.as_bytes());
+        input.push(b'\n');
+    }
+    
+    Ok(input)
+}
+
+/// Run a keyslot-changing cryptsetup command with passphrases on stdin
+/// 
+/// `failed_key` names the localized message used when cryptsetup fails for
+/// any reason other than a rejected passphrase.
+fn run_keyslot_command(cmd: &mut Command, passphrases: &[&SecretString], failed_key: &str) -> Result<()> {
+    let input = passphrase_input(passphrases)?;
+    
+    if is_dry_run() {
+        print_dry_run(cmd);
+        return Ok(());
+    }
+    
+    let output = run_cryptsetup(cmd, Some(&input))?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("No key available") {
+            return Err(LuksError::WrongPassphrase);
+        }
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!(failed_key, error = stderr.trim()),
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Parse the active keyslot numbers from `cryptsetup luksDump` output
+/// 
+/// Handles both the LUKS1 (`Key Slot 0: ENABLED`) and the LUKS2
+/// (`Keyslots:` section with `  0: luks2` entries) formats.
+fn parse_active_keyslots(dump: &str) -> Vec<u8> {
+    let mut slots = Vec::new();
+    let mut in_luks2_keyslots = false;
+    
+    for line in dump.lines() {
+        if let Some(rest) = line.strip_prefix("Key Slot ") {
+            if let Some((slot, state)) = rest.split_once(':') {
+                if state.trim() == "ENABLED" {
+                    slots.extend(slot.trim().parse::<u8>().ok());
+                }
+            }
+            continue;
+        }
+        
+        if !line.starts_with(char::is_whitespace) {
+            in_luks2_keyslots = line.trim_end() == "Keyslots:";
+            continue;
+        }
+        
+        if in_luks2_keyslots {
+            if let Some((slot, _)) = line.trim().split_once(':') {
+                slots.extend(slot.parse::<u8>().ok());
+            }
+        }
+    }
+    
+    slots
+}
+
+/// List the active keyslots of a LUKS device
+/// 
+/// # Security
+/// - Device path is validated to prevent path traversal
+pub fn luks_active_keyslots(device: &Path) -> Result<Vec<u8>> {
+    validate_device_path(device)?;
+    
+    let output = run_cryptsetup(tool_command(Tool::Cryptsetup).arg("luksDump").arg(device), None)?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("is not a valid LUKS device") {
+            return Err(LuksError::NotLuks { path: device.to_path_buf() });
+        }
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_read_keyslots", error = stderr.trim()),
+        ));
+    }
+    
+    Ok(parse_active_keyslots(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the version, UUID and label out of `cryptsetup luksDump` output
+/// 
+/// Returns `None` without a `Version:` line. LUKS2 reports a missing label
+/// as `(no label)`.
+fn parse_luks_info(dump: &str) -> Option<LuksInfo> {
+    let mut version = None;
+    let mut uuid = None;
+    let mut label = None;
+    
+    for line in dump.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "Version" => version = value.parse().ok(),
+            "UUID" if !value.is_empty() => uuid = Some(value.to_string()),
+            "Label" if !value.is_empty() && value != "(no label)" => label = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    
+    Some(LuksInfo { version: version?, uuid, label })
+}
+
+/// Read the LUKS version, UUID and label of a device
+/// 
+/// Only the header is read; the device is never opened, and root is not
+/// needed if the caller can read the device.
+/// 
+/// # Security
+/// - Device path is validated to prevent path traversal
+pub fn luks_probe(device: &Path) -> Result<LuksInfo> {
+    validate_device_path(device)?;
+    
+    let output = run_cryptsetup(tool_command(Tool::Cryptsetup).arg("luksDump").arg(device), None)?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("is not a valid LUKS device") {
+            return Err(LuksError::NotLuks { path: device.to_path_buf() });
+        }
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_probe", error = stderr.trim()),
+        ));
+    }
+    
+    parse_luks_info(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| LuksError::NotLuks { path: device.to_path_buf() })
+}
+
+/// Refuse to remove the last remaining way to unlock `device`
+/// 
+/// `removing` is the slot about to be removed, or `None` when it is only
+/// known by passphrase (any single remaining slot is then the last one).
+fn check_not_last_keyslot(device: &Path, removing: Option<u8>) -> Result<()> {
+    let active = luks_active_keyslots(device)?;
+    let is_last = match removing {
+        Some(slot) => active == [slot],
+        None => active.len() <= 1,
+    };
+    
+    if is_last {
+        bail!("{}", t!("luks.refuse_last_keyslot", path = device.display().to_string()));
+    }
+    
+    Ok(())
+}
+
+/// Add a new passphrase to a LUKS device
+/// 
+/// Runs `cryptsetup luksAddKey`, unlocking with `existing` and storing
+/// `new_passphrase` in `key_slot` (or the first free slot).
+/// 
+/// # Security
+/// - Both passphrases are written to cryptsetup's stdin from a buffer that
+///   is zeroized afterwards; neither appears on the command line
+/// - Device path and keyslot are validated before use
+pub fn luks_add_key(
+    device: &Path,
+    existing: &SecretString,
+    new_passphrase: &SecretString,
+    key_slot: Option<u8>,
+) -> Result<()> {
+    validate_device_path(device)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksAddKey");
+    if let Some(slot) = key_slot {
+        validate_key_slot(slot)?;
+        cmd.arg("--key-slot").arg(slot.to_string());
+    }
+    cmd.arg(device);
+    
+    run_keyslot_command(&mut cmd, &[existing, new_passphrase], "luks.failed_add_key")
+}
+
+/// Replace a passphrase on a LUKS device
+/// 
+/// Runs `cryptsetup luksChangeKey`: the keyslot unlocked by `existing` (or
+/// `key_slot`, if given) is rewritten with `new_passphrase`.
+/// 
+/// # Security
+/// - Both passphrases are written to cryptsetup's stdin from a buffer that
+///   is zeroized afterwards; neither appears on the command line
+/// - Device path and keyslot are validated before use
+pub fn luks_change_key(
+    device: &Path,
+    existing: &SecretString,
+    new_passphrase: &SecretString,
+    key_slot: Option<u8>,
+) -> Result<()> {
+    validate_device_path(device)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksChangeKey");
+    if let Some(slot) = key_slot {
+        validate_key_slot(slot)?;
+        cmd.arg("--key-slot").arg(slot.to_string());
+    }
+    cmd.arg(device);
+    
+    run_keyslot_command(&mut cmd, &[existing, new_passphrase], "luks.failed_change_key")
+}
+
+/// Remove the keyslot that `passphrase` unlocks
+/// 
+/// Runs `cryptsetup luksRemoveKey`. Removing the last active keyslot makes
+/// the volume permanently inaccessible, so it is refused unless `force` is
+/// set.
+/// 
+/// # Security
+/// - The passphrase is written to cryptsetup's stdin and zeroized afterwards
+/// - Device path is validated to prevent path traversal
+pub fn luks_remove_key(device: &Path, passphrase: &SecretString, force: bool) -> Result<()> {
+    validate_device_path(device)?;
+    if !force {
+        check_not_last_keyslot(device, None)?;
+    }
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksRemoveKey").arg(device);
+    
+    run_keyslot_command(&mut cmd, &[passphrase], "luks.failed_remove_key")
+}
+
+/// Wipe a keyslot by number
+/// 
+/// Runs `cryptsetup luksKillSlot`, authorized by `existing` (a passphrase
+/// for any other slot). Killing the last active keyslot is refused unless
+/// `force` is set.
+/// 
+/// # Security
+/// - The passphrase is written to cryptsetup's stdin and zeroized afterwards
+/// - Device path and keyslot are validated before use
+pub fn luks_kill_slot(device: &Path, key_slot: u8, existing: &SecretString, force: bool) -> Result<()> {
+    validate_device_path(device)?;
+    validate_key_slot(key_slot)?;
+    if !force {
+        check_not_last_keyslot(device, Some(key_slot))?;
+    }
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksKillSlot").arg(device).arg(key_slot.to_string());
+    
+    run_keyslot_command(&mut cmd, &[existing], "luks.failed_kill_slot")
+}
+
+/// Validate the parameters of a `luksFormat`
+/// 
+/// LUKS1 keyslots only support PBKDF2.
+fn validate_format_options(options: &LuksFormatOptions) -> Result<()> {
+    if !LUKS_TYPES.contains(&options.luks_type.as_str()) {
+        bail!("{}", t!("luks.format_invalid_type", luks_type = &options.luks_type));
+    }
+    if let Some(ref cipher) = options.cipher {
+        if !is_valid_crypto_spec(cipher, ":") {
+            bail!("{}", t!("luks.plain_invalid_cipher", cipher = cipher));
+        }
+    }
+    if let Some(key_size) = options.key_size {
+        if !PLAIN_KEY_SIZE_RANGE.contains(&key_size) || !key_size.is_multiple_of(8) {
+            bail!("{}", t!("luks.plain_invalid_key_size", size = key_size,
+                min = PLAIN_KEY_SIZE_RANGE.start(), max = PLAIN_KEY_SIZE_RANGE.end()));
+        }
+    }
+    if let Some(ref pbkdf) = options.pbkdf {
+        if !PBKDF_TYPES.contains(&pbkdf.as_str()) {
+            bail!("{}", t!("luks.format_invalid_pbkdf", pbkdf = pbkdf, allowed = PBKDF_TYPES.join(", ")));
+        }
+        if options.luks_type == "luks1" && pbkdf != "pbkdf2" {
+            bail!("{}", t!("luks.format_luks1_pbkdf", pbkdf = pbkdf));
+        }
+    }
+    if let Some(memory) = options.pbkdf_memory {
+        // Only Argon2 has a memory cost
+        if options.luks_type == "luks1" || options.pbkdf.as_deref() == Some("pbkdf2") {
+            bail!("{}", t!("luks.format_memory_needs_argon2"));
+        }
+        if !PBKDF_MEMORY_RANGE.contains(&memory) {
+            bail!("{}", t!("luks.format_invalid_pbkdf_memory", memory = memory,
+                min = PBKDF_MEMORY_RANGE.start(), max = PBKDF_MEMORY_RANGE.end()));
+        }
+    }
+    
+    Ok(())
+}
+
+/// Create a LUKS volume on `device`, destroying whatever it holds
+/// 
+/// Runs `cryptsetup luksFormat --batch-mode`: cryptsetup's own "type YES"
+/// confirmation is skipped, so callers must confirm with the user (and
+/// check for existing data) before calling this.
+/// 
+/// # Security
+/// - The passphrase is written to cryptsetup's stdin and zeroized afterwards
+/// - Device path and crypto parameters are validated before use
+pub fn luks_format(device: &Path, passphrase: &SecretString, options: &LuksFormatOptions) -> Result<()> {
+    validate_device_path(device)?;
+    validate_format_options(options)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksFormat").arg("--batch-mode").arg("--type").arg(&options.luks_type);
+    if let Some(ref cipher) = options.cipher {
+        cmd.arg("--cipher").arg(cipher);
+    }
+    if let Some(key_size) = options.key_size {
+        cmd.arg("--key-size").arg(key_size.to_string());
+    }
+    if let Some(ref pbkdf) = options.pbkdf {
+        cmd.arg("--pbkdf").arg(pbkdf);
+    }
+    if let Some(memory) = options.pbkdf_memory {
+        cmd.arg("--pbkdf-memory").arg(memory.to_string());
+    }
+    cmd.arg(device);
+    
+    run_keyslot_command(&mut cmd, &[passphrase], "luks.failed_format")
+}
+
+/// Wipe every keyslot of a LUKS device
+/// 
+/// Runs `cryptsetup luksErase --batch-mode`. Without a keyslot the volume
+/// key cannot be recovered, so the data becomes permanently inaccessible
+/// unless a header backup exists; callers must confirm with the user first.
+/// 
+/// # Security
+/// - Device path is validated to prevent path traversal
+/// - A device that is still open is refused: its volume key stays in
+///   kernel memory, so erasing would give a false sense of safety
+pub fn luks_erase(device: &Path) -> Result<()> {
+    validate_device_path(device)?;
+    
+    if let Some(mapper_name) = find_mapper_by_device(device)? {
+        bail!("{}", t!("luks.erase_device_open", path = device.display().to_string(), name = mapper_name));
+    }
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("luksErase").arg("--batch-mode").arg(device);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    let output = run_cryptsetup(&mut cmd, None)?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_erase", error = stderr.trim()),
+        ));
+    }
+    
+    Ok(())
+}
+
+/// Check if a mapper is currently active
+/// 
+/// Uses the exit status of `cryptsetup status`, which is zero only for
+/// active mappings.
+/// 
+/// # Security
+/// - Mapper name is validated to prevent injection attacks
+pub fn is_mapper_active(mapper_name: &str) -> Result<bool> {
+    validate_mapper_name(mapper_name)?;
+    
+    let output = run_cryptsetup(tool_command(Tool::Cryptsetup).args(["status", mapper_name]), None)?;
+
+    Ok(output.status.success())
+}
+
+/// Grow an active mapping to the current size of its backing device
+/// 
+/// Wraps `cryptsetup resize`; the filesystem inside is left untouched, see
+/// [`crate::resize::grow_filesystem`].
+/// 
+/// # Security
+/// - Mapper name is validated to prevent injection attacks
+/// - Only active mappings are resized
+pub fn luks_resize(mapper_name: &str) -> Result<()> {
+    if !is_mapper_active(mapper_name)? {
+        bail!("{}", t!("luks.mapper_not_active", name = mapper_name));
+    }
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["resize", mapper_name]);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    let output = run_cryptsetup(&mut cmd, None)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_resize", error = stderr.trim()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Suspend an active mapping and wipe its volume key from kernel memory
+/// 
+/// Wraps `cryptsetup luksSuspend`, which flushes pending I/O and then
+/// blocks all further I/O until [`luks_resume`] is called with a passphrase.
+/// 
+/// # Security
+/// - Mapper name is validated to prevent injection attacks
+/// - Only active mappings are suspended
+pub fn luks_suspend(mapper_name: &str) -> Result<()> {
+    if !is_mapper_active(mapper_name)? {
+        bail!("{}", t!("luks.mapper_not_active", name = mapper_name));
+    }
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["luksSuspend", mapper_name]);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+    
+    let output = run_cryptsetup(&mut cmd, None)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_suspend", error = stderr.trim()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resume a mapping suspended with [`luks_suspend`]
+/// 
+/// Wraps `cryptsetup luksResume`; `device` is the LUKS device backing the
+/// mapping and is validated like in [`luks_open`].
+/// 
+/// # Security
+/// - Password is handled via SecretString, written to cryptsetup's stdin
+///   and zeroized afterwards
+/// - Mapper name is validated to prevent injection attacks
+/// - Device path is validated to prevent path traversal
+pub fn luks_resume(device: &Path, mapper_name: &str, password: &SecretString) -> Result<()> {
+    validate_device_path(device)?;
+    validate_mapper_name(mapper_name)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.args(["luksResume", mapper_name]);
+    
+    run_keyslot_command(&mut cmd, &[password], "luks.failed_resume")
+}
+
+/// Parse the leading number of a value such as `512 bits` or `32768 sectors`
+fn parse_leading_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse the output of `cryptsetup status`
+/// 
+/// Only the stable `key: value` prefixes are matched, so the free-form
+/// first line (which may be localized) is ignored.
+fn parse_luks_status(output: &str) -> LuksStatus {
+    let mut status = LuksStatus::default();
+    
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        
+        match key.trim() {
+            "type" => status.luks_type = Some(value.to_string()),
+            "cipher" => status.cipher = Some(value.to_string()),
+            "keysize" => status.keysize = parse_leading_number(value),
+            "device" => status.device = Some(PathBuf::from(value)),
+            "offset" => status.offset = parse_leading_number(value),
+            "size" => status.size = parse_leading_number(value),
+            "mode" => status.read_only = value.starts_with("readonly") || value == "read-only",
+            "integrity" => status.integrity = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    
+    status
+}
+
+/// Get details of an active mapping via `cryptsetup status`
+/// 
+/// # Security
+/// - Mapper name is validated to prevent injection attacks
+pub fn luks_status(mapper_name: &str) -> Result<LuksStatus> {
+    validate_mapper_name(mapper_name)?;
+    
+    let output = run_cryptsetup(tool_command(Tool::Cryptsetup).args(["status", mapper_name]), None)?;
+
+    if !output.status.success() {
+        bail!("{}", t!("luks.mapper_not_active", name = mapper_name));
+    }
+
+    Ok(parse_luks_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse a throughput column such as `1234.5` or `1234,5` (MiB/s)
+fn parse_throughput(value: &str) -> Option<f64> {
+    value.replace(',', ".").parse().ok()
+}
+
+/// Parse the cipher rows of `cryptsetup benchmark` output
+/// 
+/// Rows are recognized by their shape rather than by the (translatable)
+/// header: a cipher name, a key size such as `256b`, then two throughput
+/// values (or `N/A`), each optionally followed by a unit. PBKDF lines and
+/// comments don't match and are skipped.
+fn parse_benchmark(output: &str) -> Vec<BenchmarkRow> {
+    let mut rows = Vec::new();
+    
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [cipher, key_size, ref rest @ ..] = fields[..] else {
+            continue;
+        };
+        let Some(key_size) = key_size.strip_suffix('b').and_then(|bits| bits.parse().ok()) else {
+            continue;
+        };
+        
+        // Keep the two throughput columns, dropping their units
+        let values: Vec<&str> = rest.iter()
+            .copied()
+            .filter(|field| *field == "N/A" || parse_throughput(field).is_some())
+            .collect();
+        let [encryption, decryption] = values[..] else {
+            continue;
+        };
+        
+        let encryption = parse_throughput(encryption);
+        let decryption = parse_throughput(decryption);
+        rows.push(BenchmarkRow { cipher: cipher.to_string(), key_size, encryption, decryption });
+    }
+    
+    rows
+}
+
+/// Measure cipher throughput with `cryptsetup benchmark`
+/// 
+/// Useful for picking a cipher before formatting a volume. Only the
+/// in-memory cipher rows are returned; PBKDF timings are not.
+pub fn luks_benchmark() -> Result<Vec<BenchmarkRow>> {
+    let output = run_cryptsetup(tool_command(Tool::Cryptsetup).arg("benchmark"), None)?;
+    
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LuksError::command_failed(
+            "cryptsetup",
+            stderr.trim(),
+            t!("luks.failed_benchmark", error = stderr.trim()),
+        ));
+    }
+    
+    Ok(parse_benchmark(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Check if a device is a LUKS device
+/// 
+/// With a detached `header`, the header is checked instead of the device.
+/// 
+/// # Security
+/// - Device path is validated before use
+/// - A symlink such as `/dev/disk/by-id/...` is resolved to its device node
+/// - Header path is validated before use
+pub fn is_luks_device(device: &Path, header: Option<&Path>) -> Result<bool> {
+    // Basic path validation (existence check is done separately)
+    ensure_safe_absolute(device)?;
+    ensure_under_dev(device)?;
+    let device = &canonical_device_path(device)?;
+    
+    let mut cmd = tool_command(Tool::Cryptsetup);
+    cmd.arg("isLuks");
+    
+    if let Some(header) = header {
+        validate_header_path(header)?;
+        cmd.arg("--header").arg(header);
+    }
+    
+    let output = run_with_timeout(cmd.arg(device), None, cryptsetup_timeout())
+        .context(t!("luks.failed_execute_isluks").to_string())?;
+
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+
+    #[test]
+    fn test_open_passes_password_on_stdin_only() {
+        let password = SecretString::from("hunter2".to_string());
+        let options = LuksOpenOptions { key_slot: Some(3), header: None, read_only: true, ..Default::default() };
+        let (result, commands) = MockRunner::default().capture(|| {
+            run_cryptsetup_open(Path::new("/dev/sda1"), "luks-test", &options, &[], Some(&password))
+        });
+
+        result.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].program, "cryptsetup");
+        assert_eq!(
+            commands[0].args,
+            ["open", "--type", "luks", "--key-slot", "3", "--readonly", "/dev/sda1", "luks-test"]
+        );
+        assert_eq!(commands[0].input.as_deref(), Some("hunter2".as_bytes()));
+    }
+
+    #[test]
+    fn test_open_reports_wrong_passphrase() {
+        let password = SecretString::from("wrong".to_string());
+        let runner = MockRunner {
+            exit_code: 2,
+            stderr: "No key available with this passphrase.".to_string(),
+            ..Default::default()
+        };
+        let (result, _) = runner.capture(|| {
+            run_cryptsetup_open(Path::new("/dev/sda1"), "luks-test", &LuksOpenOptions::default(), &[], Some(&password))
+        });
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, LuksError::WrongPassphrase));
+        assert_eq!(err.to_string(), t!("luks.failed_open_luks_incorrect"));
+    }
+
+    #[test]
+    fn test_passphrase_check_creates_no_mapping() {
+        let password = SecretString::from("hunter2".to_string());
+        let (result, commands) = MockRunner::default().capture(|| {
+            run_test_passphrase(Path::new("/dev/sda1"), &password, Some(1))
+        });
+
+        assert!(result.unwrap());
+        assert_eq!(
+            commands[0].args,
+            ["open", "--type", "luks", "--test-passphrase", "--key-slot", "1", "/dev/sda1"]
+        );
+        assert_eq!(commands[0].input.as_deref(), Some("hunter2".as_bytes()));
+
+        let wrong = MockRunner {
+            exit_code: 2,
+            stderr: "No key available with this passphrase.".to_string(),
+            ..Default::default()
+        };
+        let (result, _) = wrong.capture(|| run_test_passphrase(Path::new("/dev/sda1"), &password, None));
+        assert!(!result.unwrap());
+
+        let not_luks = MockRunner {
+            exit_code: 1,
+            stderr: "Device /dev/sda1 is not a valid LUKS device.".to_string(),
+            ..Default::default()
+        };
+        let (result, _) = not_luks.capture(|| run_test_passphrase(Path::new("/dev/sda1"), &password, None));
+        assert!(matches!(result, Err(LuksError::NotLuks { .. })));
+    }
+
+    #[test]
+    fn test_failed_backup_keeps_previous_backup() {
+        let dir = std::env::temp_dir().join(format!("luksctl-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backup_file = dir.join("header.img");
+        std::fs::write(&backup_file, "previous").unwrap();
+
+        let runner = MockRunner { exit_code: 1, stderr: "Read error".to_string(), ..Default::default() };
+        let (result, commands) = runner.capture(|| run_header_backup(Path::new("/dev/sda1"), &backup_file, true));
+
+        assert!(matches!(result, Err(LuksError::CommandFailed { .. })));
+        assert_eq!(commands[0].args.last().unwrap(), &*backup_temp_path(&backup_file).to_string_lossy());
+        assert_eq!(std::fs::read_to_string(&backup_file).unwrap(), "previous");
+        assert!(!backup_temp_path(&backup_file).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_keyfiles_tries_candidates_in_order() {
+        let dir = std::env::temp_dir().join(format!("luksctl-keyfiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let keyfiles = [dir.join("new.key"), dir.join("old.key")];
+        for keyfile in &keyfiles {
+            std::fs::write(keyfile, "key").unwrap();
+            std::fs::set_permissions(keyfile, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        let open = |runner: MockRunner, keyfiles: &[PathBuf]| runner.capture(|| {
+            run_open_with_keyfiles(Path::new("/dev/loop0"), "luks-test", keyfiles, &LuksOpenOptions::default())
+        });
+
+        // The first that works wins
+        let (result, commands) = open(MockRunner::default(), &keyfiles);
+        assert_eq!(result.unwrap(), keyfiles[0]);
+        assert_eq!(commands.len(), 1);
+
+        // Every candidate is rejected
+        let rejected = MockRunner {
+            exit_code: 2,
+            stderr: "No key available with this passphrase.".to_string(),
+            ..Default::default()
+        };
+        let (result, commands) = open(rejected, &keyfiles);
+        assert!(matches!(result, Err(LuksError::WrongPassphrase)));
+        assert_eq!(commands.len(), 2);
+        assert!(commands[1].args.iter().any(|arg| arg == &keyfiles[1].display().to_string()));
+
+        // Other failures are not retried with the next key file
+        let (result, commands) = open(MockRunner { exit_code: 5, stderr: "Device busy".to_string(), ..Default::default() }, &keyfiles);
+        assert!(matches!(result, Err(LuksError::CommandFailed { .. })));
+        assert_eq!(commands.len(), 1);
+
+        // A missing candidate fails before any attempt
+        let (result, commands) = open(MockRunner::default(), &[keyfiles[0].clone(), dir.join("missing.key")]);
+        assert!(result.is_err());
+        assert!(commands.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_reports_integrity_failures() {
+        let password = SecretString::from("hunter2".to_string());
+        let options = LuksOpenOptions { integrity_no_journal: true, ..Default::default() };
+        let runner = MockRunner {
+            exit_code: 1,
+            stderr: "Kernel does not support dm-integrity mapping.".to_string(),
+            ..Default::default()
+        };
+        let (result, commands) = runner.capture(|| {
+            run_cryptsetup_open(Path::new("/dev/sda1"), "luks-test", &options, &[], Some(&password))
+        });
+
+        assert!(commands[0].args.iter().any(|arg| arg == "--integrity-no-journal"));
+        let err = result.unwrap_err();
+        assert!(matches!(err, LuksError::CommandFailed { .. }));
+        assert_eq!(
+            err.to_string(),
+            t!("luks.failed_open_integrity", error = "Kernel does not support dm-integrity mapping.")
+        );
+    }
+
+    #[test]
+    fn test_open_reports_memory_failures() {
+        let password = SecretString::from("hunter2".to_string());
+        let options = LuksOpenOptions::default();
+        // The exit status is recognized even when the message is localized
+        for (exit_code, stderr) in [(3, "Mémoire insuffisante."), (1, "Not enough available memory to open a keyslot.")] {
+            let runner = MockRunner { exit_code, stderr: stderr.to_string(), ..Default::default() };
+            let (result, _) = runner.capture(|| {
+                run_cryptsetup_open(Path::new("/dev/sda1"), "luks-test", &options, &[], Some(&password))
+            });
+            assert_eq!(result.unwrap_err().to_string(), t!("luks.failed_open_memory", error = stderr));
+        }
+    }
+
+    #[test]
+    fn test_plain_open_requires_valid_parameters() {
+        let password = SecretString::from("hunter2".to_string());
+        let options = PlainOpenOptions {
+            cipher: "aes-cbc-essiv:sha256".to_string(),
+            hash: "sha512".to_string(),
+            key_size: 256,
+            read_only: true,
+        };
+        let (result, commands) = MockRunner::default().capture(|| {
+            run_plain_open(Path::new("/dev/sda1"), "luks-test", &password, &options)
+        });
+        result.unwrap();
+        assert_eq!(
+            commands[0].args,
+            ["open", "--type", "plain", "--cipher", "aes-cbc-essiv:sha256", "--hash", "sha512",
+                "--key-size", "256", "--readonly", "/dev/sda1", "luks-test"]
+        );
+        assert_eq!(commands[0].input.as_deref(), Some("hunter2".as_bytes()));
+
+        for bad in [
+            PlainOpenOptions { cipher: "--help".to_string(), ..options.clone() },
+            PlainOpenOptions { cipher: "aes xts".to_string(), ..options.clone() },
+            PlainOpenOptions { hash: "sha:512".to_string(), ..options.clone() },
+            PlainOpenOptions { hash: String::new(), ..options.clone() },
+            PlainOpenOptions { key_size: 255, ..options.clone() },
+            PlainOpenOptions { key_size: 4096, ..options.clone() },
+        ] {
+            let (result, commands) = MockRunner::default().capture(|| {
+                run_plain_open(Path::new("/dev/sda1"), "luks-test", &password, &bad)
+            });
+            assert!(result.is_err(), "{bad:?} should be rejected");
+            assert!(commands.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_close_rejects_mapper_name_injection() {
+        for name in ["luks-a; rm -rf /", "../luks-a", "luks a", "--help", ""] {
+            let (result, commands) = MockRunner::default().capture(|| luks_close(name));
+            assert!(
+                matches!(result, Err(LuksError::InvalidMapperName { .. })),
+                "{name:?} should be rejected"
+            );
+            assert!(commands.is_empty(), "{name:?} must not reach cryptsetup");
+        }
+
+        let (result, commands) = MockRunner::default().capture(|| luks_close("luks-test"));
+        result.unwrap();
+        assert_eq!(commands[0].args, ["close", "luks-test"]);
+    }
+
+    #[test]
+    fn test_resize_requires_active_mapper() {
+        let (result, commands) = MockRunner { exit_code: 4, ..Default::default() }
+            .capture(|| luks_resize("luks-test"));
+        assert!(result.is_err());
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].args, ["status", "luks-test"]);
+
+        let (result, commands) = MockRunner::default().capture(|| luks_resize("luks-test"));
+        result.unwrap();
+        assert_eq!(commands[1].args, ["resize", "luks-test"]);
+    }
+
+    #[test]
+    fn test_suspend_and_resume_commands() {
+        let (result, commands) = MockRunner::default().capture(|| luks_suspend("luks-test"));
+        result.unwrap();
+        assert_eq!(commands[1].args, ["luksSuspend", "luks-test"]);
+        assert!(commands[1].input.is_none());
+
+        let password = SecretString::from("hunter2".to_string());
+        let (result, commands) = MockRunner::default()
+            .capture(|| luks_resume(Path::new("/dev/null"), "luks-test", &password));
+        assert!(result.is_err(), "a character device is not a LUKS device");
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_restore_file_must_be_non_empty_regular_file() {
+        let dir = std::env::temp_dir().join(format!("luksctl-restore-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let backup = dir.join("header.img");
+
+        assert!(validate_restore_file(&backup).is_err());
+        assert!(validate_restore_file(&dir).is_err());
+        fs::write(&backup, b"").unwrap();
+        assert!(validate_restore_file(&backup).is_err());
+        fs::write(&backup, b"LUKS\xba\xbe").unwrap();
+        assert!(validate_restore_file(&backup).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_keyslot_command_sends_one_passphrase_per_line() {
+        let existing = SecretString::from("old".to_string());
+        let new_passphrase = SecretString::from("new".to_string());
+        let mut cmd = Command::new("cryptsetup");
+        cmd.args(["luksAddKey", "/dev/sda1"]);
+        let (result, commands) = MockRunner::default().capture(|| {
+            run_keyslot_command(&mut cmd, &[&existing, &new_passphrase], "luks.failed_add_key")
+        });
+
+        result.unwrap();
+        assert_eq!(commands[0].args, ["luksAddKey", "/dev/sda1"]);
+        assert_eq!(commands[0].input.as_deref(), Some("old\nnew\n".as_bytes()));
+
+        let runner = MockRunner {
+            exit_code: 2,
+            stderr: "No key available with this passphrase.".to_string(),
+            ..Default::default()
+        };
+        let (result, _) = runner.capture(|| {
+            run_keyslot_command(&mut Command::new("cryptsetup"), &[&existing], "luks.failed_remove_key")
+        });
+        assert!(matches!(result, Err(LuksError::WrongPassphrase)));
+
+        let multiline = SecretString::from("a\nb".to_string());
+        assert!(passphrase_input(&[&multiline]).is_err());
+    }
+
+    #[test]
+    fn test_format_options_validation() {
+        assert!(validate_format_options(&LuksFormatOptions::default()).is_ok());
+        
+        let valid = LuksFormatOptions {
+            luks_type: "luks1".to_string(),
+            cipher: Some("aes-xts-plain64".to_string()),
+            key_size: Some(512),
+            pbkdf: Some("pbkdf2".to_string()),
+            pbkdf_memory: None,
+        };
+        assert!(validate_format_options(&valid).is_ok());
+        
+        let invalid = [
+            LuksFormatOptions { luks_type: "luks3".to_string(), ..Default::default() },
+            LuksFormatOptions { cipher: Some("--batch-mode".to_string()), ..Default::default() },
+            LuksFormatOptions { key_size: Some(100), ..Default::default() },
+            LuksFormatOptions { pbkdf: Some("scrypt".to_string()), ..Default::default() },
+            LuksFormatOptions { pbkdf: Some("argon2id".to_string()), ..valid.clone() },
+            LuksFormatOptions { pbkdf_memory: Some(65536), ..valid.clone() },
+            LuksFormatOptions { pbkdf_memory: Some(16), ..Default::default() },
+        ];
+        let low_memory = LuksFormatOptions { pbkdf_memory: Some(65536), ..Default::default() };
+        assert!(validate_format_options(&low_memory).is_ok());
+        for options in &invalid {
+            assert!(validate_format_options(options).is_err(), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_parse_active_keyslots() {
+        let luks1 = "LUKS header information for /dev/sda1\n\n\
+            Key Slot 0: ENABLED\n\tIterations:\t\t1000\n\
+            Key Slot 1: DISABLED\n\
+            Key Slot 2: ENABLED\n";
+        assert_eq!(parse_active_keyslots(luks1), [0, 2]);
+
+        let luks2 = "LUKS header information\nVersion:       \t2\n\n\
+            Data segments:\n  0: crypt\n\toffset: 16777216 [bytes]\n\
+            Keyslots:\n  0: luks2\n\tKey:        512 bits\n  3: luks2\n\tKey:        512 bits\n\
+            Tokens:\nDigests:\n  0: pbkdf2\n";
+        assert_eq!(parse_active_keyslots(luks2), [0, 3]);
+    }
+
+    #[test]
+    fn test_parse_luks_info() {
+        let luks1 = "LUKS header information for /dev/sda1\n\n\
+            Version:       \t1\nCipher name:   \taes\n\
+            UUID:          \t0b4c5a7e-1d2f-4c3b-9a8e-7f6d5c4b3a21\n\n\
+            Key Slot 0: ENABLED\n";
+        assert_eq!(parse_luks_info(luks1), Some(LuksInfo {
+            version: 1,
+            uuid: Some("0b4c5a7e-1d2f-4c3b-9a8e-7f6d5c4b3a21".to_string()),
+            label: None,
+        }));
+
+        let luks2 = "LUKS header information\nVersion:       \t2\nEpoch:         \t3\n\
+            UUID:          \t5d3f0c1a-2b4e-4f6a-8c9d-0e1f2a3b4c5d\n\
+            Label:         \tbackup\nSubsystem:     \t(no subsystem)\n";
+        let info = parse_luks_info(luks2).unwrap();
+        assert_eq!(info.version, 2);
+        assert_eq!(info.label.as_deref(), Some("backup"));
+
+        let unlabeled = "Version:       \t2\nLabel:         \t(no label)\n";
+        assert_eq!(parse_luks_info(unlabeled).unwrap().label, None);
+        assert_eq!(parse_luks_info("Device /dev/sda1 is not a valid LUKS device.\n"), None);
+    }
+
+    #[test]
+    fn test_parse_benchmark() {
+        let output = "# Tests are approximate using memory only (no storage IO).\n\
+            PBKDF2-sha256    1599024 iterations per second for 256-bit key\n\
+            argon2id      4 iterations, 1048576 memory, 4 parallel threads (CPUs) for 256-bit key (requested 2000 ms time)\n\
+            #     Algorithm |       Key |      Encryption |      Decryption\n\
+            \x20       aes-cbc        128b      1245.6 MiB/s      3876.1 MiB/s\n\
+            \x20   serpent-xts        512b       701,3 MiB/s       698,0 MiB/s\n\
+            \x20   twofish-cbc        256b         N/A               N/A\n";
+        let rows = parse_benchmark(output);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], BenchmarkRow {
+            cipher: "aes-cbc".to_string(),
+            key_size: 128,
+            encryption: Some(1245.6),
+            decryption: Some(3876.1),
+        });
+        assert_eq!(rows[1].encryption, Some(701.3));
+        assert_eq!(rows[2].encryption, None);
+    }
+
+    #[test]
+    fn test_is_luks_device_uses_exit_status() {
+        let (result, commands) = MockRunner::default()
+            .capture(|| is_luks_device(Path::new("/dev/sda1"), None));
+        assert!(result.unwrap());
+        assert_eq!(commands[0].args, ["isLuks", "/dev/sda1"]);
+
+        let runner = MockRunner { exit_code: 1, ..Default::default() };
+        let (result, _) = runner.capture(|| is_luks_device(Path::new("/dev/sda1"), None));
+        assert!(!result.unwrap());
+    }
+
+    #[test]
+    fn test_parse_luks_status() {
+        let output = "\
+/dev/mapper/luks-test is active and is in use.
+  type:    LUKS2
+  cipher:  aes-xts-plain64
+  keysize: 512 bits
+  key location: keyring
+  device:  /dev/sda1
+  sector size:  512
+  offset:  32768 sectors
+  size:    1953492992 sectors
+  mode:    read/write
+";
+        let status = parse_luks_status(output);
+        assert_eq!(status.luks_type.as_deref(), Some("LUKS2"));
+        assert_eq!(status.cipher.as_deref(), Some("aes-xts-plain64"));
+        assert_eq!(status.keysize, Some(512));
+        assert_eq!(status.device, Some(PathBuf::from("/dev/sda1")));
+        assert_eq!(status.offset, Some(32768));
+        assert_eq!(status.size, Some(1953492992));
+        assert!(!status.read_only);
+
+        assert_eq!(status.integrity, None);
+
+        let status = parse_luks_status("  mode:    readonly\n  integrity: hmac(sha256)\n  integrity keysize: 256 bits\n");
+        assert!(status.read_only);
+        assert_eq!(status.integrity.as_deref(), Some("hmac(sha256)"));
+    }
 }
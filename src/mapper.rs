@@ -6,16 +6,54 @@
 //! - Secure file permissions for state files
 //! - Input validation and sanitization
 
-use anyhow::{bail, Context, Result};
+use anyhow::Context;
+use log::{debug, warn};
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use nix::sys::stat::{major, minor};
 use rust_i18n::t;
-use std::fs::{self, OpenOptions, Permissions};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions, Permissions};
 use std::io::Write;
-use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use crate::command::is_dry_run;
+use crate::error::{bail, LuksError, Result};
+use crate::mount::{mount_entries, BindMount, MountOptions};
+use crate::pathsafe::{ensure_no_null, ensure_no_traversal, ensure_safe_absolute, ensure_under_dev};
+
 const MAPPER_DIR: &str = "/dev/mapper";
 const MAPPER_STATE_DIR: &str = "/run/luksctl";
+/// Environment variable overriding the state directory
+const STATE_DIR_ENV: &str = "LUKSCTL_STATE_DIR";
+/// Environment variable enabling a persistent copy of the state (e.g. /var/lib/luksctl)
+const PERSISTENT_STATE_DIR_ENV: &str = "LUKSCTL_PERSISTENT_STATE_DIR";
+/// Prefix of mapper names created and managed by luksctl
+const DEFAULT_MAPPER_PREFIX: &str = "luks-";
+/// Environment variable overriding the mapper name prefix
+const MAPPER_PREFIX_ENV: &str = "LUKSCTL_MAPPER_PREFIX";
+/// Environment variable sudo sets to the calling user's uid
+const SUDO_UID_ENV: &str = "SUDO_UID";
+/// Maximum length of a mapper name prefix
+const MAX_MAPPER_PREFIX_LEN: usize = 32;
+/// Default number of attempts at generating an unused mapper name
+pub const DEFAULT_NAME_RETRIES: u32 = 10;
+/// Environment variable overriding the number of name generation attempts
+const NAME_RETRIES_ENV: &str = "LUKSCTL_NAME_RETRIES";
+/// Largest accepted number of name generation attempts
+const MAX_NAME_RETRIES: u32 = 1000;
+/// Upper bound of the random pause between name generation attempts
+const NAME_RETRY_JITTER: Duration = Duration::from_millis(20);
+/// Permission bit granting write access to other users
+const WORLD_WRITABLE: u32 = 0o002;
 
 /// Secure file permissions: owner read/write only (0600)
 const STATE_FILE_PERMS: u32 = 0o600;
@@ -24,13 +62,251 @@ const STATE_DIR_PERMS: u32 = 0o700;
 
 /// Maximum length for escaped mount point names
 const MAX_ESCAPED_NAME_LEN: usize = 255;
+/// Number of hex digits of the path hash appended to state file names
+const STATE_NAME_HASH_LEN: usize = 8;
+
+/// Lock file inside the state directory serializing state changes
+const STATE_LOCK_FILE: &str = ".lock";
+/// How long to wait for another luksctl process to release the state lock
+const STATE_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay between attempts to acquire the state lock
+const STATE_LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for a freshly opened mapper to appear under /dev/mapper
+const MAPPER_APPEAR_TIMEOUT: Duration = Duration::from_secs(2);
+/// Delay between checks for the mapper device node
+const MAPPER_APPEAR_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a closed mapper's device node may linger before it is an error
+const MAPPER_VANISH_TIMEOUT: Duration = Duration::from_millis(300);
+/// Delay between checks for a closed mapper's device node
+const MAPPER_VANISH_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Sysfs directory of block devices by `major:minor`
+const SYS_DEV_BLOCK: &str = "/sys/dev/block";
+
+/// Maximum size of a state file's content
+const MAX_STATE_CONTENT_LEN: usize = 4096;
+
+thread_local! {
+    /// State directory override for the current thread (see [`with_state_dir`])
+    static STATE_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Version of the state file format written by this release
+/// 
+/// Version 0 covers the unversioned JSON and the legacy `mapper_name:device`
+/// content of older releases. Bump it when a change would be misread by an
+/// older reader, and migrate in [`parse_state_content`].
+pub const STATE_VERSION: u32 = 1;
+
+/// Content of a state file
+/// 
+/// State files are written as JSON so the original mount point can be
+/// recovered exactly. Older releases wrote `mapper_name:device`, which is
+/// still accepted when reading.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountState {
+    /// Format version the file was written with; always [`STATE_VERSION`]
+    /// when stored
+    #[serde(default)]
+    pub version: u32,
+    pub mapper_name: String,
+    pub device: PathBuf,
+    /// The device as given on the command line (a symlink, `UUID=` or
+    /// `LABEL=`), if it differs from the canonical `device`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_input: Option<String>,
+    pub mount_point: PathBuf,
+    /// Detached LUKS header used to open the device, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<PathBuf>,
+    /// Options the filesystem was mounted with (absent in legacy state files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_options: Option<MountOptions>,
+    /// Loop device the container file in `device` was attached to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_device: Option<PathBuf>,
+    /// Bind mounts made from the volume, in the order they were created
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binds: Vec<BindMount>,
+    /// Whether the dm-crypt mapping itself was opened read-only
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub crypt_read_only: bool,
+    /// Filesystem type the kernel mounted, as listed in /proc/mounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs_type: Option<String>,
+    /// When the volume was mounted, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mounted_at: Option<u64>,
+    /// Real uid of the process that mounted the volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// The user who asked for the mount, see [`invoking_owner`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<MountOwner>,
+    /// Whether luksctl created the mount point (`luks_mount --mkdir`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub created_dir: bool,
+}
+
+/// The user a mount was made for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountOwner {
+    pub uid: u32,
+    /// User name from the passwd database, if the uid has an entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for MountOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "{} ({})", name, self.uid),
+            None => write!(f, "{}", self.uid),
+        }
+    }
+}
+
+/// The user luksctl is acting for
+/// 
+/// Run through sudo, that is the calling user from `SUDO_UID`; otherwise
+/// the real uid of the process.
+/// 
+/// # Security
+/// - `SUDO_UID` is only consulted when the real uid is root, and root can
+///   set it to anything: the owner is a record against mistakes between
+///   admins, not a security boundary
+pub fn invoking_owner() -> MountOwner {
+    let real_uid = nix::unistd::getuid();
+    let uid = std::env::var(SUDO_UID_ENV).ok()
+        .filter(|_| real_uid.is_root())
+        .and_then(|value| value.parse::<u32>().ok())
+        .unwrap_or_else(|| real_uid.as_raw());
+    let name = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|user| user.name);
+    MountOwner { uid, name }
+}
+
+/// Seconds since the Unix epoch, for [`MountState::mounted_at`]
+pub fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Prefix identifying mapper names managed by luksctl
+/// 
+/// Defaults to `luks-` and can be overridden with the
+/// `LUKSCTL_MAPPER_PREFIX` environment variable to give luksctl its own
+/// namespace, e.g. `myapp-luks-`.
+/// 
+/// # Security
+/// - The prefix may only contain `[a-z0-9-]` and may not start with `-`
+pub fn mapper_prefix() -> Result<String> {
+    resolve_mapper_prefix(std::env::var(MAPPER_PREFIX_ENV).ok())
+}
+
+/// Validate an optional mapper prefix override, falling back to the default
+fn resolve_mapper_prefix(override_prefix: Option<String>) -> Result<String> {
+    let Some(prefix) = override_prefix.filter(|prefix| !prefix.is_empty()) else {
+        return Ok(DEFAULT_MAPPER_PREFIX.to_string());
+    };
+    
+    let valid_chars = prefix.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_chars || prefix.starts_with('-') || prefix.len() > MAX_MAPPER_PREFIX_LEN {
+        bail!("{}", t!("mapper.prefix_invalid", prefix = &prefix, max = MAX_MAPPER_PREFIX_LEN));
+    }
+    
+    Ok(prefix)
+}
 
 /// Generate a unique mapper name using UUID
 /// 
 /// Uses UUID v4 for cryptographically secure random generation
-pub fn generate_mapper_name() -> String {
+pub fn generate_mapper_name() -> Result<String> {
     let uuid = Uuid::new_v4();
-    format!("luks-{}", uuid)
+    Ok(format!("{}{}", mapper_prefix()?, uuid))
+}
+
+/// Number of attempts at generating an unused mapper name
+/// 
+/// Defaults to [`DEFAULT_NAME_RETRIES`] and can be overridden with the
+/// `LUKSCTL_NAME_RETRIES` environment variable.
+pub fn name_retries() -> Result<u32> {
+    resolve_name_retries(std::env::var(NAME_RETRIES_ENV).ok())
+}
+
+/// Validate an optional retry count override, falling back to the default
+fn resolve_name_retries(override_retries: Option<String>) -> Result<u32> {
+    let Some(value) = override_retries.filter(|value| !value.is_empty()) else {
+        return Ok(DEFAULT_NAME_RETRIES);
+    };
+    
+    match value.parse::<u32>() {
+        Ok(retries) if (1..=MAX_NAME_RETRIES).contains(&retries) => Ok(retries),
+        _ => bail!("{}", t!("mapper.name_retries_invalid", value = &value, max = MAX_NAME_RETRIES)),
+    }
+}
+
+/// Generate a mapper name that no existing mapper uses
+/// 
+/// Up to `max_retries` UUID-based names starting with `prefix` are tried,
+/// with a short random pause between attempts so that two processes
+/// racing for names don't stay in lockstep.
+/// 
+/// # Security
+/// - The returned name passes [`validate_mapper_name`]
+/// - Fails rather than reuse a name taken by an existing mapper
+pub fn allocate_unique_name(prefix: &str, max_retries: u32) -> Result<String> {
+    allocate_unique_name_with(prefix, max_retries, || Uuid::new_v4().to_string(), mapper_exists)
+}
+
+/// [`allocate_unique_name`] with the name generator and the collision
+/// check passed in
+fn allocate_unique_name_with(
+    prefix: &str,
+    max_retries: u32,
+    mut generate: impl FnMut() -> String,
+    is_taken: impl Fn(&str) -> bool,
+) -> Result<String> {
+    for attempt in 1..=max_retries {
+        if attempt > 1 {
+            thread::sleep(name_retry_jitter());
+        }
+        
+        let name = format!("{}{}", prefix, generate());
+        validate_mapper_name(&name)?;
+        if !is_taken(&name) {
+            return Ok(name);
+        }
+        debug!("{}", t!("mapper.name_taken", name = &name, attempt = attempt));
+    }
+    
+    bail!("{}", t!("mapper.failed_generate_name", count = max_retries))
+}
+
+/// Random pause of up to [`NAME_RETRY_JITTER`]
+fn name_retry_jitter() -> Duration {
+    let max_micros = NAME_RETRY_JITTER.as_micros();
+    let micros = Uuid::new_v4().as_u128() % max_micros;
+    Duration::from_micros(micros as u64)
+}
+
+/// Build a mapper name from a user-chosen name (`luks_mount --name`)
+/// 
+/// The mapper prefix is prepended unless the name already carries it, so
+/// the result is still recognised as one of our managed mappers.
+pub fn custom_mapper_name(name: &str) -> Result<String> {
+    let prefix = mapper_prefix()?;
+    let name = if name.starts_with(&prefix) {
+        name.to_string()
+    } else {
+        format!("{}{}", prefix, name)
+    };
+    
+    validate_mapper_name(&name)?;
+    Ok(name)
 }
 
 /// Get the mapper device path
@@ -43,21 +319,145 @@ pub fn mapper_exists(mapper_name: &str) -> bool {
     get_mapper_path(mapper_name).exists()
 }
 
+/// List the luksctl-managed mappers currently open
+/// 
+/// Returns the names under /dev/mapper that carry the mapper prefix,
+/// sorted. Mappers of other tools, such as the root volume, are never
+/// returned.
+/// 
+/// # Security
+/// - Every name goes through the same validation as a user-supplied one
+pub fn list_managed_mappers() -> Result<Vec<String>> {
+    let Ok(entries) = fs::read_dir(MAPPER_DIR) else {
+        return Ok(Vec::new());
+    };
+    
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| validate_mapper_name(name).is_ok())
+        .collect();
+    
+    names.sort();
+    Ok(names)
+}
+
+/// Check that `mapper_path` is the device-mapper device `mapper_name`
+/// 
+/// # Security
+/// - The path must be a block device, or a symlink to one under /dev
+/// - The kernel must report that device as the dm device of this name, so
+///   a file or symlink planted at `/dev/mapper/<name>` is never used
+pub fn validate_mapper_device(mapper_name: &str, mapper_path: &Path) -> Result<()> {
+    let not_block = || t!("mapper.not_block_device", path = mapper_path.display().to_string()).to_string();
+    
+    let link = fs::symlink_metadata(mapper_path).with_context(not_block)?;
+    if link.file_type().is_symlink() {
+        let target = mapper_path.canonicalize().with_context(not_block)?;
+        if ensure_under_dev(&target).is_err() {
+            bail!("{}", not_block());
+        }
+    }
+    
+    let metadata = fs::metadata(mapper_path).with_context(not_block)?;
+    if !metadata.file_type().is_block_device() {
+        bail!("{}", not_block());
+    }
+    
+    // The dm name the kernel gives this major:minor must be ours
+    let dm_name_path = format!("{}/{}:{}/dm/name", SYS_DEV_BLOCK, major(metadata.rdev()), minor(metadata.rdev()));
+    let dm_name = fs::read_to_string(dm_name_path).unwrap_or_default();
+    if dm_name.trim_end() != mapper_name {
+        bail!("{}", t!("mapper.not_mapper_device", path = mapper_path.display().to_string(), name = mapper_name));
+    }
+    
+    Ok(())
+}
+
+/// Wait for a just-opened mapper's device node to appear
+/// 
+/// `cryptsetup open` can return before udev has created
+/// `/dev/mapper/<name>`, so poll for it for a short, bounded time and
+/// return its path once [`validate_mapper_device`] accepts it. Returns
+/// immediately in dry-run mode, where nothing is opened.
+pub fn wait_for_mapper(mapper_name: &str) -> Result<PathBuf> {
+    let mapper_path = get_mapper_path(mapper_name);
+    if is_dry_run() {
+        return Ok(mapper_path);
+    }
+    
+    let deadline = Instant::now() + MAPPER_APPEAR_TIMEOUT;
+    while !mapper_path.exists() {
+        if Instant::now() >= deadline {
+            bail!("{}", t!("mapper.mapper_did_not_appear",
+                path = mapper_path.display().to_string(),
+                secs = MAPPER_APPEAR_TIMEOUT.as_secs()));
+        }
+        thread::sleep(MAPPER_APPEAR_POLL_INTERVAL);
+    }
+    validate_mapper_device(mapper_name, &mapper_path)?;
+    
+    Ok(mapper_path)
+}
+
+/// Wait for a just-closed mapper's device node to disappear
+/// 
+/// Some kernels remove `/dev/mapper/<name>` shortly after `cryptsetup close`
+/// returns, and reopening under the same name fails until they do. Polls
+/// for a few hundred milliseconds at most, so the common case where the
+/// node is already gone costs nothing.
+pub fn wait_for_mapper_gone(mapper_name: &str) -> Result<()> {
+    let deadline = Instant::now() + MAPPER_VANISH_TIMEOUT;
+    while mapper_exists(mapper_name) {
+        if Instant::now() >= deadline {
+            bail!("{}", t!("mapper.mapper_still_present",
+                path = get_mapper_path(mapper_name).display().to_string(),
+                millis = MAPPER_VANISH_TIMEOUT.as_millis()));
+        }
+        thread::sleep(MAPPER_VANISH_POLL_INTERVAL);
+    }
+    
+    Ok(())
+}
+
 /// Safely escape a mount point path for use as a filename
 /// 
+/// The readable escaped path is followed by `-` and the first hex digits of
+/// the SHA-256 of the path, so paths that escape alike (`/a_b` and `/a/b`)
+/// still get distinct state files.
+/// 
 /// # Security
 /// - Validates input length
 /// - Replaces path separators safely
 /// - Prevents null byte injection
 fn escape_mount_path(mount_point: &Path) -> Result<String> {
-    let path_str = mount_point.to_string_lossy();
+    let escaped = format!("{}-{}", legacy_escape_mount_path(mount_point)?, path_hash(mount_point));
     
-    // Check for null bytes
-    if path_str.contains('\0') {
-        bail!("{}", t!("mapper.path_contains_null"));
+    if escaped.len() > MAX_ESCAPED_NAME_LEN {
+        bail!("{}", t!("mapper.path_too_long"));
     }
     
+    Ok(escaped)
+}
+
+/// Short, stable hash of a mount point path
+fn path_hash(mount_point: &Path) -> String {
+    let digest = Sha256::digest(mount_point.as_os_str().as_bytes());
+    digest.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()[..STATE_NAME_HASH_LEN]
+        .to_string()
+}
+
+/// Escape a mount point the way releases before the hash suffix did
+/// 
+/// Only used to find state files those releases wrote.
+fn legacy_escape_mount_path(mount_point: &Path) -> Result<String> {
+    ensure_no_null(mount_point)?;
+    ensure_no_traversal(mount_point)?;
+    
     // Escape the path
+    let path_str = mount_point.to_string_lossy();
     let escaped = path_str.replace('/', "_");
     
     // Validate length
@@ -66,8 +466,7 @@ fn escape_mount_path(mount_point: &Path) -> Result<String> {
     }
     
     // Ensure the escaped name doesn't start with a dot (hidden file)
-    // and doesn't contain path traversal attempts
-    if escaped.starts_with('.') || escaped.contains("..") {
+    if escaped.starts_with('.') {
         bail!("{}", t!("mapper.path_traversal_detected"));
     }
     
@@ -75,56 +474,246 @@ fn escape_mount_path(mount_point: &Path) -> Result<String> {
 }
 
 /// Validate mapper name format
-fn validate_mapper_name(name: &str) -> Result<()> {
+/// 
+/// The name must carry the mapper prefix, so mappers luksctl doesn't manage
+/// are never touched.
+pub fn validate_mapper_name(name: &str) -> Result<()> {
     if name.is_empty() || name.len() > 128 {
-        bail!("{}", t!("mapper.name_invalid_length"));
+        return Err(LuksError::invalid_mapper_name(name, t!("mapper.name_invalid_length")));
     }
     
-    // Must start with "luks-" for our managed mappers
-    if !name.starts_with("luks-") {
-        bail!("{}", t!("mapper.name_must_start_luks"));
+    // Must carry our prefix to be one of our managed mappers
+    let prefix = mapper_prefix()?;
+    if !name.starts_with(&prefix) {
+        return Err(LuksError::invalid_mapper_name(name, t!("mapper.name_must_start_prefix", prefix = &prefix)));
     }
     
     // Check for path traversal or injection
     if name.contains('/') || name.contains('\0') || name.contains("..") {
-        bail!("{}", t!("mapper.name_invalid_chars"));
+        return Err(LuksError::invalid_mapper_name(name, t!("mapper.name_invalid_chars")));
     }
     
-    Ok(())
+    // Must also be acceptable to cryptsetup calls in the luks module
+    crate::luks::validate_mapper_name(name)
+}
+
+/// Directory holding luksctl state files
+/// 
+/// Defaults to `/run/luksctl` and can be overridden with the
+/// `LUKSCTL_STATE_DIR` environment variable, e.g. to run tests against a
+/// temporary directory.
+/// 
+/// # Security
+/// - The directory must be an absolute path without `..` components
+/// - An existing directory must not be a symlink or world-writable
+pub fn state_dir() -> Result<PathBuf> {
+    let override_dir = STATE_DIR_OVERRIDE.with(|dir| dir.borrow().clone())
+        .or_else(|| std::env::var_os(STATE_DIR_ENV).map(PathBuf::from));
+    resolve_state_dir(override_dir)
+}
+
+/// Run `f` with [`state_dir`] returning `dir` on this thread
+/// 
+/// Takes precedence over `LUKSCTL_STATE_DIR`, without changing it for
+/// other threads (used by tests).
+pub fn with_state_dir<T>(dir: PathBuf, f: impl FnOnce() -> T) -> T {
+    let previous = STATE_DIR_OVERRIDE.with(|current| current.replace(Some(dir)));
+    let result = f();
+    STATE_DIR_OVERRIDE.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// Validate an optional state directory override, falling back to the default
+fn resolve_state_dir(override_dir: Option<PathBuf>) -> Result<PathBuf> {
+    let Some(dir) = override_dir.filter(|dir| !dir.as_os_str().is_empty()) else {
+        return Ok(PathBuf::from(MAPPER_STATE_DIR));
+    };
+    
+    validate_state_dir(dir)
+}
+
+/// Directory holding the persistent copy of the state, if enabled
+/// 
+/// `/run/luksctl` is a tmpfs and is lost on reboot. Setting
+/// `LUKSCTL_PERSISTENT_STATE_DIR` (e.g. to `/var/lib/luksctl`) makes every
+/// state file also be written there, so [`reconcile_state`] can clean up
+/// after an unclean shutdown.
+/// 
+/// # Security
+/// - Validated like the state directory
+/// - Kept at 0700, with 0600 state files, whenever it is written to
+pub fn persistent_state_dir() -> Result<Option<PathBuf>> {
+    std::env::var_os(PERSISTENT_STATE_DIR_ENV)
+        .map(PathBuf::from)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(validate_state_dir)
+        .transpose()
+}
+
+/// Validate a state directory path and any existing directory there
+fn validate_state_dir(dir: PathBuf) -> Result<PathBuf> {
+    let dir_str = dir.to_string_lossy();
+    if ensure_safe_absolute(&dir).is_err() {
+        bail!("{}", t!("mapper.state_dir_invalid", path = dir_str));
+    }
+    
+    if let Ok(metadata) = fs::symlink_metadata(&dir) {
+        if !metadata.is_dir() {
+            bail!("{}", t!("mapper.state_dir_not_dir", path = dir_str));
+        }
+        if metadata.permissions().mode() & WORLD_WRITABLE != 0 {
+            bail!("{}", t!("mapper.state_dir_world_writable", path = dir_str));
+        }
+    }
+    
+    Ok(dir)
+}
+
+/// Create the state directory with secure permissions if it doesn't exist
+fn ensure_state_dir() -> Result<PathBuf> {
+    let state_dir = state_dir()?;
+    
+    if !state_dir.exists() {
+        fs::create_dir_all(&state_dir)
+            .context(t!("mapper.failed_create_state_dir").to_string())?;
+        fs::set_permissions(&state_dir, Permissions::from_mode(STATE_DIR_PERMS))
+            .context(t!("mapper.failed_set_state_dir_perms").to_string())?;
+    }
+    
+    Ok(state_dir)
+}
+
+/// Create the persistent state directory if enabled, forcing it to 0700
+/// 
+/// Unlike /run, the persistent directory lives on disk and may have been
+/// created by hand, so its permissions are reset every time.
+fn ensure_persistent_state_dir() -> Result<Option<PathBuf>> {
+    let Some(persistent_dir) = persistent_state_dir()? else {
+        return Ok(None);
+    };
+    
+    fs::create_dir_all(&persistent_dir)
+        .context(t!("mapper.failed_create_state_dir").to_string())?;
+    fs::set_permissions(&persistent_dir, Permissions::from_mode(STATE_DIR_PERMS))
+        .context(t!("mapper.failed_set_state_dir_perms").to_string())?;
+    
+    Ok(Some(persistent_dir))
+}
+
+/// Run `f` while holding an exclusive lock on the state directory
+/// 
+/// Serializes mapper name allocation and state file writes between
+/// concurrent luksctl processes. The lock is an advisory `flock` on
+/// `.lock` inside the state directory and is released when the lock file
+/// is closed, so it is dropped on every return path including errors.
+/// Gives up with an error if the lock can't be acquired within a few seconds.
+/// In dry-run mode `f` runs without touching the state directory.
+/// 
+/// `f` may use any error type a [`LuksError`] converts into, such as
+/// `anyhow::Error`.
+pub fn with_state_lock<T, E: From<LuksError>>(f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    if is_dry_run() {
+        return f();
+    }
+    
+    // The lock is held until `_lock_file` is dropped at the end of this scope
+    let _lock_file = lock_state_dir()?;
+    f()
+}
+
+/// Open the state lock file and take an exclusive lock on it
+fn lock_state_dir() -> Result<File> {
+    let state_dir = ensure_state_dir()?;
+    
+    let lock_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(STATE_FILE_PERMS)
+        .open(state_dir.join(STATE_LOCK_FILE))
+        .context(t!("mapper.failed_open_lock_file").to_string())?;
+    
+    let deadline = Instant::now() + STATE_LOCK_TIMEOUT;
+    loop {
+        match flock(lock_file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            Ok(()) => return Ok(lock_file),
+            Err(Errno::EWOULDBLOCK) => {
+                if Instant::now() >= deadline {
+                    bail!("{}", t!("mapper.state_lock_timeout", secs = STATE_LOCK_TIMEOUT.as_secs()));
+                }
+                thread::sleep(STATE_LOCK_POLL_INTERVAL);
+            }
+            Err(Errno::EINTR) => continue,
+            Err(e) => {
+                return Err(anyhow::Error::new(e).context(t!("mapper.failed_lock_state").to_string()).into());
+            }
+        }
+    }
 }
 
 /// Store the mapping between mount point and mapper name
 /// 
+/// The whole `MountState` is recorded, including the mount options and
+/// header, so they can later be inspected or reapplied. Nothing is written
+/// in dry-run mode.
+/// 
 /// # Security
 /// - Creates state directory with restricted permissions (0700)
 /// - Creates state files with restricted permissions (0600)
 /// - Validates all inputs before writing
-pub fn store_mount_mapping(mount_point: &Path, mapper_name: &str, device: &Path) -> Result<()> {
+pub fn store_mount_mapping(state: &MountState) -> Result<()> {
     // Validate inputs
-    validate_mapper_name(mapper_name)?;
+    validate_mapper_name(&state.mapper_name)?;
     
-    let escaped_mount = escape_mount_path(mount_point)?;
+    let escaped_mount = escape_mount_path(&state.mount_point)?;
     
-    let state_dir = Path::new(MAPPER_STATE_DIR);
+    if is_dry_run() {
+        return Ok(());
+    }
     
-    // Create state directory with secure permissions
-    if !state_dir.exists() {
-        fs::create_dir_all(state_dir)
-            .context(t!("mapper.failed_create_state_dir").to_string())?;
-        fs::set_permissions(state_dir, Permissions::from_mode(STATE_DIR_PERMS))
-            .context(t!("mapper.failed_set_state_dir_perms").to_string())?;
+    let state_dir = ensure_state_dir()?;
+    let content = serialize_state(state)?;
+    
+    write_state_file(&state_dir.join(&escaped_mount), &content)?;
+    
+    // Keep a copy that survives a reboot, if enabled
+    if let Some(persistent_dir) = ensure_persistent_state_dir()? {
+        write_state_file(&persistent_dir.join(&escaped_mount), &content)?;
     }
     
-    let state_file = state_dir.join(&escaped_mount);
-    let content = format!("{}:{}", mapper_name, device.to_string_lossy());
+    Ok(())
+}
+
+/// Serialize a state in the current format
+/// 
+/// The version is always set to [`STATE_VERSION`], so a state read from an
+/// older file is migrated when written back.
+fn serialize_state(state: &MountState) -> Result<String> {
+    let state = MountState { version: STATE_VERSION, ..state.clone() };
+    let content = serde_json::to_string(&state)
+        .context(t!("mapper.failed_serialize_state").to_string())?;
+    
+    if content.len() > MAX_STATE_CONTENT_LEN {
+        bail!("{}", t!("mapper.state_content_too_large"));
+    }
     
+    Ok(content)
+}
+
+/// Write a state file, created or reset to 0600
+fn write_state_file(state_file: &Path, content: &str) -> Result<()> {
     // Create file with secure permissions atomically
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
         .mode(STATE_FILE_PERMS)
-        .open(&state_file)
+        .open(state_file)
+        .context(t!("mapper.failed_create_state_file").to_string())?;
+    
+    // The mode only applies to new files; an existing one may be looser
+    file.set_permissions(Permissions::from_mode(STATE_FILE_PERMS))
         .context(t!("mapper.failed_create_state_file").to_string())?;
     
     file.write_all(content.as_bytes())
@@ -137,70 +726,303 @@ pub fn store_mount_mapping(mount_point: &Path, mapper_name: &str, device: &Path)
     Ok(())
 }
 
-/// Retrieve the mapper name and device for a mount point
+/// Parse state file content
 /// 
-/// # Security
-/// - Validates the state file content format
-/// - Validates retrieved mapper name
-pub fn get_mount_mapping(mount_point: &Path) -> Result<Option<(String, PathBuf)>> {
-    let escaped_mount = escape_mount_path(mount_point)?;
-    
-    let state_file = Path::new(MAPPER_STATE_DIR).join(escaped_mount);
+/// `escaped_name` is the state file name, used to recover the mount point
+/// from legacy `mapper_name:device` content, which does not record it.
+/// Returns `Ok(None)` when the content is in neither format, and an error
+/// for a file written by a newer release in a format this one can't read.
+fn parse_state_content(content: &str, escaped_name: &str) -> Result<Option<MountState>> {
+    let state = if content.trim_start().starts_with('{') {
+        match serde_json::from_str::<MountState>(content) {
+            Ok(state) if state.version > STATE_VERSION => {
+                bail!("{}", t!("mapper.state_version_unsupported", version = state.version, supported = STATE_VERSION));
+            }
+            Ok(state) => state,
+            Err(_) => return Ok(None),
+        }
+    } else {
+        let parts: Vec<&str> = content.splitn(2, ':').collect();
+        if parts.len() != 2 {
+            return Ok(None);
+        }
+        
+        MountState {
+            version: 0,
+            mapper_name: parts[0].to_string(),
+            device: PathBuf::from(parts[1]),
+            device_input: None,
+            mount_point: unescape_mount_path(escaped_name),
+            header: None,
+            mount_options: None,
+            loop_device: None,
+            binds: Vec::new(),
+            crypt_read_only: false,
+            fs_type: None,
+            mounted_at: None,
+            uid: None,
+            owner: None,
+            created_dir: false,
+        }
+    };
     
-    if !state_file.exists() {
-        return Ok(None);
-    }
+    // Validate the retrieved mapper name
+    validate_mapper_name(&state.mapper_name)?;
     
+    Ok(Some(state))
+}
+
+/// Read and parse a single state file
+/// 
+/// # Security
+/// - Verifies the state file is a regular file (not a symlink)
+/// - Limits content size
+/// - Validates the retrieved mapper name
+fn read_state_file(state_file: &Path) -> Result<Option<MountState>> {
     // Verify the state file is actually a file (not a symlink attack)
-    let metadata = fs::symlink_metadata(&state_file)
+    let metadata = fs::symlink_metadata(state_file)
         .context(t!("mapper.failed_get_metadata").to_string())?;
     
     if !metadata.is_file() {
         bail!("{}", t!("mapper.state_not_regular_file"));
     }
     
-    let content = fs::read_to_string(&state_file)
+    let content = fs::read_to_string(state_file)
         .context(t!("mapper.failed_read_state_file").to_string())?;
     
     // Limit content size to prevent DoS
-    if content.len() > 1024 {
+    if content.len() > MAX_STATE_CONTENT_LEN {
         bail!("{}", t!("mapper.state_content_too_large"));
     }
     
-    let parts: Vec<&str> = content.splitn(2, ':').collect();
-    if parts.len() != 2 {
+    let escaped_name = state_file.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    
+    parse_state_content(&content, &escaped_name)
+}
+
+/// Retrieve the recorded state for a mount point
+/// 
+/// # Security
+/// - Validates the state file content format
+/// - Validates retrieved mapper name
+pub fn get_mount_mapping(mount_point: &Path) -> Result<Option<MountState>> {
+    Ok(find_state_file(mount_point)?.map(|(_, state)| state))
+}
+
+/// Find and read the state file recording `mount_point`
+/// 
+/// Files written by older releases under the unhashed name are accepted
+/// only if they record this mount point, since another path may escape to
+/// the same legacy name.
+fn find_state_file(mount_point: &Path) -> Result<Option<(PathBuf, MountState)>> {
+    let state_dir = state_dir()?;
+    
+    let state_file = state_dir.join(escape_mount_path(mount_point)?);
+    if state_file.exists() {
+        return Ok(read_state_file(&state_file)?.map(|state| (state_file, state)));
+    }
+    
+    let legacy_file = state_dir.join(legacy_escape_mount_path(mount_point)?);
+    if legacy_file.exists() {
+        if let Some(state) = read_state_file(&legacy_file)? {
+            if state.mount_point == mount_point {
+                return Ok(Some((legacy_file, state)));
+            }
+        }
+    }
+    
+    Ok(None)
+}
+
+/// Recover the original mount point from an escaped state file name
+/// 
+/// The mount point stored inside the state file is authoritative. State
+/// files written by older releases don't record it, in which case the name
+/// is naively un-escaped, which is ambiguous for paths containing `_`.
+/// 
+/// # Security
+/// - Rejects names containing path separators or traversal
+pub fn get_mount_mapping_reverse(escaped_name: &str) -> Result<Option<PathBuf>> {
+    if escaped_name.contains('/') || escaped_name.contains('\0')
+        || escaped_name.starts_with('.') {
+        bail!("{}", t!("mapper.path_traversal_detected"));
+    }
+    
+    let state_file = state_dir()?.join(escaped_name);
+    
+    if !state_file.exists() {
         return Ok(None);
     }
     
-    let mapper_name = parts[0].to_string();
-    let device_path = PathBuf::from(parts[1]);
+    Ok(read_state_file(&state_file)?.map(|state| state.mount_point))
+}
+
+/// Naively recover a mount point from its escaped state file name
+fn unescape_mount_path(escaped: &str) -> PathBuf {
+    PathBuf::from(escaped.replace('_', "/"))
+}
+
+/// List all mappings recorded in the state directory
+/// 
+/// Returns `(mount_point, mapper_name, device)` for each state file.
+/// Malformed entries and entries that are not regular files are skipped
+/// with a warning rather than aborting the whole listing.
+/// 
+/// # Security
+/// - Every entry is read through the same validation as `get_mount_mapping`
+/// - Hidden files in the state directory are ignored
+pub fn list_mappings() -> Result<Vec<(PathBuf, String, PathBuf)>> {
+    let mut mappings: Vec<_> = read_state_dir(&state_dir()?)?
+        .into_iter()
+        .map(|(_, state)| (state.mount_point, state.mapper_name, state.device))
+        .collect();
     
-    // Validate the retrieved mapper name
-    validate_mapper_name(&mapper_name)?;
+    mappings.sort();
+    Ok(mappings)
+}
+
+/// Read every state file in `dir`, with its path
+/// 
+/// Malformed entries are skipped with a warning; a missing directory has
+/// no entries.
+fn read_state_dir(dir: &Path) -> Result<Vec<(PathBuf, MountState)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
     
-    Ok(Some((mapper_name, device_path)))
+    let entries = fs::read_dir(dir)
+        .context(t!("mapper.failed_read_state_dir").to_string())?;
+    
+    let mut states = Vec::new();
+    for entry in entries {
+        let entry = entry.context(t!("mapper.failed_read_state_dir").to_string())?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        
+        // Escaped names never start with a dot; anything else is not ours
+        if file_name.starts_with('.') {
+            continue;
+        }
+        
+        match read_state_file(&entry.path()) {
+            Ok(Some(state)) => states.push((entry.path(), state)),
+            Ok(None) => {
+                warn!("{}", t!("mapper.warning_skipping_state_file",
+                    name = &file_name, error = t!("mapper.state_malformed")));
+            }
+            Err(e) => {
+                warn!("{}", t!("mapper.warning_skipping_state_file",
+                    name = &file_name, error = e.to_string()));
+            }
+        }
+    }
+    
+    Ok(states)
+}
+
+/// Outcome of [`reconcile_state`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Mount points whose state was dropped because the mapper is gone
+    pub removed: Vec<PathBuf>,
+    /// Mount points whose runtime state was restored from the persistent copy
+    pub restored: Vec<PathBuf>,
+}
+
+/// Bring the state files in line with the mappers actually open
+/// 
+/// Meant to run at boot, or after an unclean shutdown. Entries, in the
+/// runtime and the persistent store, whose mapper no longer exists are
+/// removed. Entries of the persistent store whose mapper is still open but
+/// that are missing from the runtime store are copied back. Nothing is
+/// changed in dry-run mode; the report says what would be.
+/// 
+/// # Security
+/// - Every entry is read through the same validation as `get_mount_mapping`
+/// - Restored files are written with the usual 0600 permissions
+pub fn reconcile_state() -> Result<ReconcileReport> {
+    with_state_lock(|| {
+        let mut report = ReconcileReport::default();
+        let state_dir = state_dir()?;
+        let persistent_dir = persistent_state_dir()?;
+        
+        for dir in std::iter::once(&state_dir).chain(persistent_dir.as_ref()) {
+            for (state_file, state) in read_state_dir(dir)? {
+                if mapper_exists(&state.mapper_name) {
+                    if dir != &state_dir && get_mount_mapping(&state.mount_point)?.is_none() {
+                        if !is_dry_run() {
+                            let content = serialize_state(&state)?;
+                            write_state_file(&state_dir.join(escape_mount_path(&state.mount_point)?), &content)?;
+                        }
+                        report.restored.push(state.mount_point);
+                    }
+                    continue;
+                }
+                
+                if !is_dry_run() {
+                    remove_state_file(&state_file)?;
+                }
+                if !report.removed.contains(&state.mount_point) {
+                    report.removed.push(state.mount_point);
+                }
+            }
+        }
+        
+        Ok(report)
+    })
+}
+
+/// Find the state recorded for a mapper, whatever its mount point
+/// 
+/// # Security
+/// - Every entry is read through the same validation as `get_mount_mapping`
+pub fn find_state_by_mapper(mapper_name: &str) -> Result<Option<MountState>> {
+    Ok(read_state_dir(&state_dir()?)?
+        .into_iter()
+        .map(|(_, state)| state)
+        .find(|state| state.mapper_name == mapper_name))
 }
 
 /// Remove the mapping for a mount point
 /// 
+/// Nothing is removed in dry-run mode.
+/// 
 /// # Security
 /// - Validates mount point before removing
 /// - Verifies target is a regular file
 pub fn remove_mount_mapping(mount_point: &Path) -> Result<()> {
     let escaped_mount = escape_mount_path(mount_point)?;
     
-    let state_file = Path::new(MAPPER_STATE_DIR).join(escaped_mount);
+    if is_dry_run() {
+        return Ok(());
+    }
+    
+    remove_state_file(&state_dir()?.join(&escaped_mount))?;
+    if let Some(persistent_dir) = persistent_state_dir()? {
+        remove_state_file(&persistent_dir.join(&escaped_mount))?;
+    }
     
+    // Also clear a state file an older release left under the legacy name
+    if let Ok(Some((legacy_file, _))) = find_state_file(mount_point) {
+        remove_state_file(&legacy_file)?;
+    }
+    
+    Ok(())
+}
+
+/// Remove a single state file if it exists
+fn remove_state_file(state_file: &Path) -> Result<()> {
     if state_file.exists() {
         // Verify it's a regular file before removing
-        let metadata = fs::symlink_metadata(&state_file)
+        let metadata = fs::symlink_metadata(state_file)
             .context(t!("mapper.failed_get_metadata").to_string())?;
         
         if !metadata.is_file() {
             bail!("{}", t!("mapper.state_not_regular_file"));
         }
         
-        fs::remove_file(&state_file)
+        fs::remove_file(state_file)
             .context(t!("mapper.failed_remove_state_file").to_string())?;
     }
     
@@ -213,32 +1035,301 @@ pub fn remove_mount_mapping(mount_point: &Path) -> Result<()> {
 /// - Validates found mapper names
 /// - Uses canonical paths for comparison
 pub fn find_mapper_by_mount_point(mount_point: &Path) -> Result<Option<String>> {
-    let mounts = fs::read_to_string("/proc/mounts")
-        .context(t!("mapper.failed_read_proc_mounts").to_string())?;
-    
-    let canonical_mount = mount_point.canonicalize()
-        .unwrap_or_else(|_| mount_point.to_path_buf());
-    
-    for line in mounts.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let mounted_on = Path::new(parts[1]);
-            let canonical_mounted = mounted_on.canonicalize()
-                .unwrap_or_else(|_| mounted_on.to_path_buf());
-            
-            if canonical_mounted == canonical_mount {
-                let device = parts[0];
-                if let Some(mapper_name) = device.strip_prefix("/dev/mapper/") {
-                    // Validate the mapper name before returning
-                    if mapper_name.starts_with("luks-") && 
-                       !mapper_name.contains('/') && 
-                       !mapper_name.contains('\0') {
-                        return Ok(Some(mapper_name.to_string()));
-                    }
-                }
+    // With stacked mounts, prefer the topmost luksctl mapper
+    let found = mount_entries(mount_point)?
+        .into_iter()
+        .rev()
+        .filter_map(|entry| entry.device.strip_prefix("/dev/mapper/").map(str::to_string))
+        .find(|mapper_name| validate_mapper_name(mapper_name).is_ok());
+    
+    Ok(found)
+}
+
+/// Pick the mapper backing a mount point from the recorded and mounted names
+/// 
+/// The state file is preferred, but if /proc/mounts shows a different
+/// mapper on the mount point the state file is stale and the kernel's view
+/// wins.
+fn reconcile_mapper_name(recorded: Option<String>, mounted: Option<String>) -> Result<Option<String>> {
+    let mapper_name = match (recorded, mounted) {
+        (Some(recorded), Some(mounted)) if recorded != mounted => {
+            warn!("{}", t!("mapper.warning_state_mismatch", state = &recorded, mounted = &mounted));
+            Some(mounted)
+        }
+        (Some(recorded), _) => Some(recorded),
+        (None, mounted) => mounted,
+    };
+    
+    if let Some(ref name) = mapper_name {
+        validate_mapper_name(name)?;
+    }
+    
+    Ok(mapper_name)
+}
+
+/// Resolve the luksctl mapper backing a mount point
+/// 
+/// Consults the state file first and falls back to /proc/mounts for
+/// volumes mounted without a state file. When the two disagree, the mapper
+/// actually mounted is returned.
+/// 
+/// # Security
+/// - The returned mapper name is validated (`luks-` prefix, safe characters)
+pub fn resolve_mapper_name(mount_point: &Path) -> Result<Option<String>> {
+    let recorded = get_mount_mapping(mount_point)?.map(|state| state.mapper_name);
+    let mounted = find_mapper_by_mount_point(mount_point)?;
+    reconcile_mapper_name(recorded, mounted)
+}
+
+/// Find an open dm device backed by `device`
+/// 
+/// The kernel lists the dm devices stacked on a block device under its
+/// `holders` directory in sysfs, which catches mappings opened outside
+/// luksctl too. Mappings recorded in the state files whose mapper still
+/// exists are checked as well, in case sysfs can't be read.
+pub fn find_mapper_by_device(device: &Path) -> Result<Option<String>> {
+    let metadata = fs::metadata(device)
+        .with_context(|| t!("mapper.failed_read_device", path = device.display().to_string()).to_string())?;
+    
+    let holders_dir = format!("{}/{}:{}/holders", SYS_DEV_BLOCK, major(metadata.rdev()), minor(metadata.rdev()));
+    if let Ok(holders) = fs::read_dir(holders_dir) {
+        for holder in holders.flatten() {
+            if let Ok(name) = fs::read_to_string(holder.path().join("dm/name")) {
+                return Ok(Some(name.trim_end().to_string()));
             }
         }
     }
     
-    Ok(None)
+    let device = device.canonicalize().unwrap_or_else(|_| device.to_path_buf());
+    let recorded = list_mappings()?
+        .into_iter()
+        .find(|(_, mapper_name, recorded)| {
+            mapper_exists(mapper_name)
+                && recorded.canonicalize().unwrap_or_else(|_| recorded.clone()) == device
+        })
+        .map(|(_, mapper_name, _)| mapper_name);
+    
+    Ok(recorded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_mapper_device_rejects_substitutes() {
+        let file = std::env::temp_dir().join(format!("luksctl-fake-mapper-{}", std::process::id()));
+        fs::write(&file, b"").unwrap();
+        let link = file.with_extension("link");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        assert!(validate_mapper_device("luks-test", &file).is_err());
+        assert!(validate_mapper_device("luks-test", &link).is_err());
+        assert!(validate_mapper_device("luks-test", Path::new("/dev/null")).is_err());
+        assert!(validate_mapper_device("luks-test", Path::new("/dev/mapper/luks-missing")).is_err());
+
+        fs::remove_file(&link).unwrap();
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_parse_state_content_recovers_mount_point() {
+        let state = MountState {
+            version: STATE_VERSION,
+            mapper_name: "luks-test".to_string(),
+            device: PathBuf::from("/dev/sda1"),
+            device_input: Some("/dev/disk/by-uuid/1234".to_string()),
+            mount_point: PathBuf::from("/mnt/foo_bar"),
+            header: None,
+            mount_options: Some(MountOptions {
+                read_only: true,
+                fs_type: Some("ext4".to_string()),
+                ..Default::default()
+            }),
+            loop_device: None,
+            binds: vec![BindMount {
+                source: PathBuf::from("/mnt/foo_bar/app"),
+                target: PathBuf::from("/var/lib/app"),
+            }],
+            crypt_read_only: true,
+            fs_type: Some("ext4".to_string()),
+            mounted_at: Some(1_700_000_000),
+            uid: Some(0),
+            owner: Some(MountOwner { uid: 1000, name: Some("alice".to_string()) }),
+            created_dir: true,
+        };
+        let content = serialize_state(&state).unwrap();
+
+        let parsed = parse_state_content(&content, "_mnt_foo_bar").unwrap().unwrap();
+        assert_eq!(parsed, state);
+        assert_eq!(parsed.mount_point, PathBuf::from("/mnt/foo_bar"));
+        assert_eq!(parsed.device, PathBuf::from("/dev/sda1"));
+        assert_eq!(parsed.device_input, state.device_input);
+        assert_eq!(parsed.mount_options, state.mount_options);
+        assert_eq!(parsed.binds, state.binds);
+        assert!(parsed.crypt_read_only);
+    }
+
+    #[test]
+    fn test_escape_mount_path_avoids_collisions() {
+        for (a, b) in [("/a_b", "/a/b"), ("/mnt/data_1", "/mnt/data/1"), ("/mnt/x_", "/mnt/x/")] {
+            let (a, b) = (Path::new(a), Path::new(b));
+            assert_eq!(legacy_escape_mount_path(a).unwrap(), legacy_escape_mount_path(b).unwrap());
+            assert_ne!(escape_mount_path(a).unwrap(), escape_mount_path(b).unwrap());
+        }
+
+        let escaped = escape_mount_path(Path::new("/mnt/data")).unwrap();
+        assert_eq!(escaped, escape_mount_path(Path::new("/mnt/data")).unwrap());
+        assert!(escaped.starts_with("_mnt_data-"));
+        assert_eq!(escaped.len(), "_mnt_data-".len() + STATE_NAME_HASH_LEN);
+
+        assert!(escape_mount_path(Path::new("/mnt/a..b")).unwrap().starts_with("_mnt_a..b-"));
+        assert!(escape_mount_path(Path::new("/mnt/../etc")).is_err());
+    }
+
+    #[test]
+    fn test_parse_state_content_legacy_format() {
+        let parsed = parse_state_content("luks-test:/dev/sda1", "_mnt_data").unwrap().unwrap();
+        assert_eq!(parsed.mapper_name, "luks-test");
+        assert_eq!(parsed.device, PathBuf::from("/dev/sda1"));
+        assert_eq!(parsed.mount_point, PathBuf::from("/mnt/data"));
+        assert!(parsed.mount_options.is_none());
+        assert_eq!(parsed.version, 0);
+
+        assert!(parse_state_content("garbage", "_mnt_data").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_state_content_versions() {
+        // Unversioned JSON from older releases reads as version 0
+        let unversioned = r#"{"mapper_name":"luks-test","device":"/dev/sda1","mount_point":"/mnt/data"}"#;
+        let parsed = parse_state_content(unversioned, "_mnt_data").unwrap().unwrap();
+        assert_eq!(parsed.version, 0);
+        assert!(parsed.mounted_at.is_none());
+
+        // Written back, it is migrated to the current version
+        let migrated: serde_json::Value = serde_json::from_str(&serialize_state(&parsed).unwrap()).unwrap();
+        assert_eq!(migrated["version"], STATE_VERSION);
+
+        // A format from a newer release is refused rather than misread
+        let newer = unversioned.replacen('{', &format!(r#"{{"version":{},"#, STATE_VERSION + 1), 1);
+        assert!(parse_state_content(&newer, "_mnt_data").is_err());
+    }
+
+    #[test]
+    fn test_resolve_state_dir_override() {
+        assert_eq!(resolve_state_dir(None).unwrap(), PathBuf::from(MAPPER_STATE_DIR));
+        assert_eq!(resolve_state_dir(Some(PathBuf::new())).unwrap(), PathBuf::from(MAPPER_STATE_DIR));
+        assert!(resolve_state_dir(Some(PathBuf::from("relative/state"))).is_err());
+        assert!(resolve_state_dir(Some(PathBuf::from("/tmp/../etc"))).is_err());
+
+        let base = std::env::temp_dir().join(format!("luksctl-state-test-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        fs::set_permissions(&base, Permissions::from_mode(0o700)).unwrap();
+        assert_eq!(resolve_state_dir(Some(base.clone())).unwrap(), base);
+
+        fs::set_permissions(&base, Permissions::from_mode(0o777)).unwrap();
+        assert!(resolve_state_dir(Some(base.clone())).is_err());
+        fs::remove_dir(&base).unwrap();
+    }
+
+    #[test]
+    fn test_write_and_read_state_dir() {
+        let dir = std::env::temp_dir().join(format!("luksctl-state-dir-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let state = MountState {
+            version: STATE_VERSION,
+            mapper_name: "luks-test".to_string(),
+            device: PathBuf::from("/dev/sda1"),
+            device_input: None,
+            mount_point: PathBuf::from("/mnt/data"),
+            header: None,
+            mount_options: None,
+            loop_device: None,
+            binds: Vec::new(),
+            crypt_read_only: false,
+            fs_type: None,
+            mounted_at: None,
+            uid: None,
+            owner: None,
+            created_dir: false,
+        };
+        let state_file = dir.join(escape_mount_path(&state.mount_point).unwrap());
+
+        // A pre-existing, looser file is tightened when rewritten
+        fs::write(&state_file, "").unwrap();
+        fs::set_permissions(&state_file, Permissions::from_mode(0o644)).unwrap();
+        write_state_file(&state_file, &serialize_state(&state).unwrap()).unwrap();
+        assert_eq!(fs::metadata(&state_file).unwrap().permissions().mode() & 0o777, STATE_FILE_PERMS);
+
+        fs::write(dir.join(STATE_LOCK_FILE), "").unwrap();
+        fs::write(dir.join("_mnt_garbage"), "not a state file").unwrap();
+        assert_eq!(read_state_dir(&dir).unwrap(), [(state_file, state)]);
+        assert!(read_state_dir(&dir.join("missing")).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reconcile_mapper_name() {
+        let name = |s: &str| Some(s.to_string());
+
+        assert_eq!(reconcile_mapper_name(name("luks-a"), name("luks-a")).unwrap(), name("luks-a"));
+        assert_eq!(reconcile_mapper_name(name("luks-a"), None).unwrap(), name("luks-a"));
+        assert_eq!(reconcile_mapper_name(None, name("luks-b")).unwrap(), name("luks-b"));
+        assert_eq!(reconcile_mapper_name(None, None).unwrap(), None);
+
+        // A stale state file loses to what is actually mounted
+        assert_eq!(reconcile_mapper_name(name("luks-a"), name("luks-b")).unwrap(), name("luks-b"));
+
+        assert!(reconcile_mapper_name(name("luks-a/../x"), None).is_err());
+        assert!(reconcile_mapper_name(name("other"), None).is_err());
+    }
+
+    #[test]
+    fn test_custom_mapper_name() {
+        assert_eq!(custom_mapper_name("backup").unwrap(), "luks-backup");
+        assert_eq!(custom_mapper_name("luks-backup").unwrap(), "luks-backup");
+        assert!(custom_mapper_name("../backup").is_err());
+        assert!(custom_mapper_name("back up").is_err());
+    }
+
+    #[test]
+    fn test_resolve_mapper_prefix() {
+        assert_eq!(resolve_mapper_prefix(None).unwrap(), "luks-");
+        assert_eq!(resolve_mapper_prefix(Some(String::new())).unwrap(), "luks-");
+        assert_eq!(resolve_mapper_prefix(Some("myapp-luks-".to_string())).unwrap(), "myapp-luks-");
+        assert!(resolve_mapper_prefix(Some("MyApp-".to_string())).is_err());
+        assert!(resolve_mapper_prefix(Some("-luks-".to_string())).is_err());
+        assert!(resolve_mapper_prefix(Some("a/b-".to_string())).is_err());
+        assert!(resolve_mapper_prefix(Some("x".repeat(MAX_MAPPER_PREFIX_LEN + 1))).is_err());
+
+        // Generated names are accepted by both the mapper and luks modules
+        let name = generate_mapper_name().unwrap();
+        assert!(validate_mapper_name(&name).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_name_retries() {
+        assert_eq!(resolve_name_retries(None).unwrap(), DEFAULT_NAME_RETRIES);
+        assert_eq!(resolve_name_retries(Some(String::new())).unwrap(), DEFAULT_NAME_RETRIES);
+        assert_eq!(resolve_name_retries(Some("3".to_string())).unwrap(), 3);
+        assert!(resolve_name_retries(Some("0".to_string())).is_err());
+        assert!(resolve_name_retries(Some("many".to_string())).is_err());
+        assert!(resolve_name_retries(Some((MAX_NAME_RETRIES + 1).to_string())).is_err());
+    }
+
+    #[test]
+    fn test_allocate_unique_name_retries_collisions() {
+        let mut names = ["a", "b", "c"].into_iter().map(str::to_string);
+        let name = allocate_unique_name_with("luks-", 3, || names.next().unwrap(), |name| name != "luks-c").unwrap();
+        assert_eq!(name, "luks-c");
+
+        // Every attempt collides
+        assert!(allocate_unique_name_with("luks-", 2, || "a".to_string(), |_| true).is_err());
+
+        // Names outside the managed prefix are refused
+        assert!(allocate_unique_name_with("other-", 1, || "a".to_string(), |_| false).is_err());
+    }
 }
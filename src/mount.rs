@@ -5,10 +5,21 @@
 //! - Path validation to prevent attacks
 //! - Safe command execution
 
-use anyhow::{bail, Context, Result};
+use anyhow::Context;
+use log::{debug, warn};
 use rust_i18n::t;
-use std::path::Path;
-use std::process::Command;
+use serde::{Deserialize, Serialize};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use nix::unistd::{Gid, Group, Uid, User};
+
+use crate::command::{is_dry_run, print_dry_run, run};
+use crate::error::{bail, LuksError, Result};
+use crate::pathsafe::{ensure_no_traversal, ensure_safe_absolute, ensure_under_dev};
+use crate::tools::{tool_command, tool_path, Tool};
 
 /// Allowed filesystem types (whitelist approach)
 const ALLOWED_FS_TYPES: &[&str] = &[
@@ -23,16 +34,260 @@ const FORBIDDEN_MOUNT_OPTIONS: &[&str] = &[
     "exec",     // Allow execution - be explicit about this
 ];
 
+/// SELinux options whose value is a security context (`context="..."`)
+const CONTEXT_MOUNT_OPTIONS: &[&str] = &["context", "fscontext", "defcontext"];
+
+/// Mount option pairs that cancel each other out
+const CONFLICTING_MOUNT_OPTIONS: &[(&str, &str)] = &[
+    ("ro", "rw"),
+    ("sync", "async"),
+    ("exec", "noexec"),
+    ("noatime", "atime"),
+    ("noatime", "relatime"),
+    ("noatime", "strictatime"),
+    ("relatime", "norelatime"),
+    ("relatime", "strictatime"),
+    ("nodiratime", "diratime"),
+];
+
+/// Hardening options applied to every mount
+/// 
+/// `secure()` is the default. Each flag maps onto one `--allow-*` option,
+/// and `--no-hardening` starts from `permissive()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardeningProfile {
+    /// Ignore setuid/setgid bits
+    pub nosuid: bool,
+    /// Ignore device files
+    pub nodev: bool,
+    /// Disallow execution
+    pub noexec: bool,
+    /// Further options emitted after the flags (e.g. `nosymfollow`)
+    /// 
+    /// Validated like `--options`, so these can only add restrictions.
+    pub custom: Vec<String>,
+}
+
+impl HardeningProfile {
+    /// `nosuid,nodev,noexec`
+    pub fn secure() -> Self {
+        Self {
+            nosuid: true,
+            nodev: true,
+            noexec: true,
+            custom: Vec::new(),
+        }
+    }
+
+    /// No hardening options at all
+    pub fn permissive() -> Self {
+        Self {
+            nosuid: false,
+            nodev: false,
+            noexec: false,
+            custom: Vec::new(),
+        }
+    }
+}
+
+impl Default for HardeningProfile {
+    fn default() -> Self {
+        Self::secure()
+    }
+}
+
+/// Common mount options that have their own command-line flag
+/// 
+/// Each one is the mount option of the same name. They are merged with
+/// `--options` and checked against it for conflicts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MountFlags {
+    /// Don't update access times
+    pub noatime: bool,
+    /// Don't update directory access times
+    pub nodiratime: bool,
+    /// Update access times only when older than the modification time
+    pub relatime: bool,
+    /// Write synchronously
+    pub sync: bool,
+    /// Don't fail the boot if the device is missing (fstab only)
+    pub nofail: bool,
+}
+
+impl MountFlags {
+    /// The options of the flags that are set, in a fixed order
+    pub fn options(&self) -> Vec<String> {
+        [
+            (self.noatime, "noatime"),
+            (self.nodiratime, "nodiratime"),
+            (self.relatime, "relatime"),
+            (self.sync, "sync"),
+            (self.nofail, "nofail"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| name.to_string())
+        .collect()
+    }
+    
+    /// Whether no flag is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// Mount options structure
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MountOptions {
     pub read_only: bool,
     pub fs_type: Option<String>,
     pub options: Option<String>,
+    /// Options given as dedicated flags (`--noatime`, ...)
+    #[serde(skip_serializing_if = "MountFlags::is_empty")]
+    pub flags: MountFlags,
+    pub hardening: HardeningProfile,
+    /// Run the mount inside the mount namespace of this process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<u32>,
+}
+
+impl MountOptions {
+    /// Hardening options that will be applied to the mount, in a fixed order
+    pub fn hardening_options(&self) -> Vec<String> {
+        let mut opts = Vec::new();
+        if self.hardening.nosuid {
+            opts.push("nosuid".to_string());
+        }
+        if self.hardening.nodev {
+            opts.push("nodev".to_string());
+        }
+        // An explicit `exec` in the user's options takes precedence
+        if self.hardening.noexec && !self.has_user_option("exec") {
+            opts.push("noexec".to_string());
+        }
+        opts.extend(self.hardening.custom.iter().cloned());
+        opts
+    }
+
+    /// The complete `-o` option string: hardening, `ro`, the flags, then
+    /// the validated additional options
+    /// 
+    /// Repeated options are only emitted once. Contradictory options (such
+    /// as `--ro` together with `-o rw`) are rejected rather than leaving the
+    /// outcome to mount's last-one-wins parsing.
+    pub fn option_string(&self) -> Result<String> {
+        let mut opts = self.hardening_options();
+        if !self.hardening.custom.is_empty() {
+            validate_mount_options(&self.hardening.custom.join(","))?;
+        }
+        if self.read_only {
+            opts.push("ro".to_string());
+        }
+        opts.extend(self.flags.options());
+        if let Some(ref extra) = self.options {
+            opts.extend(validate_mount_options(extra)?);
+        }
+
+        let mut deduped: Vec<String> = Vec::with_capacity(opts.len());
+        for opt in opts {
+            if !deduped.iter().any(|seen| seen.eq_ignore_ascii_case(&opt)) {
+                deduped.push(opt);
+            }
+        }
+        check_option_conflicts(&deduped)?;
+
+        Ok(deduped.join(","))
+    }
+
+    /// Whether the additional options contain the given option name
+    pub fn has_user_option(&self, name: &str) -> bool {
+        self.options.as_deref().is_some_and(|opts| {
+            split_mount_options(opts).any(|opt| opt.trim().eq_ignore_ascii_case(name))
+        })
+    }
+}
+
+/// A single entry from /proc/mounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+impl MountEntry {
+    /// Whether the filesystem is mounted read-only
+    pub fn is_read_only(&self) -> bool {
+        self.options.iter().any(|opt| opt == "ro")
+    }
+}
+
+/// A directory inside a mounted volume bind-mounted to another location
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindMount {
+    /// Resolved source directory inside the volume
+    pub source: PathBuf,
+    /// Absolute target directory the source is mounted on
+    pub target: PathBuf,
+}
+
+/// Parse a `SRC:DST` bind specification
+/// 
+/// `SRC` is relative to the volume's mount point and `DST` must be absolute.
+/// 
+/// # Security
+/// - `SRC` may not be absolute or contain `..` components
+/// - `DST` must be absolute without `..` components
+pub fn parse_bind_spec(spec: &str) -> Result<(PathBuf, PathBuf)> {
+    let Some((source, target)) = spec.split_once(':') else {
+        bail!("{}", t!("mount.bind_spec_invalid", spec = spec));
+    };
+    
+    let source = PathBuf::from(source);
+    let target = PathBuf::from(target);
+    
+    if source.as_os_str().is_empty() || source.is_absolute() || ensure_no_traversal(&source).is_err() || spec.contains('\0') {
+        bail!("{}", t!("mount.bind_source_invalid", path = source.display().to_string()));
+    }
+    if ensure_safe_absolute(&target).is_err() {
+        bail!("{}", t!("mount.bind_target_invalid", path = target.display().to_string()));
+    }
+    
+    Ok((source, target))
+}
+
+/// Resolve a bind source relative to a volume's mount point
+/// 
+/// Symlinks are resolved and the result must still lie inside the volume.
+/// In dry-run mode the volume isn't mounted, so only the lexical checks done
+/// by `parse_bind_spec` apply.
+fn resolve_bind_source(mount_root: &Path, source: &Path) -> Result<PathBuf> {
+    let joined = mount_root.join(source);
+    if is_dry_run() {
+        return Ok(joined);
+    }
+    
+    let root = mount_root.canonicalize()
+        .context(t!("mount.invalid_mount_point_path").to_string())?;
+    let resolved = joined.canonicalize()
+        .with_context(|| t!("mount.bind_source_not_exist", path = joined.display().to_string()).to_string())?;
+    
+    if !resolved.starts_with(&root) {
+        bail!("{}", t!("mount.bind_source_escapes", path = source.display().to_string()));
+    }
+    if !resolved.is_dir() {
+        bail!("{}", t!("mount.mount_point_not_dir", path = resolved.display().to_string()));
+    }
+    
+    Ok(resolved)
 }
 
-/// Validate filesystem type
-fn validate_fs_type(fs_type: &str) -> Result<()> {
+/// Validate filesystem type against the whitelist
+pub fn validate_fs_type(fs_type: &str) -> Result<()> {
     // Check for null bytes or path separators
     if fs_type.contains('\0') || fs_type.contains('/') {
         bail!("{}", t!("mount.invalid_fs_type"));
@@ -52,8 +307,92 @@ fn validate_fs_type(fs_type: &str) -> Result<()> {
     Ok(())
 }
 
+/// Filesystem types in /proc/filesystems, block device ones only
+/// 
+/// Lines are either `nodev\t<type>` (virtual filesystems) or `\t<type>`.
+fn parse_proc_filesystems(content: &str) -> Vec<String> {
+    content.lines()
+        .filter(|line| !line.starts_with("nodev"))
+        .filter_map(|line| line.split_whitespace().last())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether modules.alias names a module the kernel loads for `fs_type`
+/// 
+/// The kernel requests `fs-<type>` when mounting a type it doesn't know.
+fn has_fs_module_alias(aliases: &str, fs_type: &str) -> bool {
+    let wanted = format!("fs-{}", fs_type);
+    aliases.lines().any(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next() == Some("alias") && fields.next() == Some(wanted.as_str())
+    })
+}
+
+/// Check that the running kernel can mount `fs_type`
+/// 
+/// Types in /proc/filesystems are fine. Any other type can only be mounted
+/// if a module for it is loaded on demand: with one, that is a warning;
+/// without one, the type is rejected before mount is run. Nothing is
+/// rejected when the kernel's lists can't be read.
+fn check_kernel_fs_type(fs_type: &str) -> Result<()> {
+    let fs_lower = fs_type.to_lowercase();
+    let Ok(filesystems) = std::fs::read_to_string("/proc/filesystems") else {
+        return Ok(());
+    };
+    if parse_proc_filesystems(&filesystems).contains(&fs_lower) {
+        return Ok(());
+    }
+    
+    let aliases = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()
+        .and_then(|release| std::fs::read_to_string(format!("/lib/modules/{}/modules.alias", release.trim())).ok());
+    match aliases {
+        Some(aliases) if !has_fs_module_alias(&aliases, &fs_lower) => {
+            bail!("{}", t!("mount.fs_type_not_in_kernel", fs_type = fs_type));
+        }
+        _ => {
+            warn!("{}", t!("mount.warning_fs_type_not_loaded", fs_type = fs_type));
+            Ok(())
+        }
+    }
+}
+
+/// Split a mount option list on commas outside double quotes
+/// 
+/// SELinux contexts may contain commas (`s0:c127,c456`), so mount accepts
+/// them quoted: `context="system_u:object_r:tmp_t:s0:c127,c456"`.
+fn split_mount_options(options: &str) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    options.split(move |c: char| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ',' && !in_quotes
+    })
+}
+
+/// Validate an SELinux context option such as `context="user:role:type:level"`
+/// 
+/// The label may be quoted (required if it contains a comma) and may only
+/// contain the characters SELinux labels are made of. The option is passed
+/// to mount as part of a single argument and never through a shell.
+fn validate_context_option(opt: &str) -> Result<()> {
+    let value = opt.split_once('=').map(|(_, value)| value).unwrap_or_default();
+    let label = match value.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"').unwrap_or_default(),
+        None => value,
+    };
+    
+    let valid_chars = label.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | ',' | '-'));
+    if label.is_empty() || !label.contains(':') || !valid_chars {
+        bail!("{}", t!("mount.invalid_context_option", opt = opt));
+    }
+    
+    Ok(())
+}
+
 /// Validate and sanitize mount options
-fn validate_mount_options(options: &str) -> Result<String> {
+fn validate_mount_options(options: &str) -> Result<Vec<String>> {
     // Check for null bytes
     if options.contains('\0') {
         bail!("{}", t!("mount.mount_options_null_bytes"));
@@ -67,13 +406,23 @@ fn validate_mount_options(options: &str) -> Result<String> {
     // Parse individual options and validate
     let mut validated_opts = Vec::new();
     
-    for opt in options.split(',') {
+    for opt in split_mount_options(options) {
         let opt = opt.trim();
         
         if opt.is_empty() {
             continue;
         }
         
+        // Extract option name (before '=' if present)
+        let opt_name = opt.split('=').next().unwrap_or(opt);
+        
+        // SELinux contexts need quotes and colons; they get their own check
+        if CONTEXT_MOUNT_OPTIONS.iter().any(|name| opt_name.eq_ignore_ascii_case(name)) {
+            validate_context_option(opt)?;
+            validated_opts.push(opt.to_string());
+            continue;
+        }
+        
         // Check for shell metacharacters and injection attempts
         if opt.contains(|c: char| {
             matches!(c, ';' | '&' | '|' | '$' | '`' | '\n' | '\r' | '\\' | '"' | '\'')
@@ -81,79 +430,130 @@ fn validate_mount_options(options: &str) -> Result<String> {
             bail!("{}", t!("mount.mount_option_forbidden_chars", opt = opt));
         }
         
-        // Extract option name (before '=' if present)
-        let opt_name = opt.split('=').next().unwrap_or(opt);
-        
         // Check against forbidden options
         for forbidden in FORBIDDEN_MOUNT_OPTIONS {
             if opt_name.eq_ignore_ascii_case(forbidden) {
                 // Note: We warn but don't fail - user might want these
-                eprintln!("{}", t!("mount.warning_dangerous_option", opt = opt_name));
+                warn!("{}", t!("mount.warning_dangerous_option", opt = opt_name));
             }
         }
         
         validated_opts.push(opt.to_string());
     }
     
-    Ok(validated_opts.join(","))
+    Ok(validated_opts)
+}
+
+/// Reject option lists containing both sides of a conflicting pair
+fn check_option_conflicts(opts: &[String]) -> Result<()> {
+    let has = |name: &str| opts.iter().any(|opt| opt.eq_ignore_ascii_case(name));
+    for (first, second) in CONFLICTING_MOUNT_OPTIONS {
+        if has(first) && has(second) {
+            bail!("{}", t!("mount.conflicting_options", first = first, second = second));
+        }
+    }
+    Ok(())
 }
 
 /// Validate mount point path
 fn validate_mount_point(mount_point: &Path) -> Result<()> {
-    // Must be absolute
-    if !mount_point.is_absolute() {
-        bail!("{}", t!("mount.mount_point_must_absolute"));
-    }
-    
-    let path_str = mount_point.to_string_lossy();
-    
-    // Check for null bytes
-    if path_str.contains('\0') {
-        bail!("{}", t!("mount.mount_point_null_bytes"));
-    }
-    
-    // Check for path traversal
-    if path_str.contains("..") {
-        bail!("{}", t!("mount.mount_point_path_traversal"));
-    }
+    validate_mount_point_under(Path::new("/"), mount_point)
+}
+
+/// Validate a mount point as seen from `root`
+/// 
+/// `mount_point` is the path the mount itself will use; only its copy
+/// under `root` (e.g. `/proc/<pid>/root` for another mount namespace) has
+/// to exist, since the path on the host may not.
+fn validate_mount_point_under(root: &Path, mount_point: &Path) -> Result<()> {
+    // Must be absolute, without null bytes or path traversal
+    ensure_safe_absolute(mount_point)?;
     
     // Must exist and be a directory
-    if !mount_point.exists() {
+    let view = rooted(root, mount_point);
+    if !view.exists() {
         bail!("{}", t!("mount.mount_point_not_exist", path = mount_point.display().to_string()));
     }
     
-    if !mount_point.is_dir() {
+    if !view.is_dir() {
         bail!("{}", t!("mount.mount_point_not_dir", path = mount_point.display().to_string()));
     }
     
     Ok(())
 }
 
+/// Path of the mount namespace of process `pid`
+/// 
+/// # Security
+/// - PID 0 is rejected
+/// - The namespace link must exist, so the process must still be running
+pub fn mount_namespace_path(pid: u32) -> Result<PathBuf> {
+    if pid == 0 {
+        bail!("{}", t!("mount.invalid_namespace_pid"));
+    }
+    
+    let path = PathBuf::from(format!("/proc/{}/ns/mnt", pid));
+    if path.symlink_metadata().is_err() {
+        bail!("{}", t!("mount.namespace_not_found", pid = pid, path = path.display().to_string()));
+    }
+    
+    Ok(path)
+}
+
+/// Absolute `path` resolved under `root` instead of `/`
+fn rooted(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Root directory of the mount namespace of process `pid`
+fn namespace_root(pid: u32) -> PathBuf {
+    PathBuf::from(format!("/proc/{}/root", pid))
+}
+
 /// Validate device path for mounting
 fn validate_device_for_mount(device: &Path) -> Result<()> {
-    // Must be absolute
-    if !device.is_absolute() {
-        bail!("{}", t!("mount.device_path_must_absolute"));
-    }
+    // Must be absolute, without null bytes or path traversal
+    ensure_safe_absolute(device)?;
     
-    let path_str = device.to_string_lossy();
+    // Should be under /dev/mapper for our use case
+    ensure_under_dev(device)?;
     
-    // Check for null bytes
-    if path_str.contains('\0') {
-        bail!("{}", t!("mount.device_path_null_bytes"));
+    // Must exist (the mapper is never opened in dry-run)
+    if !is_dry_run() && !device.exists() {
+        return Err(LuksError::DeviceNotFound { path: device.to_path_buf() });
     }
     
-    // Should be under /dev/mapper for our use case
-    if !path_str.starts_with("/dev/") {
-        bail!("{}", t!("mount.device_must_in_dev"));
+    Ok(())
+}
+
+/// Detect the filesystem type of a device with blkid
+/// 
+/// Returns `Ok(None)` when blkid finds no filesystem or reports a type that
+/// is not in the allowed list, so callers can fall back to the kernel's own
+/// probing.
+/// 
+/// # Security
+/// - Device path is validated before use
+/// - Only whitelisted filesystem types are ever returned
+pub fn detect_fs_type(device: &Path) -> Result<Option<String>> {
+    validate_device_for_mount(device)?;
+    
+    let mut cmd = tool_command(Tool::Blkid);
+    cmd.args(["-o", "value", "-s", "TYPE"]).arg(device);
+    let output = run(&mut cmd, None)
+        .context(t!("mount.failed_execute_blkid").to_string())?;
+    
+    if !output.status.success() {
+        return Ok(None);
     }
     
-    // Must exist
-    if !device.exists() {
-        bail!("{}", t!("mount.device_not_exist", path = device.display().to_string()));
+    let fs_type = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if validate_fs_type(&fs_type).is_err() {
+        debug!("{}", t!("mount.detected_fs_type_unsupported", fs_type = &fs_type));
+        return Ok(None);
     }
     
-    Ok(())
+    Ok(Some(fs_type))
 }
 
 /// Mount a device to a mount point
@@ -162,110 +562,851 @@ fn validate_device_for_mount(device: &Path) -> Result<()> {
 /// - Validates device path
 /// - Validates mount point
 /// - Validates and sanitizes mount options
-/// - Uses nosuid, nodev by default for security
+/// - Uses nosuid, nodev, noexec by default for security (see `HardeningProfile`)
+/// - With `options.namespace`, only mount itself runs in the target
+///   namespace (through `nsenter --mount`), and the mount point is checked
+///   as that namespace sees it
 pub fn mount_device(device: &Path, mount_point: &Path, options: &MountOptions) -> Result<()> {
     // Validate inputs
     validate_device_for_mount(device)?;
-    validate_mount_point(mount_point)?;
     
-    let mut cmd = Command::new("mount");
-    
-    // Build secure default options
-    let mut mount_opts = Vec::new();
-    
-    // Add security defaults
-    mount_opts.push("nosuid".to_string());  // Ignore setuid bits
-    mount_opts.push("nodev".to_string());   // Ignore device files
+    let mut cmd = match options.namespace {
+        Some(pid) => {
+            let namespace = mount_namespace_path(pid)?;
+            // The mount point only has to exist inside the namespace
+            validate_mount_point_under(&namespace_root(pid), mount_point)?;
+            // nsenter runs mount by the same absolute path inside the namespace
+            let mut cmd = tool_command(Tool::Nsenter);
+            cmd.arg(format!("--mount={}", namespace.display()))
+                .arg("--")
+                .arg(tool_path(Tool::Mount));
+            cmd
+        }
+        None => {
+            validate_mount_point(mount_point)?;
+            tool_command(Tool::Mount)
+        }
+    };
     
-    // Add read-only flag
-    if options.read_only {
-        mount_opts.push("ro".to_string());
-    }
+    // Build secure default options: hardening unless explicitly opted out,
+    // read-only flag and validated additional options
+    let mount_opts = options.option_string()?;
 
-    // Add filesystem type (validated)
-    if let Some(ref fs_type) = options.fs_type {
-        validate_fs_type(fs_type)?;
+    // Add filesystem type (validated), detecting it when not given; if
+    // detection fails the kernel probes the filesystem itself
+    let fs_type = match options.fs_type {
+        Some(ref fs_type) => {
+            validate_fs_type(fs_type)?;
+            // The whitelist is only a floor; fail early if this kernel can't
+            check_kernel_fs_type(fs_type)?;
+            Some(fs_type.clone())
+        }
+        None if is_dry_run() => None,
+        None => detect_fs_type(device).ok().flatten(),
+    };
+    if let Some(fs_type) = fs_type {
+        debug!("{}", t!("mount.using_fs_type", fs_type = &fs_type));
         cmd.arg("-t").arg(fs_type);
     }
 
-    // Add additional mount options (validated)
-    if let Some(ref opts) = options.options {
-        let validated = validate_mount_options(opts)?;
-        if !validated.is_empty() {
-            mount_opts.push(validated);
-        }
-    }
-    
     // Add all options
     if !mount_opts.is_empty() {
-        cmd.arg("-o").arg(mount_opts.join(","));
+        cmd.arg("-o").arg(mount_opts);
     }
 
     cmd.arg(device);
     cmd.arg(mount_point);
 
-    let output = cmd.output()
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+
+    let output = run(&mut cmd, None)
         .context(t!("mount.failed_execute_mount").to_string())?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("{}", t!("mount.failed_mount_device", error = stderr.trim()));
+        return Err(LuksError::command_failed(
+            "mount",
+            stderr.trim(),
+            t!("mount.failed_mount_device", error = stderr.trim()),
+        ));
     }
 
     Ok(())
 }
 
+/// Bind-mount a directory of a mounted volume onto `target`
+/// 
+/// `source` is relative to `mount_root`, the volume's mount point. The bind
+/// mount inherits the volume's nosuid/nodev/noexec flags.
+/// 
+/// # Security
+/// - Source must resolve inside `mount_root`, even through symlinks
+/// - Target is validated like a mount point
+pub fn bind_mount(mount_root: &Path, source: &Path, target: &Path) -> Result<BindMount> {
+    let resolved = resolve_bind_source(mount_root, source)?;
+    validate_mount_point(target)?;
+    
+    let mut cmd = tool_command(Tool::Mount);
+    cmd.arg("--bind").arg(&resolved).arg(target);
+    
+    if is_dry_run() {
+        print_dry_run(&cmd);
+    } else {
+        let output = run(&mut cmd, None)
+            .context(t!("mount.failed_execute_mount").to_string())?;
+        
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(LuksError::command_failed(
+                "mount",
+                stderr.trim(),
+                t!("mount.failed_mount_device", error = stderr.trim()),
+            ));
+        }
+    }
+    
+    Ok(BindMount { source: resolved, target: target.to_path_buf() })
+}
+
+/// Flush a mounted filesystem's dirty data to disk
+/// 
+/// Uses `syncfs(2)` on the mount point so only that filesystem is flushed,
+/// falling back to a system-wide `sync(2)` if the mount point can't be
+/// opened or syncfs fails.
+/// 
+/// # Security
+/// - Validates mount point path
+pub fn sync_mount(mount_point: &Path) -> Result<()> {
+    validate_mount_point(mount_point)?;
+    
+    let synced = std::fs::File::open(mount_point)
+        .ok()
+        .is_some_and(|dir| nix::unistd::syncfs(dir.as_raw_fd()).is_ok());
+    if !synced {
+        debug!("{}", t!("mount.syncfs_fallback", path = mount_point.display().to_string()));
+        nix::unistd::sync();
+    }
+    
+    Ok(())
+}
+
 /// Unmount a mount point
 /// 
 /// # Security
 /// - Validates mount point path
 pub fn unmount(mount_point: &Path) -> Result<()> {
-    // Validate mount point
-    if !mount_point.is_absolute() {
-        bail!("{}", t!("mount.mount_point_must_absolute"));
+    run_umount(mount_point, false)
+}
+
+/// Unmount a mount point, retrying while it is busy
+/// 
+/// Makes up to `retries + 1` attempts, sleeping `delay` between them, and
+/// returns the last error if every attempt fails.
+/// 
+/// # Security
+/// - Validates mount point path
+pub fn unmount_with_retry(mount_point: &Path, retries: u32, delay: Duration) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match unmount(mount_point) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= retries => return Err(e),
+            Err(e) => {
+                attempt += 1;
+                debug!("{}", t!("mount.retrying_unmount",
+                    attempt = attempt, retries = retries, error = e.to_string()));
+                thread::sleep(delay);
+            }
+        }
     }
+}
+
+/// Lazily unmount a mount point (`umount -l`)
+/// 
+/// The mount is detached immediately and cleaned up once it is no longer
+/// busy.
+/// 
+/// # Security
+/// - Validates mount point path
+pub fn unmount_lazy(mount_point: &Path) -> Result<()> {
+    run_umount(mount_point, true)
+}
+
+fn run_umount(mount_point: &Path, lazy: bool) -> Result<()> {
+    // Validate mount point
+    ensure_safe_absolute(mount_point)?;
     
-    let path_str = mount_point.to_string_lossy();
-    if path_str.contains('\0') || path_str.contains("..") {
-        bail!("{}", t!("mount.invalid_mount_point_path"));
+    let mut cmd = tool_command(Tool::Umount);
+    if lazy {
+        cmd.arg("-l");
     }
-    
-    let output = Command::new("umount")
-        .arg(mount_point)
-        .output()
+    cmd.arg(mount_point);
+
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+
+    let output = run(&mut cmd, None)
         .context(t!("mount.failed_execute_umount").to_string())?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("{}", t!("mount.failed_unmount", error = stderr.trim()));
+        
+        // Name the processes keeping the mount busy, if we can tell
+        let blockers = find_mount_blockers(mount_point).unwrap_or_default();
+        if !blockers.is_empty() {
+            let processes = blockers.iter()
+                .map(|(pid, name)| format!("{} ({})", pid, name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = t!("mount.failed_unmount_busy", error = stderr.trim(), processes = processes).to_string();
+            return Err(LuksError::MountBusy { mount_point: mount_point.to_path_buf(), blockers, message });
+        }
+        if stderr.contains("busy") {
+            let message = t!("mount.failed_unmount", error = stderr.trim()).to_string();
+            return Err(LuksError::MountBusy { mount_point: mount_point.to_path_buf(), blockers, message });
+        }
+        return Err(LuksError::command_failed(
+            "umount",
+            stderr.trim(),
+            t!("mount.failed_unmount", error = stderr.trim()),
+        ));
     }
 
     Ok(())
 }
 
+/// Find processes holding files open under a mount point
+/// 
+/// Scans the working directory, root, executable and open file descriptors
+/// of every process in /proc and returns `(pid, command name)` pairs sorted
+/// by pid. Processes that can't be inspected are skipped.
+pub fn find_mount_blockers(mount_point: &Path) -> Result<Vec<(u32, String)>> {
+    let processes = std::fs::read_dir("/proc")
+        .context(t!("mount.failed_read_proc").to_string())?;
+    
+    let mut blockers = Vec::new();
+    for entry in processes.flatten() {
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let proc_dir = entry.path();
+        
+        let mut links = vec![proc_dir.join("cwd"), proc_dir.join("root"), proc_dir.join("exe")];
+        if let Ok(fds) = std::fs::read_dir(proc_dir.join("fd")) {
+            links.extend(fds.flatten().map(|fd| fd.path()));
+        }
+        
+        let holds_mount = links.iter()
+            .filter_map(|link| std::fs::read_link(link).ok())
+            .any(|target| target.starts_with(mount_point));
+        if holds_mount {
+            let name = std::fs::read_to_string(proc_dir.join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_default();
+            blockers.push((pid, name));
+        }
+    }
+    
+    blockers.sort();
+    Ok(blockers)
+}
+
+/// Decode a field of /proc/mounts
+/// 
+/// The kernel writes space, tab, newline and backslash in device names and
+/// mount points as three-digit octal escapes (`/mnt/my\040data`). Anything
+/// that isn't a valid escape is kept as-is.
+pub fn decode_proc_mounts_field(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let digits = &bytes[i + 1..i + 4];
+            if digits.iter().all(|d| (b'0'..=b'7').contains(d)) {
+                let value = digits.iter().fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+                if let Ok(byte) = u8::try_from(value) {
+                    decoded.push(byte);
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parse the entries of /proc/mounts content
+fn parse_proc_mounts(content: &str) -> Vec<MountEntry> {
+    content.lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [device, mount_point, fs_type, options, ..] = parts[..] else {
+                return None;
+            };
+            Some(MountEntry {
+                device: decode_proc_mounts_field(device),
+                mount_point: PathBuf::from(decode_proc_mounts_field(mount_point)),
+                fs_type: fs_type.to_string(),
+                options: options.split(',').map(str::to_string).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Parse the entries of /proc/self/mountinfo content
+fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+    crate::mountinfo::parse_mountinfo(content).iter().map(MountEntry::from).collect()
+}
+
+/// Mount tables in /proc/mounts format, tried in order
+const PROC_MOUNTS_SOURCES: &[&str] = &["/proc/mounts", "/proc/self/mounts"];
+
+/// Read and parse the mount table from the first readable source
+/// 
+/// Fails with [`LuksError::MountTableUnavailable`] when none can be read.
+fn read_mount_table(mounts_sources: &[&str], mountinfo: &str) -> Result<Vec<MountEntry>> {
+    for source in mounts_sources {
+        match std::fs::read_to_string(source) {
+            Ok(content) => return Ok(parse_proc_mounts(&content)),
+            Err(e) => debug!("{}: {}", source, e),
+        }
+    }
+    
+    match std::fs::read_to_string(mountinfo) {
+        Ok(content) => Ok(parse_mountinfo(&content)),
+        Err(e) => {
+            debug!("{}: {}", mountinfo, e);
+            Err(LuksError::MountTableUnavailable)
+        }
+    }
+}
+
+/// Read and parse all entries from /proc/mounts
+/// 
+/// Falls back to /proc/self/mounts and then /proc/self/mountinfo, for
+/// containers where /proc/mounts is missing or masked.
+fn read_mount_entries() -> Result<Vec<MountEntry>> {
+    read_mount_table(PROC_MOUNTS_SOURCES, crate::mountinfo::PROC_MOUNTINFO)
+}
+
+/// Find every mount entry for a mount point
+/// 
+/// A directory mounted more than once (an overmount) has one entry per
+/// mount, in /proc/mounts order: the last one is the mount that is visible
+/// and the one `umount` removes.
+/// 
+/// # Security
+/// - Uses canonical paths for reliable comparison
+pub fn mount_entries(path: &Path) -> Result<Vec<MountEntry>> {
+    let canonical_path = path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf());
+    
+    let found = read_mount_entries()?
+        .into_iter()
+        .filter(|entry| {
+            let canonical_mounted = entry.mount_point.canonicalize()
+                .unwrap_or_else(|_| entry.mount_point.clone());
+            canonical_mounted == canonical_path
+        })
+        .collect();
+    
+    Ok(found)
+}
+
+/// Find the mount entry for a mount point
+/// 
+/// If the directory is mounted more than once, the last (topmost) entry wins.
+pub fn find_mount_entry(path: &Path) -> Result<Option<MountEntry>> {
+    Ok(mount_entries(path)?.pop())
+}
+
+/// Find the mount entry whose source is the given device
+/// 
+/// Both the literal path and its canonical form are compared, so
+/// `/dev/mapper/<name>` matches entries listed as `/dev/dm-N` and vice versa.
+pub fn find_mount_by_device(device: &Path) -> Result<Option<MountEntry>> {
+    Ok(find_mounts_by_device(device)?.into_iter().next())
+}
+
+/// Find every mount entry whose source is the given device
+/// 
+/// Includes bind mounts, which list the same source device. Entries are in
+/// /proc/mounts order, i.e. the order they were mounted.
+pub fn find_mounts_by_device(device: &Path) -> Result<Vec<MountEntry>> {
+    let canonical_device = device.canonicalize()
+        .unwrap_or_else(|_| device.to_path_buf());
+    
+    let found = read_mount_entries()?
+        .into_iter()
+        .filter(|entry| {
+            let source = Path::new(&entry.device);
+            source == device || source.canonicalize().is_ok_and(|c| c == canonical_device)
+        })
+        .collect();
+    
+    Ok(found)
+}
+
 /// Check if a path is currently mounted
 /// 
 /// # Security
 /// - Uses canonical paths for reliable comparison
+/// - Without a readable mount table, falls back to comparing device numbers
 pub fn is_mounted(path: &Path) -> Result<bool> {
-    let mounts = std::fs::read_to_string("/proc/mounts")
-        .context(t!("mount.failed_read_proc_mounts").to_string())?;
+    match mount_entries(path) {
+        Ok(entries) => Ok(!entries.is_empty()),
+        Err(LuksError::MountTableUnavailable) => is_mount_point_by_stat(path),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check whether a directory is a mount point without reading /proc
+/// 
+/// A mount point lives on a different device than its parent directory,
+/// or is the parent itself (`/`). Bind mounts from the same filesystem
+/// keep the parent's device and are not detected.
+fn is_mount_point_by_stat(path: &Path) -> Result<bool> {
+    let Ok(canonical_path) = path.canonicalize() else {
+        return Ok(false);
+    };
+    let parent = canonical_path.parent().unwrap_or(&canonical_path);
     
-    let canonical_path = path.canonicalize()
-        .unwrap_or_else(|_| path.to_path_buf());
+    let own = nix::sys::stat::stat(&canonical_path)
+        .with_context(|| t!("mount.failed_stat_mount_point", path = canonical_path.display().to_string()).to_string())?;
+    let above = nix::sys::stat::stat(parent)
+        .with_context(|| t!("mount.failed_stat_mount_point", path = parent.display().to_string()).to_string())?;
     
-    for line in mounts.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let mounted_on = Path::new(parts[1]);
-            let canonical_mounted = mounted_on.canonicalize()
-                .unwrap_or_else(|_| mounted_on.to_path_buf());
-            
-            if canonical_mounted == canonical_path {
-                return Ok(true);
-            }
+    Ok(own.st_dev != above.st_dev || own.st_ino == above.st_ino)
+}
+
+/// Default permissions for a mount point created with `--mkdir`
+pub const DEFAULT_MOUNT_POINT_MODE: u32 = 0o755;
+
+/// setuid and setgid bits, which make no sense on a mount point
+const SETID_BITS: u32 = 0o6000;
+
+/// Parse an octal directory mode such as `750` or `0750`
+/// 
+/// # Security
+/// - setuid/setgid bits are rejected; only permission and sticky bits are allowed
+pub fn parse_dir_mode(mode: &str) -> Result<u32> {
+    let Ok(bits) = u32::from_str_radix(mode, 8) else {
+        bail!("{}", t!("mount.invalid_mode", mode = mode));
+    };
+    if bits > 0o7777 {
+        bail!("{}", t!("mount.invalid_mode", mode = mode));
+    }
+    if bits & SETID_BITS != 0 {
+        bail!("{}", t!("mount.mode_setid", mode = mode));
+    }
+    Ok(bits)
+}
+
+/// Resolve a user name or numeric uid through the passwd database
+pub fn resolve_user(user: &str) -> Result<Uid> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(Uid::from_raw(uid));
+    }
+    match User::from_name(user) {
+        Ok(Some(entry)) => Ok(entry.uid),
+        _ => bail!("{}", t!("mount.unknown_user", name = user)),
+    }
+}
+
+/// Resolve a group name or numeric gid through the group database
+pub fn resolve_group(group: &str) -> Result<Gid> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(Gid::from_raw(gid));
+    }
+    match Group::from_name(group) {
+        Ok(Some(entry)) => Ok(entry.gid),
+        _ => bail!("{}", t!("mount.unknown_group", name = group)),
+    }
+}
+
+/// Check whether a directory has any entries
+/// 
+/// Mounting over a non-empty directory hides its contents until the
+/// volume is unmounted, so callers use this to warn before doing so.
+pub fn is_dir_empty(path: &Path) -> Result<bool> {
+    let mut entries = std::fs::read_dir(path)
+        .context(t!("mount.failed_read_mount_point", path = path.display().to_string()).to_string())?;
+    Ok(entries.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+
+    #[test]
+    fn test_decode_proc_mounts_field() {
+        assert_eq!(decode_proc_mounts_field("/mnt/my\\040data"), "/mnt/my data");
+        assert_eq!(decode_proc_mounts_field("/mnt/a\\011b\\012c\\134d"), "/mnt/a\tb\nc\\d");
+        assert_eq!(decode_proc_mounts_field("/mnt/plain"), "/mnt/plain");
+        // Not valid escapes: kept verbatim
+        assert_eq!(decode_proc_mounts_field("/mnt/x\\04"), "/mnt/x\\04");
+        assert_eq!(decode_proc_mounts_field("/mnt/x\\999"), "/mnt/x\\999");
+    }
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let entries = parse_mountinfo(
+            "22 1 0:21 / /proc rw,nosuid shared:5 - proc proc rw\n\
+             95 29 253:1 / /mnt/my\\040data rw,nosuid,relatime shared:50 master:3 - ext4 /dev/mapper/luks-test ro,errors=remount-ro\n\
+             garbage line\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].device, "/dev/mapper/luks-test");
+        assert_eq!(entries[1].mount_point, PathBuf::from("/mnt/my data"));
+        assert_eq!(entries[1].fs_type, "ext4");
+        assert_eq!(entries[1].options, ["rw", "nosuid", "relatime", "ro", "errors=remount-ro"]);
+        assert!(entries[1].is_read_only());
+    }
+
+    #[test]
+    fn test_read_mount_table_falls_back() {
+        let dir = std::env::temp_dir().join(format!("luksctl-mount-table-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let missing = dir.join("mounts").display().to_string();
+        let mountinfo = dir.join("mountinfo");
+        let _ = std::fs::remove_file(&mountinfo);
+        
+        let result = read_mount_table(&[&missing], &mountinfo.display().to_string());
+        assert!(matches!(result, Err(LuksError::MountTableUnavailable)));
+        
+        std::fs::write(&mountinfo, "22 1 0:21 / /proc rw - proc proc rw\n").unwrap();
+        let entries = read_mount_table(&[&missing], &mountinfo.display().to_string()).unwrap();
+        assert_eq!(entries[0].mount_point, PathBuf::from("/proc"));
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_mount_point_by_stat() {
+        assert!(is_mount_point_by_stat(Path::new("/")).unwrap());
+        let dir = std::env::temp_dir().join(format!("luksctl-stat-mount-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(!is_mount_point_by_stat(&dir).unwrap());
+        assert!(!is_mount_point_by_stat(&dir.join("missing")).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_proc_mounts_with_escaped_mount_point() {
+        let entries = parse_proc_mounts(
+            "proc /proc proc rw,nosuid 0 0\n\
+             /dev/mapper/luks-test /mnt/my\\040data ext4 rw,nosuid,nodev 0 0\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].device, "/dev/mapper/luks-test");
+        assert_eq!(entries[1].mount_point, PathBuf::from("/mnt/my data"));
+        assert_eq!(entries[1].fs_type, "ext4");
+        assert_eq!(entries[1].options, ["rw", "nosuid", "nodev"]);
+    }
+
+    #[test]
+    fn test_hardening_defaults_and_exec_precedence() {
+        let options = MountOptions::default();
+        assert_eq!(options.hardening_options(), vec!["nosuid", "nodev", "noexec"]);
+
+        let options = MountOptions {
+            options: Some("noatime,exec".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(options.hardening_options(), vec!["nosuid", "nodev"]);
+
+        let options = MountOptions {
+            hardening: HardeningProfile::permissive(),
+            ..Default::default()
+        };
+        assert!(options.hardening_options().is_empty());
+
+        let options = MountOptions {
+            read_only: true,
+            hardening: HardeningProfile {
+                nodev: false,
+                custom: vec!["nosymfollow".to_string()],
+                ..HardeningProfile::secure()
+            },
+            ..Default::default()
+        };
+        assert_eq!(options.option_string().unwrap(), "nosuid,noexec,nosymfollow,ro");
+
+        let options = MountOptions {
+            hardening: HardeningProfile {
+                custom: vec!["noatime;reboot".to_string()],
+                ..HardeningProfile::permissive()
+            },
+            ..Default::default()
+        };
+        assert!(options.option_string().is_err());
+    }
+
+    #[test]
+    fn test_option_conflicts_and_duplicates() {
+        let with = |read_only: bool, extra: &str| MountOptions {
+            read_only,
+            options: Some(extra.to_string()),
+            ..Default::default()
+        }.option_string();
+
+        let err = with(true, "rw").unwrap_err().to_string();
+        assert!(err.contains("ro") && err.contains("rw"), "{}", err);
+        assert!(with(false, "ro,rw").is_err());
+        assert!(with(false, "sync,async").is_err());
+        assert!(with(false, "noexec,exec").is_err());
+
+        assert_eq!(with(true, "ro,noexec,noatime,noatime").unwrap(), "nosuid,nodev,noexec,ro,noatime");
+        assert_eq!(with(false, "exec,sync").unwrap(), "nosuid,nodev,exec,sync");
+    }
+
+    #[test]
+    fn test_mount_flags_merge_with_options() {
+        let with = |flags: MountFlags, extra: Option<&str>| MountOptions {
+            flags,
+            options: extra.map(str::to_string),
+            ..Default::default()
+        }.option_string();
+        let noatime = MountFlags { noatime: true, nofail: true, ..Default::default() };
+
+        assert_eq!(with(noatime.clone(), None).unwrap(), "nosuid,nodev,noexec,noatime,nofail");
+        assert_eq!(with(noatime.clone(), Some("noatime,nodiratime")).unwrap(),
+            "nosuid,nodev,noexec,noatime,nofail,nodiratime");
+        assert!(with(noatime.clone(), Some("relatime")).is_err());
+        assert!(with(MountFlags { noatime: true, relatime: true, ..Default::default() }, None).is_err());
+        assert!(with(MountFlags { sync: true, ..Default::default() }, Some("async")).is_err());
+        assert!(MountFlags::default().is_empty() && !noatime.is_empty());
+    }
+
+    #[test]
+    fn test_selinux_context_options() {
+        let with = |extra: &str| MountOptions {
+            options: Some(extra.to_string()),
+            ..Default::default()
+        }.option_string();
+
+        assert_eq!(
+            with(r#"noatime,context="system_u:object_r:tmp_t:s0:c127,c456""#).unwrap(),
+            r#"nosuid,nodev,noexec,noatime,context="system_u:object_r:tmp_t:s0:c127,c456""#
+        );
+        assert!(with("fscontext=system_u:object_r:fs_t:s0").is_ok());
+        assert!(with(r#"defcontext="system_u:object_r:default_t:s0""#).is_ok());
+
+        for malicious in [
+            r#"context="system_u:object_r:tmp_t:s0;rm -rf /""#,
+            r#"context="a:b:c"$(id)""#,
+            r#"context="a:b:c\"#,
+            r#"context="a:b:c"#,
+            "context=a:b:`id`",
+            "context=",
+            r#"noatime,"context=a:b:c""#,
+        ] {
+            assert!(with(malicious).is_err(), "{malicious:?} should be rejected");
         }
     }
-    
-    Ok(false)
+
+    #[test]
+    fn test_mount_device_always_hardens() {
+        let mount_point = std::env::temp_dir();
+        let options = MountOptions {
+            read_only: true,
+            fs_type: Some("ext4".to_string()),
+            options: Some("noatime".to_string()),
+            ..Default::default()
+        };
+        let (result, commands) = MockRunner::default()
+            .capture(|| mount_device(Path::new("/dev/null"), &mount_point, &options));
+
+        result.unwrap();
+        assert_eq!(commands[0].program, "mount");
+        assert_eq!(
+            commands[0].args,
+            [
+                "-t", "ext4",
+                "-o", "nosuid,nodev,noexec,ro,noatime",
+                "/dev/null", &*mount_point.to_string_lossy(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mount_device_in_namespace() {
+        let mount_point = std::env::temp_dir();
+        let pid = std::process::id();
+        let options = MountOptions {
+            fs_type: Some("ext4".to_string()),
+            namespace: Some(pid),
+            ..Default::default()
+        };
+        let (result, commands) = MockRunner::default()
+            .capture(|| mount_device(Path::new("/dev/null"), &mount_point, &options));
+
+        result.unwrap();
+        assert_eq!(commands[0].program, "nsenter");
+        assert_eq!(commands[0].args[0], format!("--mount=/proc/{}/ns/mnt", pid));
+        assert_eq!(commands[0].args[1], "--");
+        assert!(commands[0].args[2].ends_with("/mount"));
+        assert_eq!(commands[0].args.last().unwrap(), &*mount_point.to_string_lossy());
+
+        assert!(mount_namespace_path(0).is_err());
+        assert!(mount_namespace_path(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_unmount_arguments() {
+        let (result, commands) = MockRunner::default()
+            .capture(|| unmount_lazy(Path::new("/mnt/data")));
+        result.unwrap();
+        assert_eq!(commands[0].program, "umount");
+        assert_eq!(commands[0].args, ["-l", "/mnt/data"]);
+
+        let (result, commands) = MockRunner::default()
+            .capture(|| unmount(Path::new("/mnt/../etc")));
+        assert!(result.is_err());
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_detect_fs_type() {
+        let runner = MockRunner { stdout: "ntfs3\n".to_string(), ..Default::default() };
+        let (result, commands) = runner.capture(|| detect_fs_type(Path::new("/dev/null")));
+        assert_eq!(result.unwrap().as_deref(), Some("ntfs3"));
+        assert_eq!(commands[0].args, ["-o", "value", "-s", "TYPE", "/dev/null"]);
+
+        let runner = MockRunner { stdout: "zfs_member\n".to_string(), ..Default::default() };
+        let (result, _) = runner.capture(|| detect_fs_type(Path::new("/dev/null")));
+        assert_eq!(result.unwrap(), None);
+
+        let runner = MockRunner { exit_code: 2, ..Default::default() };
+        let (result, _) = runner.capture(|| detect_fs_type(Path::new("/dev/null")));
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn test_unmount_with_retry_gives_up() {
+        let runner = MockRunner { exit_code: 32, stderr: "target is busy".to_string(), ..Default::default() };
+        let (result, commands) = runner
+            .capture(|| unmount_with_retry(Path::new("/mnt/data"), 2, Duration::ZERO));
+        assert!(result.is_err());
+        assert_eq!(commands.len(), 3);
+
+        let (result, commands) = MockRunner::default()
+            .capture(|| unmount_with_retry(Path::new("/mnt/data"), 2, Duration::ZERO));
+        result.unwrap();
+        assert_eq!(commands.len(), 1);
+    }
+
+    #[test]
+    fn test_find_mount_blockers_sees_open_files() {
+        let dir = std::env::temp_dir().join(format!("luksctl-blockers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = std::fs::File::create(dir.join("held")).unwrap();
+
+        let blockers = find_mount_blockers(&dir).unwrap();
+        assert!(blockers.iter().any(|(pid, _)| *pid == std::process::id()));
+
+        drop(file);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_dir_mode_and_owners() {
+        assert_eq!(parse_dir_mode("750").unwrap(), 0o750);
+        assert_eq!(parse_dir_mode("1777").unwrap(), 0o1777);
+        assert!(parse_dir_mode("4755").is_err());
+        assert!(parse_dir_mode("2755").is_err());
+        assert!(parse_dir_mode("rwx").is_err());
+        assert!(parse_dir_mode("17777").is_err());
+
+        assert_eq!(resolve_user("root").unwrap(), Uid::from_raw(0));
+        assert_eq!(resolve_user("1234").unwrap(), Uid::from_raw(1234));
+        assert_eq!(resolve_group("0").unwrap(), Gid::from_raw(0));
+        assert!(resolve_user("no-such-user-luksctl").is_err());
+    }
+
+    #[test]
+    fn test_is_dir_empty() {
+        let dir = std::env::temp_dir().join(format!("luksctl-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(is_dir_empty(&dir).unwrap());
+        std::fs::write(dir.join(".hidden"), b"").unwrap();
+        assert!(!is_dir_empty(&dir).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mount_point_checked_under_namespace_root() {
+        let root = std::env::temp_dir().join(format!("luksctl-nsroot-{}", std::process::id()));
+        let mount_point = Path::new("/luksctl-only-in-namespace/data");
+        std::fs::create_dir_all(rooted(&root, mount_point)).unwrap();
+
+        // Absent on the host, present under the namespace root
+        assert!(!mount_point.exists());
+        assert!(validate_mount_point_under(&root, mount_point).is_ok());
+        assert!(validate_mount_point(mount_point).is_err());
+        assert!(validate_mount_point_under(&root, Path::new("/luksctl-missing")).is_err());
+        assert!(validate_mount_point_under(&root, Path::new("/luksctl-only-in-namespace/../etc")).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_mount_point_dots_only_rejected_as_component() {
+        let dir = std::env::temp_dir().join(format!("luksctl-a..b-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(validate_mount_point(&dir).is_ok());
+        assert!(validate_mount_point(Path::new("/mnt/../etc")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_bind_spec() {
+        assert_eq!(
+            parse_bind_spec("app/data:/var/lib/app").unwrap(),
+            (PathBuf::from("app/data"), PathBuf::from("/var/lib/app"))
+        );
+        assert!(parse_bind_spec("app/data").is_err());
+        assert!(parse_bind_spec("/etc:/mnt/etc").is_err());
+        assert!(parse_bind_spec("../outside:/mnt/x").is_err());
+        assert!(parse_bind_spec("app/../../x:/mnt/x").is_err());
+        assert!(parse_bind_spec("app:relative/target").is_err());
+        assert!(parse_bind_spec("app:/var/../etc").is_err());
+    }
+
+    #[test]
+    fn test_bind_source_cannot_escape_through_symlink() {
+        let root = std::env::temp_dir().join(format!("luksctl-bind-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("inside")).unwrap();
+        std::os::unix::fs::symlink("/etc", root.join("escape")).unwrap();
+
+        assert!(resolve_bind_source(&root, Path::new("inside")).is_ok());
+        assert!(resolve_bind_source(&root, Path::new("escape")).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_kernel_filesystem_lists() {
+        let filesystems = "nodev\tsysfs\nnodev\ttmpfs\n\text3\n\text4\n\tvfat\nnodev\tfuse\n";
+        assert_eq!(parse_proc_filesystems(filesystems), ["ext3", "ext4", "vfat"]);
+
+        let aliases = "alias fs-xfs xfs\nalias fs-ext3 ext4\nalias char-major-10-229 fuse\n";
+        assert!(has_fs_module_alias(aliases, "xfs"));
+        assert!(has_fs_module_alias(aliases, "ext3"));
+        assert!(!has_fs_module_alias(aliases, "btrfs"));
+        assert!(!has_fs_module_alias(aliases, "fuse"));
+    }
 }
@@ -0,0 +1,235 @@
+//! Mount tree from /proc/self/mountinfo
+//!
+//! /proc/mounts is a flat list: it can't say which mount sits inside
+//! which, or whether an entry is a bind mount of a subdirectory. This
+//! module parses mountinfo, which carries mount IDs, the parent of every
+//! mount and the root of the filesystem each one exposes, so teardown can
+//! unmount nested mounts before the mount they live in.
+
+use log::debug;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::{LuksError, Result};
+use crate::mount::{decode_proc_mounts_field, MountEntry};
+
+/// Location of the calling process's mount tree
+pub const PROC_MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// A single line of /proc/self/mountinfo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfoEntry {
+    /// Unique ID of the mount
+    pub mount_id: u32,
+    /// ID of the mount this one is mounted on
+    pub parent_id: u32,
+    /// `major:minor` of the filesystem's device
+    pub dev: (u32, u32),
+    /// Directory of the filesystem exposed at the mount point (`/` unless
+    /// this is a bind mount of a subdirectory)
+    pub root: PathBuf,
+    pub mount_point: PathBuf,
+    /// Per-mount options such as `rw,nosuid`
+    pub mount_options: Vec<String>,
+    /// Propagation tags such as `shared:5` or `master:3`
+    pub optional_fields: Vec<String>,
+    pub fs_type: String,
+    pub source: String,
+    /// Superblock options shared by every mount of the filesystem
+    pub super_options: Vec<String>,
+}
+
+impl MountInfoEntry {
+    /// Whether this mount exposes a subdirectory of its filesystem
+    ///
+    /// Bind mounts of a filesystem's root look like any other mount and
+    /// can only be told apart by comparing [`dev`](Self::dev) with the
+    /// other mounts of the same device.
+    pub fn is_subdir_bind(&self) -> bool {
+        self.root != Path::new("/")
+    }
+}
+
+impl From<&MountInfoEntry> for MountEntry {
+    /// Per-mount options come first, followed by the superblock options
+    /// not already listed, so `ro` is seen whichever level sets it
+    fn from(entry: &MountInfoEntry) -> Self {
+        let mut options = entry.mount_options.clone();
+        for opt in &entry.super_options {
+            if !options.contains(opt) {
+                options.push(opt.clone());
+            }
+        }
+        MountEntry {
+            device: entry.source.clone(),
+            mount_point: entry.mount_point.clone(),
+            fs_type: entry.fs_type.clone(),
+            options,
+        }
+    }
+}
+
+/// Parse a single mountinfo line
+///
+/// The format is `id parent major:minor root mount_point options
+/// [optional...] - fs_type source super_options`; the optional fields end
+/// at the lone `-` separator.
+fn parse_line(line: &str) -> Option<MountInfoEntry> {
+    let (mount_fields, fs_fields) = line.split_once(" - ")?;
+
+    let mount_fields: Vec<&str> = mount_fields.split_whitespace().collect();
+    let [mount_id, parent_id, dev, root, mount_point, mount_options, ref optional @ ..] = mount_fields[..] else {
+        return None;
+    };
+    let (major, minor) = dev.split_once(':')?;
+
+    let fs_fields: Vec<&str> = fs_fields.split_whitespace().collect();
+    let [fs_type, source, super_options, ..] = fs_fields[..] else {
+        return None;
+    };
+
+    let split_options = |options: &str| options.split(',').map(str::to_string).collect();
+    Some(MountInfoEntry {
+        mount_id: mount_id.parse().ok()?,
+        parent_id: parent_id.parse().ok()?,
+        dev: (major.parse().ok()?, minor.parse().ok()?),
+        root: PathBuf::from(decode_proc_mounts_field(root)),
+        mount_point: PathBuf::from(decode_proc_mounts_field(mount_point)),
+        mount_options: split_options(mount_options),
+        optional_fields: optional.iter().map(|field| field.to_string()).collect(),
+        fs_type: fs_type.to_string(),
+        source: decode_proc_mounts_field(source),
+        super_options: split_options(super_options),
+    })
+}
+
+/// Parse /proc/self/mountinfo content, skipping malformed lines
+pub fn parse_mountinfo(content: &str) -> Vec<MountInfoEntry> {
+    content.lines().filter_map(parse_line).collect()
+}
+
+/// The mount tree of the calling process
+#[derive(Debug, Clone, Default)]
+pub struct MountTree {
+    /// Entries in mountinfo order, i.e. parents before their children
+    pub entries: Vec<MountInfoEntry>,
+}
+
+impl MountTree {
+    /// Build a tree from mountinfo content
+    pub fn parse(content: &str) -> Self {
+        MountTree { entries: parse_mountinfo(content) }
+    }
+
+    /// Read the tree of the calling process
+    ///
+    /// Fails with [`LuksError::MountTableUnavailable`] when /proc is not
+    /// mounted.
+    pub fn read() -> Result<Self> {
+        match std::fs::read_to_string(PROC_MOUNTINFO) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) => {
+                debug!("{}: {}", PROC_MOUNTINFO, e);
+                Err(LuksError::MountTableUnavailable)
+            }
+        }
+    }
+
+    /// Look up a mount by ID
+    pub fn get(&self, mount_id: u32) -> Option<&MountInfoEntry> {
+        self.entries.iter().find(|entry| entry.mount_id == mount_id)
+    }
+
+    /// The visible (topmost) mount on a directory
+    ///
+    /// `mount_point` is compared literally; pass a canonical path.
+    pub fn find_by_mount_point(&self, mount_point: &Path) -> Option<&MountInfoEntry> {
+        self.entries.iter().rev().find(|entry| entry.mount_point == mount_point)
+    }
+
+    /// Mounts directly on top of `mount_id`
+    pub fn children(&self, mount_id: u32) -> impl Iterator<Item = &MountInfoEntry> {
+        self.entries.iter()
+            .filter(move |entry| entry.parent_id == mount_id && entry.mount_id != mount_id)
+    }
+
+    /// Every mount nested under `mount_id`, in unmount order
+    ///
+    /// Children come before their parents and later mounts before earlier
+    /// ones, so unmounting in this order never hits a mount that still has
+    /// something mounted inside it. `mount_id` itself is not included.
+    pub fn descendants(&self, mount_id: u32) -> Vec<&MountInfoEntry> {
+        let mut ordered = Vec::new();
+        let mut visited = HashSet::from([mount_id]);
+        self.collect_descendants(mount_id, &mut visited, &mut ordered);
+        ordered
+    }
+
+    fn collect_descendants<'a>(&'a self, mount_id: u32, visited: &mut HashSet<u32>, ordered: &mut Vec<&'a MountInfoEntry>) {
+        let children: Vec<_> = self.children(mount_id).collect();
+        for child in children.into_iter().rev() {
+            // A malformed table could loop; never visit a mount twice
+            if visited.insert(child.mount_id) {
+                self.collect_descendants(child.mount_id, visited, ordered);
+                ordered.push(child);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+95 22 253:1 / /mnt/vault rw,nosuid,nodev shared:50 - ext4 /dev/mapper/luks-test rw
+96 95 0:40 / /mnt/vault/tmp rw - tmpfs tmpfs rw
+97 96 0:41 / /mnt/vault/tmp/deep rw - tmpfs tmpfs rw
+98 22 253:1 /photos /srv/my\\040photos rw,nosuid,nodev shared:50 master:3 - ext4 /dev/mapper/luks-test rw
+99 95 253:1 /music /mnt/vault/music ro - ext4 /dev/mapper/luks-test rw
+not a mountinfo line
+";
+
+    #[test]
+    fn test_parse_mountinfo_fields() {
+        let tree = MountTree::parse(SAMPLE);
+        assert_eq!(tree.entries.len(), 6);
+
+        let bind = tree.get(98).unwrap();
+        assert_eq!(bind.parent_id, 22);
+        assert_eq!(bind.dev, (253, 1));
+        assert_eq!(bind.root, PathBuf::from("/photos"));
+        assert_eq!(bind.mount_point, PathBuf::from("/srv/my photos"));
+        assert_eq!(bind.optional_fields, ["shared:50", "master:3"]);
+        assert_eq!(bind.source, "/dev/mapper/luks-test");
+        assert!(bind.is_subdir_bind());
+        assert!(!tree.get(95).unwrap().is_subdir_bind());
+
+        let entry = MountEntry::from(tree.get(99).unwrap());
+        assert_eq!(entry.options, ["ro", "rw"]);
+        assert!(entry.is_read_only());
+    }
+
+    #[test]
+    fn test_descendants_unmount_order() {
+        let tree = MountTree::parse(SAMPLE);
+        let volume = tree.find_by_mount_point(Path::new("/mnt/vault")).unwrap();
+
+        let order: Vec<u32> = tree.descendants(volume.mount_id).iter().map(|e| e.mount_id).collect();
+        assert_eq!(order, [99, 97, 96]);
+
+        // The bind outside the volume hangs off / rather than the volume
+        assert!(tree.descendants(98).is_empty());
+    }
+
+    #[test]
+    fn test_descendants_survive_cycles() {
+        let tree = MountTree::parse("\
+1 2 0:1 / /a rw - tmpfs tmpfs rw
+2 1 0:2 / /a/b rw - tmpfs tmpfs rw
+");
+        let order: Vec<u32> = tree.descendants(1).iter().map(|e| e.mount_id).collect();
+        assert_eq!(order, [2]);
+    }
+}
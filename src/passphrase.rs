@@ -0,0 +1,143 @@
+//! Non-interactive passphrase sources
+//!
+//! This module reads passphrases from stdin or the environment for scripted
+//! use, with security hardening:
+//! - Raw bytes are kept in a zeroizing buffer that never reallocates
+//! - The result is handed out as a SecretString only
+//! - Input is bounded so a runaway pipe can't exhaust memory
+//!
+//! Passphrases from other sources (such as the terminal prompt) go through
+//! [`into_secret`], which is what guarantees that the `String` they arrive
+//! in is cleared.
+
+use anyhow::{bail, Context, Result};
+use rust_i18n::t;
+use secrecy::SecretString;
+use std::io::BufRead;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Longest passphrase accepted from stdin, in bytes (cryptsetup's limit is 512 characters)
+const MAX_PASSPHRASE_LEN: usize = 4096;
+
+/// Wrap a passphrase in a `SecretString`, zeroizing the `String` it came in
+///
+/// `SecretString::from(String)` converts through `into_boxed_str`, which
+/// reallocates whenever the string has spare capacity (as one returned by
+/// `rpassword` always does, having had its newline stripped) and frees the
+/// old buffer without clearing it. Instead, the passphrase is copied into an
+/// exact-size allocation that the `SecretString` takes over as is, and the
+/// original buffer is zeroized, spare capacity included, before it drops.
+///
+/// Copies made before the `String` reaches us (e.g. while `rpassword` grows
+/// its buffer) are outside our control.
+pub fn into_secret(mut raw: String) -> SecretString {
+    let secret = SecretString::from(Box::<str>::from(raw.as_str()));
+    raw.zeroize();
+    secret
+}
+
+/// Read a passphrase from `reader`, up to the first newline
+///
+/// A trailing `\n` (or `\r\n`) is stripped, so both `echo` and
+/// `echo -n` work. The buffer is allocated once with room for the whole
+/// limit, so no partial copies are left behind by reallocation.
+pub fn read_passphrase(reader: impl BufRead) -> Result<SecretString> {
+    let mut buf = Zeroizing::new(Vec::with_capacity(MAX_PASSPHRASE_LEN + 1));
+    reader.take(MAX_PASSPHRASE_LEN as u64 + 1)
+        .read_until(b'\n', &mut buf)
+        .context(t!("passphrase.failed_read_stdin").to_string())?;
+
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    } else if buf.len() > MAX_PASSPHRASE_LEN {
+        bail!("{}", t!("passphrase.too_long", max = MAX_PASSPHRASE_LEN));
+    }
+
+    if buf.is_empty() {
+        bail!("{}", t!("passphrase.empty"));
+    }
+
+    // Copy into an exact-size allocation so SecretString never reallocates
+    // it; the original bytes are zeroized along with the buffer
+    match String::from_utf8(buf.to_vec()) {
+        Ok(passphrase) => Ok(SecretString::from(passphrase)),
+        Err(e) => {
+            drop(Zeroizing::new(e.into_bytes()));
+            bail!("{}", t!("passphrase.not_utf8"))
+        }
+    }
+}
+
+/// Read a passphrase from standard input (`--password-stdin`)
+pub fn read_passphrase_stdin() -> Result<SecretString> {
+    read_passphrase(std::io::stdin().lock())
+}
+
+/// Read a passphrase from the environment variable `name` (`--password-env`)
+///
+/// The variable must be set and non-empty. It is removed from this
+/// process's environment afterwards so child processes don't inherit it;
+/// the copy the kernel keeps in `/proc/<pid>/environ` can't be wiped, which
+/// is why `--password-stdin` is preferred.
+pub fn read_passphrase_env(name: &str) -> Result<SecretString> {
+    let Some(value) = std::env::var_os(name) else {
+        bail!("{}", t!("passphrase.env_not_set", name = name));
+    };
+    std::env::remove_var(name);
+
+    let value = match value.into_string() {
+        Ok(value) => value,
+        Err(value) => {
+            drop(Zeroizing::new(value.into_encoded_bytes()));
+            bail!("{}", t!("passphrase.not_utf8"));
+        }
+    };
+    if value.is_empty() {
+        bail!("{}", t!("passphrase.env_empty", name = name));
+    }
+
+    Ok(into_secret(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_passphrase_strips_newline() {
+        let read = |input: &[u8]| read_passphrase(Cursor::new(input.to_vec()));
+
+        assert_eq!(read(b"hunter2").unwrap().expose_secret(), "hunter2");
+        assert_eq!(read(b"hunter2\n").unwrap().expose_secret(), "hunter2");
+        assert_eq!(read(b"hunter2\r\nignored\n").unwrap().expose_secret(), "hunter2");
+        assert!(read(b"\n").is_err());
+        assert!(read(&[b'x'; MAX_PASSPHRASE_LEN + 1]).is_err());
+        assert!(read(b"\xff\xfe").is_err());
+    }
+
+    #[test]
+    fn test_into_secret_keeps_passphrase() {
+        let mut raw = String::with_capacity(64);
+        raw.push_str("hunter2");
+        assert_eq!(into_secret(raw).expose_secret(), "hunter2");
+        assert_eq!(into_secret(String::new()).expose_secret(), "");
+    }
+
+    #[test]
+    fn test_read_passphrase_env_unsets_variable() {
+        let name = format!("LUKSCTL_TEST_PASSPHRASE_{}", std::process::id());
+        std::env::set_var(&name, "hunter2");
+
+        assert_eq!(read_passphrase_env(&name).unwrap().expose_secret(), "hunter2");
+        assert!(std::env::var_os(&name).is_none());
+        assert!(read_passphrase_env(&name).is_err());
+
+        std::env::set_var(&name, "");
+        assert!(read_passphrase_env(&name).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Path safety checks shared by the library and the binaries
+//!
+//! Every path that reaches an external command or the state directory goes
+//! through these checks, so they behave the same everywhere:
+//! - Paths must be absolute, so the working directory never matters
+//! - Null bytes are rejected before they can truncate a C string
+//! - Traversal means a `..` component; names such as `a..b` are fine
+//! - Device paths must live under `/dev`
+
+use rust_i18n::t;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path};
+
+use crate::error::{bail, Result};
+
+/// Directory under which every device node must live
+const DEV_DIR: &str = "/dev";
+
+/// Require an absolute path
+pub fn ensure_absolute(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        bail!("{}", t!("pathsafe.not_absolute", path = path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Reject a path containing a null byte
+pub fn ensure_no_null(path: &Path) -> Result<()> {
+    if path.as_os_str().as_bytes().contains(&0) {
+        bail!("{}", t!("pathsafe.contains_null"));
+    }
+    Ok(())
+}
+
+/// Reject a path with a `..` component
+///
+/// Only a whole `..` component walks up the tree; a name that merely
+/// contains two dots, like `a..b` or `..foo`, is an ordinary file name.
+pub fn ensure_no_traversal(path: &Path) -> Result<()> {
+    if path.components().any(|component| component == Component::ParentDir) {
+        bail!("{}", t!("pathsafe.traversal", path = path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Require a path strictly below `/dev`
+///
+/// The comparison is by component, so `/devices/x` and `/dev` itself are
+/// rejected. Symlinks are not resolved; callers that follow them must check
+/// the target too.
+pub fn ensure_under_dev(path: &Path) -> Result<()> {
+    if !path.starts_with(DEV_DIR) || path == Path::new(DEV_DIR) {
+        bail!("{}", t!("pathsafe.not_under_dev", path = path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// Require an absolute path free of null bytes and `..` components
+///
+/// The combination almost every caller wants.
+pub fn ensure_safe_absolute(path: &Path) -> Result<()> {
+    ensure_absolute(path)?;
+    ensure_no_null(path)?;
+    ensure_no_traversal(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_ensure_absolute() {
+        assert!(ensure_absolute(Path::new("/mnt/data")).is_ok());
+        assert!(ensure_absolute(Path::new("/")).is_ok());
+        assert!(ensure_absolute(Path::new("mnt/data")).is_err());
+        assert!(ensure_absolute(Path::new("./data")).is_err());
+        assert!(ensure_absolute(Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_ensure_no_null() {
+        assert!(ensure_no_null(Path::new("/mnt/data")).is_ok());
+        assert!(ensure_no_null(Path::new(OsStr::from_bytes(b"/mnt/da\0ta"))).is_err());
+        assert!(ensure_no_null(Path::new(OsStr::from_bytes(b"\0"))).is_err());
+    }
+
+    #[test]
+    fn test_ensure_no_traversal() {
+        for ok in ["/mnt/a..b", "/mnt/..foo", "/mnt/foo..", "/mnt/...", "/mnt/./data", "relative/x"] {
+            assert!(ensure_no_traversal(Path::new(ok)).is_ok(), "{ok}");
+        }
+        for bad in ["/mnt/../etc", "..", "../x", "/mnt/data/..", "a/b/../../c"] {
+            assert!(ensure_no_traversal(Path::new(bad)).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_ensure_under_dev() {
+        for ok in ["/dev/sda1", "/dev/mapper/luks-x", "/dev/disk/by-uuid/1234"] {
+            assert!(ensure_under_dev(Path::new(ok)).is_ok(), "{ok}");
+        }
+        for bad in ["/dev", "/dev/", "/devices/sda1", "/tmp/dev/sda1", "dev/sda1", "/mnt/sda1"] {
+            assert!(ensure_under_dev(Path::new(bad)).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_ensure_safe_absolute() {
+        assert!(ensure_safe_absolute(Path::new("/mnt/a..b")).is_ok());
+        assert!(ensure_safe_absolute(Path::new("mnt/data")).is_err());
+        assert!(ensure_safe_absolute(Path::new("/mnt/../etc")).is_err());
+        assert!(ensure_safe_absolute(Path::new(OsStr::from_bytes(b"/mnt/\0"))).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Progress indicator for slow external commands
+//!
+//! Unlocking a LUKS2 volume runs the PBKDF, which is deliberately slow and
+//! can take several seconds. While luksctl waits for such a command, a
+//! spinner with the elapsed time is drawn on stderr so the tool doesn't
+//! look hung:
+//! - Only when stderr is a terminal and `-q` was not given
+//! - Only once the command has run for a moment, so fast commands stay quiet
+//! - Started after any passphrase has been read and written to the child,
+//!   so it never draws over a prompt
+//! - The line is erased when the command finishes
+//! - Like log messages, the label passes through [`redact`]
+
+use log::LevelFilter;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::redact::redact;
+
+/// Spinner frames, drawn in turn
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+/// How long a command runs before the spinner appears
+const SHOW_AFTER: Duration = Duration::from_millis(300);
+/// Delay between frames
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// ANSI sequence returning to column 0 and clearing the line
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// A spinner drawn on stderr until dropped
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<bool>>,
+}
+
+impl Spinner {
+    /// Start a spinner labelled `message`
+    ///
+    /// Returns `None` when stderr is not a terminal or output is limited to
+    /// errors, in which case nothing is drawn.
+    pub fn start(message: &str) -> Option<Self> {
+        if log::max_level() < LevelFilter::Info || !std::io::stderr().is_terminal() {
+            return None;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let stop = Arc::clone(&stop);
+            let message = redact(message);
+            move || draw(&message, &stop)
+        });
+
+        Some(Self { stop, handle: Some(handle) })
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            if handle.join().unwrap_or(false) {
+                let mut stderr = std::io::stderr().lock();
+                let _ = write!(stderr, "{}", CLEAR_LINE);
+                let _ = stderr.flush();
+            }
+        }
+    }
+}
+
+/// Draw frames until `stop` is set; returns whether anything was drawn
+fn draw(message: &str, stop: &AtomicBool) -> bool {
+    let started = Instant::now();
+    thread::park_timeout(SHOW_AFTER);
+
+    let mut drawn = false;
+    for frame in FRAMES.iter().cycle() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "{}{} {} ({})", CLEAR_LINE, frame, message, format_elapsed(started.elapsed()));
+        let _ = stderr.flush();
+        drop(stderr);
+        drawn = true;
+        thread::park_timeout(FRAME_INTERVAL);
+    }
+    drawn
+}
+
+/// Render an elapsed time as seconds with one decimal
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1}s", elapsed.as_secs_f32())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_millis(0)), "0.0s");
+        assert_eq!(format_elapsed(Duration::from_millis(2345)), "2.3s");
+    }
+}
@@ -0,0 +1,139 @@
+//! Redaction of device paths and mount points in user-facing output
+//!
+//! With `--redact` or `LUKSCTL_REDACT=1`, every device path and mount point
+//! registered here is replaced in printed messages and errors by a short
+//! token such as `dev#a1b2c3`. Values are registered as they are resolved,
+//! and the console logger and error printer apply [`redact`] to everything
+//! they print, so localized messages need no changes.
+//!
+//! Debug-level messages are never redacted, and registering a value logs
+//! its token at debug level, so `-v` still shows the real paths for local
+//! troubleshooting.
+//!
+//! Tokens are keyed by the machine id (a random key if it can't be read):
+//! stable on one host, so log lines about the same device can be matched,
+//! but not reversible by guessing common paths without that key.
+
+use log::debug;
+use rust_i18n::t;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Environment variable enabling redaction
+const REDACT_ENV: &str = "LUKSCTL_REDACT";
+/// File holding the key tokens are derived from
+const MACHINE_ID_FILE: &str = "/etc/machine-id";
+/// Number of hex digits in a token
+const TOKEN_HASH_LEN: usize = 6;
+
+/// Whether `--redact` was given
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+/// Registered values and their tokens
+static VALUES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Enable or disable redaction for the whole process
+pub fn set_redact(enabled: bool) {
+    REDACT.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether redaction is enabled, by `--redact` or `LUKSCTL_REDACT=1`
+pub fn is_redacting() -> bool {
+    REDACT.load(Ordering::SeqCst) || std::env::var_os(REDACT_ENV).is_some_and(|value| value == "1")
+}
+
+/// Key tokens are derived from
+fn token_key() -> &'static [u8] {
+    static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        std::fs::read(MACHINE_ID_FILE)
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().as_bytes().to_vec())
+    })
+}
+
+/// Token standing in for `value`, e.g. `dev#a1b2c3`
+fn token(kind: &str, key: &[u8], value: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(key)
+        .chain_update([0])
+        .chain_update(value.as_bytes())
+        .finalize();
+    let hash: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}#{}", kind, &hash[..TOKEN_HASH_LEN])
+}
+
+/// Record a value to redact under a token of the given kind
+fn register(kind: &str, value: &Path) {
+    let value = value.display().to_string();
+    if value.is_empty() {
+        return;
+    }
+
+    let mut values = VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    if values.iter().any(|(known, _)| *known == value) {
+        return;
+    }
+    let token = token(kind, token_key(), &value);
+    if is_redacting() {
+        debug!("{}", t!("redact.registered", token = &token, value = &value));
+    }
+    values.push((value, token));
+    // Longest first, so a path is replaced before any prefix of it
+    values.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+}
+
+/// Record a device path (or `UUID=`/`LABEL=` specification) to redact
+pub fn register_device(device: impl AsRef<Path>) {
+    register("dev", device.as_ref());
+}
+
+/// Record a mount point to redact
+pub fn register_mount_point(mount_point: impl AsRef<Path>) {
+    register("mnt", mount_point.as_ref());
+}
+
+/// Replace every registered value in `message` with its token
+///
+/// Returns `message` unchanged when redaction is disabled.
+pub fn redact(message: &str) -> String {
+    if !is_redacting() {
+        return message.to_string();
+    }
+
+    let values = VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    values.iter().fold(message.to_string(), |message, (value, token)| message.replace(value, token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_is_keyed_and_stable() {
+        let a = token("dev", b"key-a", "/dev/sda1");
+        assert_eq!(a, token("dev", b"key-a", "/dev/sda1"));
+        assert!(a.starts_with("dev#"));
+        assert_eq!(a.len(), "dev#".len() + TOKEN_HASH_LEN);
+        assert_ne!(a, token("dev", b"key-b", "/dev/sda1"));
+        assert_ne!(a, token("dev", b"key-a", "/dev/sda2"));
+    }
+
+    #[test]
+    fn test_redact_replaces_longest_first() {
+        register_device("/dev/sdr");
+        register_device("/dev/sdr1");
+        register_mount_point("/mnt/redact-test");
+        set_redact(true);
+        let redacted = redact("Opening /dev/sdr1 at /mnt/redact-test");
+        set_redact(false);
+
+        let device = token("dev", token_key(), "/dev/sdr1");
+        let mount_point = token("mnt", token_key(), "/mnt/redact-test");
+        assert_eq!(redacted, format!("Opening {} at {}", device, mount_point));
+        assert_eq!(redact("/dev/sdr1"), "/dev/sdr1");
+    }
+}
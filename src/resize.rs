@@ -0,0 +1,95 @@
+//! Filesystem growth after a device resize
+//!
+//! This module grows the filesystem on an opened LUKS mapper to fill the
+//! mapper, after `cryptsetup resize`, with security hardening:
+//! - Only devices under /dev/mapper are accepted
+//! - The tool is picked from the detected filesystem type, never from input
+//! - Safe command execution
+
+use anyhow::{bail, Context, Result};
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::command::{is_dry_run, print_dry_run, run};
+use crate::mount::{detect_fs_type, find_mount_by_device};
+use crate::tools::{tool_command, Tool};
+
+/// Build the command that grows a filesystem of type `fs_type`
+/// 
+/// ext2/3/4 are grown through the device (online if mounted); XFS can only
+/// be grown while mounted, through its mount point.
+fn grow_command(fs_type: &str, mapper_path: &Path, mount_point: Option<&Path>) -> Result<Command> {
+    match fs_type {
+        "ext2" | "ext3" | "ext4" => {
+            let mut cmd = tool_command(Tool::Resize2fs);
+            cmd.arg(mapper_path);
+            Ok(cmd)
+        }
+        "xfs" => {
+            let Some(mount_point) = mount_point else {
+                bail!("{}", t!("resize.xfs_not_mounted", path = mapper_path.display().to_string()));
+            };
+            let mut cmd = tool_command(Tool::XfsGrowfs);
+            cmd.arg(mount_point);
+            Ok(cmd)
+        }
+        _ => bail!("{}", t!("resize.fs_unsupported", fs_type = fs_type)),
+    }
+}
+
+/// Grow the filesystem on a mapper device to fill the device
+/// 
+/// # Security
+/// - Mapper path must be under /dev/mapper
+/// - Filesystem type is detected with blkid and checked against a whitelist
+pub fn grow_filesystem(mapper_path: &Path) -> Result<()> {
+    if !mapper_path.starts_with("/dev/mapper") {
+        bail!("{}", t!("fsck.mapper_path_not_mapper", path = mapper_path.display().to_string()));
+    }
+
+    let Some(fs_type) = detect_fs_type(mapper_path)? else {
+        bail!("{}", t!("resize.fs_unknown", path = mapper_path.display().to_string()));
+    };
+    let mount_point: Option<PathBuf> = find_mount_by_device(mapper_path)?.map(|entry| entry.mount_point);
+
+    let mut cmd = grow_command(&fs_type, mapper_path, mount_point.as_deref())?;
+
+    if is_dry_run() {
+        print_dry_run(&cmd);
+        return Ok(());
+    }
+
+    let output = run(&mut cmd, None)
+        .context(t!("resize.failed_execute", fs_type = &fs_type).to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{}", t!("resize.grow_failed", fs_type = &fs_type, error = stderr.trim()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::tool_path;
+
+    #[test]
+    fn test_grow_command_by_fs_type() {
+        let device = Path::new("/dev/mapper/luks-test");
+        let mount_point = Path::new("/mnt/data");
+
+        let cmd = grow_command("ext4", device, Some(mount_point)).unwrap();
+        assert_eq!(cmd.get_program(), tool_path(Tool::Resize2fs));
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), [device]);
+
+        let cmd = grow_command("xfs", device, Some(mount_point)).unwrap();
+        assert_eq!(cmd.get_program(), tool_path(Tool::XfsGrowfs));
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), [mount_point]);
+
+        assert!(grow_command("xfs", device, None).is_err());
+        assert!(grow_command("vfat", device, Some(mount_point)).is_err());
+    }
+}
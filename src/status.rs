@@ -0,0 +1,360 @@
+//! Volume status reporting
+//!
+//! This module reconciles the luksctl state file, /proc/mounts and
+//! `cryptsetup status` into a single report. Reports can be rendered either as
+//! localized human-readable text or as a stable JSON object for scripting.
+
+use anyhow::{bail, Context, Result};
+use rust_i18n::t;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::luks::{is_mapper_active, luks_status};
+use crate::mapper::{get_mapper_path, get_mount_mapping, list_mappings, MountOwner};
+use crate::mount::{find_mount_by_device, find_mount_entry, MountEntry};
+use crate::pathsafe::{ensure_no_null, ensure_no_traversal};
+
+const MAPPER_PREFIX: &str = "/dev/mapper/";
+
+/// How reports are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Localized, human-readable text
+    #[default]
+    Human,
+    /// Stable JSON, independent of the locale
+    Json,
+}
+
+impl OutputFormat {
+    /// Select the output format from a `--json` flag
+    pub fn from_json_flag(json: bool) -> Self {
+        if json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Human
+        }
+    }
+}
+
+/// An inconsistency between the state file, /proc/mounts and dm-crypt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StatusIssue {
+    /// State file exists but the mount point is not mounted
+    StateNotMounted,
+    /// State file exists but the mapper is not active
+    StateMapperInactive,
+    /// Mounted from a mapper but no state file was found
+    MountedNoState,
+    /// Mapper is active but not mounted anywhere
+    ActiveNotMounted,
+    /// State file and /proc/mounts disagree on the mapper name
+    MapperMismatch { state: String, mounted: String },
+}
+
+/// Reconciled status of a single luksctl mapping
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingStatus {
+    pub mapper_name: String,
+    pub device: Option<PathBuf>,
+    pub header: Option<PathBuf>,
+    pub mount_point: Option<PathBuf>,
+    pub active: bool,
+    pub mounted: bool,
+    pub read_only: bool,
+    /// Whether the dm-crypt mapping itself is read-only
+    pub crypt_read_only: bool,
+    /// dm-integrity protection of an active mapping, if any
+    pub integrity: Option<String>,
+    pub fs_type: Option<String>,
+    /// User the volume was mounted for, from the state file
+    pub owner: Option<MountOwner>,
+    /// When the volume was mounted, in seconds since the Unix epoch
+    pub mounted_at: Option<u64>,
+    pub state_file: bool,
+    pub issues: Vec<StatusIssue>,
+}
+
+/// A mapping recorded in the luksctl state directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MappingEntry {
+    pub mount_point: PathBuf,
+    pub mapper_name: String,
+    pub device: PathBuf,
+}
+
+/// Extract the mapper name from a /proc/mounts entry's source device
+fn mapper_from_entry(entry: &MountEntry) -> Option<String> {
+    Path::new(&entry.device)
+        .strip_prefix(MAPPER_PREFIX)
+        .ok()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Build the status report for a mount point or mapper name
+///
+/// A target under `/dev/mapper/` or without a leading slash names a mapper;
+/// anything else is treated as a mount point.
+///
+/// # Security
+/// - Rejects targets containing null bytes or path traversal
+/// - Mapper names are validated before being passed to cryptsetup
+pub fn mapping_status(target: &str) -> Result<MappingStatus> {
+    if ensure_no_null(Path::new(target)).is_err() || ensure_no_traversal(Path::new(target)).is_err() {
+        bail!("{}", t!("status.invalid_target"));
+    }
+
+    let (mount_point, entry, state, mapper_name) = if target.starts_with('/') && !target.starts_with(MAPPER_PREFIX) {
+        let mount_point_arg = PathBuf::from(target);
+        let mount_point = mount_point_arg.canonicalize()
+            .unwrap_or(mount_point_arg);
+        let entry = find_mount_entry(&mount_point)?;
+        let state = get_mount_mapping(&mount_point)?;
+        let mapper_name = state.as_ref()
+            .map(|state| state.mapper_name.clone())
+            .or_else(|| entry.as_ref().and_then(mapper_from_entry));
+        (Some(mount_point), entry, state, mapper_name)
+    } else {
+        let name = target.strip_prefix(MAPPER_PREFIX).unwrap_or(target).to_string();
+        let entry = find_mount_by_device(&get_mapper_path(&name))?;
+        let mount_point = entry.as_ref().map(|e| e.mount_point.clone());
+        let state = match mount_point {
+            Some(ref mp) => get_mount_mapping(mp)?,
+            None => None,
+        };
+        (mount_point, entry, state, Some(name))
+    };
+
+    let mapper_name = match mapper_name {
+        Some(name) => name,
+        None => bail!("{}", t!("status.mapper_not_found", target = target)),
+    };
+
+    let active = is_mapper_active(&mapper_name)?;
+    let crypt = if active { Some(luks_status(&mapper_name)?) } else { None };
+
+    // Reconcile the three sources and record anything that disagrees
+    let mut issues = Vec::new();
+    if state.is_some() && entry.is_none() {
+        issues.push(StatusIssue::StateNotMounted);
+    }
+    if state.is_some() && !active {
+        issues.push(StatusIssue::StateMapperInactive);
+    }
+    if state.is_none() && entry.is_some() {
+        issues.push(StatusIssue::MountedNoState);
+    }
+    if active && entry.is_none() {
+        issues.push(StatusIssue::ActiveNotMounted);
+    }
+    let state_mapper = state.as_ref().map(|state| state.mapper_name.clone());
+    let mounted_mapper = entry.as_ref().and_then(mapper_from_entry);
+    if let (Some(state_mapper), Some(mounted_mapper)) = (state_mapper, mounted_mapper) {
+        if state_mapper != mounted_mapper {
+            issues.push(StatusIssue::MapperMismatch { state: state_mapper, mounted: mounted_mapper });
+        }
+    }
+
+    // Prefer what dm-crypt reports for an active mapping over the record
+    let crypt_read_only = match crypt {
+        Some(ref crypt) => crypt.read_only,
+        None => state.as_ref().is_some_and(|state| state.crypt_read_only),
+    };
+
+    let integrity = crypt.as_ref().and_then(|crypt| crypt.integrity.clone());
+
+    Ok(MappingStatus {
+        mapper_name,
+        device: state.as_ref()
+            .map(|state| state.device.clone())
+            .or_else(|| crypt.and_then(|c| c.device)),
+        header: state.as_ref().and_then(|state| state.header.clone()),
+        mount_point,
+        active,
+        mounted: entry.is_some(),
+        read_only: entry.as_ref().is_some_and(MountEntry::is_read_only),
+        crypt_read_only,
+        integrity,
+        fs_type: entry.map(|e| e.fs_type),
+        owner: state.as_ref().and_then(|state| state.owner.clone()),
+        mounted_at: state.as_ref().and_then(|state| state.mounted_at),
+        state_file: state.is_some(),
+        issues,
+    })
+}
+
+/// Collect all mappings recorded in the state directory
+pub fn mapping_entries() -> Result<Vec<MappingEntry>> {
+    Ok(list_mappings()?
+        .into_iter()
+        .map(|(mount_point, mapper_name, device)| MappingEntry { mount_point, mapper_name, device })
+        .collect())
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time
+///
+/// Uses the days-to-civil conversion from Howard Hinnant's date
+/// algorithms, which is exact for the proleptic Gregorian calendar.
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, time / 3_600, time % 3_600 / 60, time % 60)
+}
+
+fn yes_no(value: bool) -> String {
+    if value {
+        t!("status.value_yes").to_string()
+    } else {
+        t!("status.value_no").to_string()
+    }
+}
+
+/// Localized description of a status issue
+fn describe_issue(issue: &StatusIssue) -> String {
+    match issue {
+        StatusIssue::StateNotMounted => t!("status.issue_state_not_mounted").to_string(),
+        StatusIssue::StateMapperInactive => t!("status.issue_state_mapper_inactive").to_string(),
+        StatusIssue::MountedNoState => t!("status.issue_mounted_no_state").to_string(),
+        StatusIssue::ActiveNotMounted => t!("status.issue_active_not_mounted").to_string(),
+        StatusIssue::MapperMismatch { state, mounted } => {
+            t!("status.issue_mapper_mismatch", state = state, mounted = mounted).to_string()
+        }
+    }
+}
+
+/// Write a status report to stdout in the requested format
+pub fn print_status(status: &MappingStatus, format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(status)
+            .context(t!("status.failed_serialize").to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let unknown = t!("status.value_unknown").to_string();
+    let display = |path: &Option<PathBuf>| path.as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| unknown.clone());
+
+    println!("{}", t!("status.label_mapper", name = &status.mapper_name));
+    println!("{}", t!("status.label_active", value = yes_no(status.active)));
+    if status.active {
+        println!("{}", t!("status.label_crypt_read_only", value = yes_no(status.crypt_read_only)));
+        if let Some(ref integrity) = status.integrity {
+            println!("{}", t!("status.label_integrity", value = integrity));
+        }
+    }
+    println!("{}", t!("status.label_device", path = display(&status.device)));
+    if status.header.is_some() {
+        println!("{}", t!("status.label_header", path = display(&status.header)));
+    }
+    println!("{}", t!("status.label_mount_point", path = display(&status.mount_point)));
+    println!("{}", t!("status.label_mounted", value = yes_no(status.mounted)));
+    if let Some(ref fs_type) = status.fs_type {
+        println!("{}", t!("status.label_read_only", value = yes_no(status.read_only)));
+        println!("{}", t!("status.label_fs_type", fs_type = fs_type));
+    }
+    if let Some(ref owner) = status.owner {
+        println!("{}", t!("status.label_owner", owner = owner.to_string()));
+    }
+    if let Some(mounted_at) = status.mounted_at {
+        println!("{}", t!("status.label_mounted_since", time = format_timestamp(mounted_at)));
+    }
+    println!("{}", t!("status.label_state_file", value = yes_no(status.state_file)));
+
+    if status.issues.is_empty() {
+        println!("\n{}", t!("status.consistent"));
+    } else {
+        println!("\n{}", t!("status.inconsistent"));
+        for issue in &status.issues {
+            println!("  - {}", describe_issue(issue));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a mapping listing to stdout in the requested format
+pub fn print_mappings(entries: &[MappingEntry], format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let json = serde_json::to_string_pretty(entries)
+            .context(t!("status.failed_serialize").to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", t!("status.no_mappings"));
+        return Ok(());
+    }
+
+    println!("{}", t!("status.list_header"));
+    for entry in entries {
+        println!("{}\t{}\t{}",
+            entry.mount_point.display(), entry.mapper_name, entry.device.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_field_names_are_stable() {
+        let status = MappingStatus {
+            mapper_name: "luks-test".to_string(),
+            device: Some(PathBuf::from("/dev/sda1")),
+            header: None,
+            mount_point: Some(PathBuf::from("/mnt/test")),
+            active: true,
+            mounted: true,
+            read_only: false,
+            crypt_read_only: true,
+            integrity: Some("hmac(sha256)".to_string()),
+            fs_type: Some("ext4".to_string()),
+            owner: Some(MountOwner { uid: 1000, name: Some("alice".to_string()) }),
+            mounted_at: Some(1_700_000_000),
+            state_file: true,
+            issues: vec![StatusIssue::MapperMismatch {
+                state: "luks-a".to_string(),
+                mounted: "luks-b".to_string(),
+            }],
+        };
+
+        let value = serde_json::to_value(&status).unwrap();
+        assert_eq!(value["device"], "/dev/sda1");
+        assert_eq!(value["mapper_name"], "luks-test");
+        assert_eq!(value["mount_point"], "/mnt/test");
+        assert_eq!(value["read_only"], false);
+        assert_eq!(value["crypt_read_only"], true);
+        assert_eq!(value["integrity"], "hmac(sha256)");
+        assert_eq!(value["fs_type"], "ext4");
+        assert_eq!(value["mounted"], true);
+        assert_eq!(value["issues"][0]["kind"], "mapper_mismatch");
+        assert_eq!(value["owner"]["uid"], 1000);
+        assert_eq!(value["owner"]["name"], "alice");
+        assert_eq!(value["mounted_at"], 1_700_000_000);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+}
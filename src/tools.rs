@@ -0,0 +1,145 @@
+//! External tool discovery
+//!
+//! luksctl never looks programs up through `PATH`, which may be stripped or
+//! under someone else's control. Each tool is resolved once to an absolute
+//! path:
+//! - `LUKSCTL_<TOOL>` (e.g. `LUKSCTL_CRYPTSETUP`) names it explicitly
+//! - Otherwise the first executable in the system directories is used
+
+use log::warn;
+use rust_i18n::t;
+use std::ffi::OsString;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::pathsafe::ensure_safe_absolute;
+
+/// Directories searched for tools, in order
+const SYSTEM_DIRS: &[&str] = &["/usr/sbin", "/sbin", "/usr/bin", "/bin"];
+
+/// External programs luksctl runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Cryptsetup,
+    Mount,
+    Umount,
+    Blkid,
+    Losetup,
+    Fsck,
+    Resize2fs,
+    XfsGrowfs,
+    Nsenter,
+}
+
+impl Tool {
+    const COUNT: usize = 9;
+    const ALL: [Tool; Tool::COUNT] = [
+        Tool::Cryptsetup,
+        Tool::Mount,
+        Tool::Umount,
+        Tool::Blkid,
+        Tool::Losetup,
+        Tool::Fsck,
+        Tool::Resize2fs,
+        Tool::XfsGrowfs,
+        Tool::Nsenter,
+    ];
+
+    /// File name of the program
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Cryptsetup => "cryptsetup",
+            Tool::Mount => "mount",
+            Tool::Umount => "umount",
+            Tool::Blkid => "blkid",
+            Tool::Losetup => "losetup",
+            Tool::Fsck => "fsck",
+            Tool::Resize2fs => "resize2fs",
+            Tool::XfsGrowfs => "xfs_growfs",
+            Tool::Nsenter => "nsenter",
+        }
+    }
+
+    /// Environment variable overriding the program's path
+    pub fn env_var(self) -> &'static str {
+        match self {
+            Tool::Cryptsetup => "LUKSCTL_CRYPTSETUP",
+            Tool::Mount => "LUKSCTL_MOUNT",
+            Tool::Umount => "LUKSCTL_UMOUNT",
+            Tool::Blkid => "LUKSCTL_BLKID",
+            Tool::Losetup => "LUKSCTL_LOSETUP",
+            Tool::Fsck => "LUKSCTL_FSCK",
+            Tool::Resize2fs => "LUKSCTL_RESIZE2FS",
+            Tool::XfsGrowfs => "LUKSCTL_XFS_GROWFS",
+            Tool::Nsenter => "LUKSCTL_NSENTER",
+        }
+    }
+}
+
+/// Whether `path` is a regular file with an execute bit set
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Resolve the path of `tool`, honouring an override from the environment
+/// 
+/// An override that isn't an absolute path to an executable is ignored with
+/// a warning. When the tool is found nowhere, the path in the first system
+/// directory is returned so running it fails with "not found" instead of
+/// falling back to `PATH`.
+fn resolve_tool(tool: Tool, override_path: Option<OsString>) -> PathBuf {
+    if let Some(path) = override_path.filter(|path| !path.is_empty()).map(PathBuf::from) {
+        let path_str = path.to_string_lossy();
+        if ensure_safe_absolute(&path).is_ok() && is_executable(&path) {
+            return path;
+        }
+        warn!("{}", t!("tools.override_ignored", var = tool.env_var(), path = path_str));
+    }
+    
+    SYSTEM_DIRS.iter()
+        .map(|dir| Path::new(dir).join(tool.name()))
+        .find(|path| is_executable(path))
+        .unwrap_or_else(|| Path::new(SYSTEM_DIRS[0]).join(tool.name()))
+}
+
+/// Absolute path of `tool`, resolved on first use
+pub fn tool_path(tool: Tool) -> &'static Path {
+    static PATHS: [OnceLock<PathBuf>; Tool::COUNT] = [const { OnceLock::new() }; Tool::COUNT];
+    PATHS[tool as usize].get_or_init(|| resolve_tool(tool, std::env::var_os(tool.env_var())))
+}
+
+/// Whether `tool` resolved to an executable that exists
+pub fn tool_available(tool: Tool) -> bool {
+    is_executable(tool_path(tool))
+}
+
+/// The tool resolved to `path`, if any
+pub fn tool_by_path(path: &Path) -> Option<Tool> {
+    Tool::ALL.into_iter().find(|&tool| tool_path(tool) == path)
+}
+
+/// A `Command` for `tool`, run by absolute path
+pub fn tool_command(tool: Tool) -> Command {
+    Command::new(tool_path(tool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_tool_override() {
+        let sh = OsString::from("/bin/sh");
+        assert_eq!(resolve_tool(Tool::Cryptsetup, Some(sh)), PathBuf::from("/bin/sh"));
+
+        // Unusable overrides fall back to the system directories
+        for bad in ["sh", "/bin/../bin/sh", "/nonexistent/cryptsetup", "/etc/passwd", ""] {
+            let resolved = resolve_tool(Tool::Cryptsetup, Some(OsString::from(bad)));
+            assert!(resolved.is_absolute());
+            assert!(resolved.ends_with("cryptsetup"), "{bad:?} gave {resolved:?}");
+        }
+    }
+}
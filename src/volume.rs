@@ -0,0 +1,646 @@
+//! Opening and mounting a volume in one step, and tearing it down again
+//!
+//! [`open_and_mount`] is the whole flow behind `luks_mount`: it allocates a
+//! mapper name, opens the device, waits for the mapper, optionally checks
+//! the filesystem, mounts it with its bind mounts and records the state.
+//! Everything done so far is undone if a later step fails, so a failed call
+//! leaves neither a mapping nor a mount behind.
+//!
+//! [`unmount_and_close`] is its counterpart behind `luks_umount`: bind
+//! mounts, mounts nested inside the volume, the filesystem, the mapper, the
+//! loop device and the state file are removed in that order.
+//! [`close_volume`], behind `luks_close`, does only the part after the
+//! unmount, for a filesystem unmounted by other means.
+
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+use rust_i18n::t;
+use secrecy::SecretString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::command::is_dry_run;
+use crate::error;
+use crate::fsck::run_fsck;
+use crate::loopdev::{detach_loop, is_loop_attached};
+use crate::luks::{luks_close, luks_open, luks_open_with_keyfiles, plain_open, LuksOpenOptions, PlainOpenOptions};
+use crate::mapper::{
+    allocate_unique_name, find_state_by_mapper, get_mapper_path, get_mount_mapping, invoking_owner, mapper_exists,
+    mapper_prefix, name_retries, remove_mount_mapping, resolve_mapper_name, store_mount_mapping, unix_timestamp,
+    validate_mapper_name, wait_for_mapper, with_state_lock, MountState, STATE_VERSION,
+};
+use crate::mount::{
+    bind_mount, find_mount_entry, find_mounts_by_device, is_dir_empty, is_mounted, mount_device, sync_mount, unmount,
+    unmount_lazy, unmount_with_retry, BindMount, MountOptions,
+};
+use crate::mountinfo::MountTree;
+use crate::pathsafe::{ensure_absolute, ensure_no_null};
+
+/// Default delay between unmount attempts
+const UNMOUNT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How to open and mount a volume
+#[derive(Debug, Clone, Default)]
+pub struct OpenMountOptions {
+    /// Mapper name to use instead of a generated one
+    pub mapper_name: Option<String>,
+    /// Unlock with the first of these key files that works instead of a
+    /// passphrase
+    pub key_files: Vec<PathBuf>,
+    pub open: LuksOpenOptions,
+    /// Open as plain dm-crypt with these parameters instead of LUKS
+    pub plain: Option<PlainOpenOptions>,
+    pub mount: MountOptions,
+    /// Check the filesystem before mounting
+    pub fsck: bool,
+    /// Let fsck repair what it finds (implies `fsck`)
+    pub fsck_repair: bool,
+    /// `(source, target)` pairs bind-mounted after the volume, with sources
+    /// relative to the mount point
+    pub binds: Vec<(PathBuf, PathBuf)>,
+    /// Loop device the container file in `device` is attached to; it is
+    /// opened instead of `device` and recorded in the state
+    pub loop_device: Option<PathBuf>,
+    /// The device as the caller named it, if that differs from `device`
+    pub device_input: Option<String>,
+    /// Record the mount in the state directory (and take its lock)
+    pub store_state: bool,
+    /// The caller created the mount point; recorded so `luks_umount
+    /// --rmdir` may remove it
+    pub created_dir: bool,
+}
+
+/// An opened and mounted volume
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountResult {
+    pub mapper_name: String,
+    pub mapper_path: PathBuf,
+    /// The key file that unlocked the device, if key files were given
+    pub key_file: Option<PathBuf>,
+}
+
+/// Use the requested mapper name, or generate an unused one
+fn allocate_mapper_name(requested: Option<&str>) -> Result<String> {
+    if let Some(name) = requested {
+        if mapper_exists(name) {
+            bail!("{}", t!("luks_mount.mapper_name_in_use", name = name));
+        }
+        return Ok(name.to_string());
+    }
+
+    Ok(allocate_unique_name(&mapper_prefix()?, name_retries()?)?)
+}
+
+/// Open `device` and mount it on `mount_point`
+///
+/// `password` unlocks the device unless `options.key_files` are given, in
+/// which case it is ignored. The device and mount point are expected to be
+/// validated and canonical already; the individual steps validate them
+/// again.
+///
+/// A rejected passphrase is returned as [`LuksError::WrongPassphrase`]
+/// (through `anyhow`), so callers can ask again.
+///
+/// # Security
+/// - Name allocation, open, mount and state are done under the state lock
+///   when `options.store_state` is set, so concurrent calls can't race
+/// - On any failure the mapper is closed and any mounts made are undone
+///
+/// [`LuksError::WrongPassphrase`]: crate::error::LuksError::WrongPassphrase
+pub fn open_and_mount(
+    device: &Path,
+    mount_point: &Path,
+    password: Option<&SecretString>,
+    options: &OpenMountOptions,
+) -> Result<MountResult> {
+    if options.store_state {
+        with_state_lock(|| open_and_mount_locked(device, mount_point, password, options))
+    } else {
+        open_and_mount_locked(device, mount_point, password, options)
+    }
+}
+
+/// The body of [`open_and_mount`], run with the state lock held if needed
+fn open_and_mount_locked(
+    device: &Path,
+    mount_point: &Path,
+    password: Option<&SecretString>,
+    options: &OpenMountOptions,
+) -> Result<MountResult> {
+    let mapper_name = allocate_mapper_name(options.mapper_name.as_deref())?;
+    info!("{}", t!("luks_mount.using_mapper", name = &mapper_name));
+
+    // Open the loop device a container file is attached to, if any
+    let open_device = options.loop_device.as_deref().unwrap_or(device);
+    let key_file = match (options.key_files.is_empty(), password) {
+        (false, _) => Some(luks_open_with_keyfiles(open_device, &mapper_name, &options.key_files, &options.open)?),
+        (true, Some(password)) => {
+            match options.plain {
+                Some(ref plain) => plain_open(open_device, &mapper_name, password, plain)?,
+                None => luks_open(open_device, &mapper_name, password, &options.open)?,
+            }
+            None
+        }
+        (true, None) => bail!("{}", t!("volume.no_passphrase")),
+    };
+    info!("{}", t!("luks_mount.luks_opened_success"));
+
+    // Wait for the mapper device node before touching it
+    let mapper_path = match wait_for_mapper(&mapper_name) {
+        Ok(path) => path,
+        Err(e) => {
+            let _ = luks_close(&mapper_name);
+            return Err(e.into());
+        }
+    };
+
+    // Check the filesystem before mounting if requested
+    if options.fsck || options.fsck_repair {
+        info!("{}", t!("luks_mount.running_fsck", path = mapper_path.display().to_string()));
+        if let Err(e) = run_fsck(&mapper_path, options.fsck_repair) {
+            warn!("{}", t!("luks_mount.fsck_failed_closing"));
+            let _ = luks_close(&mapper_name);
+            return Err(e);
+        }
+    }
+
+    info!("{}", t!("luks_mount.mounting_to", path = mount_point.display().to_string()));
+    if let Err(e) = mount_device(&mapper_path, mount_point, &options.mount) {
+        warn!("{}", t!("luks_mount.mount_failed_closing"));
+        let _ = luks_close(&mapper_name);
+        return Err(e.into());
+    }
+
+    // Bind-mount subdirectories of the volume, rolling everything back if
+    // any of them fails
+    let mut binds = Vec::new();
+    for (source, target) in &options.binds {
+        info!("{}", t!("luks_mount.binding",
+            source = source.display().to_string(), target = target.display().to_string()));
+        match bind_mount(mount_point, source, target) {
+            Ok(bind) => binds.push(bind),
+            Err(e) => {
+                warn!("{}", t!("luks_mount.bind_failed_rolling_back"));
+                for bind in binds.iter().rev() {
+                    let _ = unmount(&bind.target);
+                }
+                let _ = unmount(mount_point);
+                let _ = luks_close(&mapper_name);
+                return Err(e.into());
+            }
+        }
+    }
+
+    // Store the mapping and the options used for later unmount or remount
+    if options.store_state {
+        // Record the type the kernel actually mounted, which may have been
+        // probed rather than requested
+        let fs_type = find_mount_entry(mount_point).ok().flatten()
+            .map(|entry| entry.fs_type)
+            .or_else(|| options.mount.fs_type.clone());
+        store_mount_mapping(&MountState {
+            version: STATE_VERSION,
+            mapper_name: mapper_name.clone(),
+            device: device.to_path_buf(),
+            device_input: options.device_input.clone(),
+            mount_point: mount_point.to_path_buf(),
+            header: options.open.header.clone(),
+            mount_options: Some(options.mount.clone()),
+            loop_device: options.loop_device.clone(),
+            binds,
+            crypt_read_only: options.open.read_only,
+            fs_type,
+            mounted_at: Some(unix_timestamp()),
+            uid: Some(nix::unistd::getuid().as_raw()),
+            owner: Some(invoking_owner()),
+            created_dir: options.created_dir,
+        })?;
+    }
+
+    Ok(MountResult { mapper_name, mapper_path, key_file })
+}
+
+/// How to unmount and close a volume
+#[derive(Debug, Clone)]
+pub struct UnmountCloseOptions {
+    /// Fall back to a lazy unmount when the filesystem stays busy
+    /// 
+    /// Callers are responsible for confirming this with the user first.
+    pub force: bool,
+    /// Retries of a busy unmount before giving up (or falling back to lazy)
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// Flush the filesystem before unmounting
+    pub sync: bool,
+    /// Treat a mount point that is no longer mounted as success, finishing
+    /// whatever teardown a previous run left behind
+    pub if_mounted: bool,
+    /// Leave the mapper (and any loop device under it) open
+    /// 
+    /// The state file is kept, so [`close_volume`] (`luks_close`) can close
+    /// the mapping and detach the loop device afterwards.
+    pub keep_open: bool,
+    /// Refuse to tear down a volume whose state records a different owner
+    /// (see [`invoking_owner`]), unless `force` is also set
+    pub owner_only: bool,
+    /// Remove the mount point at the end of the teardown, if luksctl
+    /// created it and it is empty
+    /// 
+    /// Fails before anything is torn down if the state doesn't record the
+    /// directory as created by luksctl. Failing to remove it is only a
+    /// warning.
+    pub rmdir: bool,
+}
+
+impl Default for UnmountCloseOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            retries: 0,
+            retry_delay: UNMOUNT_RETRY_DELAY,
+            sync: true,
+            if_mounted: false,
+            keep_open: false,
+            owner_only: false,
+            rmdir: false,
+        }
+    }
+}
+
+/// Tear down bind mounts in reverse order of creation
+fn unmount_binds(binds: &[BindMount]) -> error::Result<()> {
+    for bind in binds.iter().rev() {
+        if is_mounted(&bind.target)? {
+            unmount_submounts(&bind.target)?;
+            info!("{}", t!("luks_umount.unmounting_bind", path = bind.target.display().to_string()));
+            unmount(&bind.target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unmount everything mounted inside `mount_point`, innermost first
+/// 
+/// The nested mounts are found in /proc/self/mountinfo below the visible
+/// mount on `mount_point`. Without mountinfo nothing is unmounted, and a
+/// nested mount makes the final unmount fail as busy instead.
+fn unmount_submounts(mount_point: &Path) -> error::Result<()> {
+    let Ok(tree) = MountTree::read() else {
+        debug!("{}", t!("luks_umount.mountinfo_unavailable", path = mount_point.display().to_string()));
+        return Ok(());
+    };
+    let Some(top) = tree.find_by_mount_point(mount_point) else {
+        return Ok(());
+    };
+
+    for nested in tree.descendants(top.mount_id) {
+        info!("{}", t!("luks_umount.unmounting_submount", path = nested.mount_point.display().to_string()));
+        unmount(&nested.mount_point)?;
+    }
+    Ok(())
+}
+
+/// Bind mounts of a mapper found in /proc/mounts, for volumes without a
+/// state file
+/// 
+/// Every other mount of the mapper device is treated as a bind mount of
+/// the volume mounted at `mount_point`.
+fn discover_binds(mapper_name: &str, mount_point: &Path) -> error::Result<Vec<BindMount>> {
+    Ok(find_mounts_by_device(&get_mapper_path(mapper_name))?
+        .into_iter()
+        .filter(|entry| entry.mount_point != mount_point)
+        .map(|entry| BindMount { source: mount_point.to_path_buf(), target: entry.mount_point })
+        .collect())
+}
+
+/// Detach the loop device a container file was attached to, if any
+fn detach_loop_device(loop_device: Option<&Path>) -> error::Result<()> {
+    if let Some(loop_device) = loop_device {
+        if is_loop_attached(loop_device) {
+            info!("{}", t!("luks_umount.detaching_loop", path = loop_device.display().to_string()));
+            detach_loop(loop_device)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove an unmounted mount point that luksctl created
+/// 
+/// Runs last, once the mapper is closed and the state file removed, so a
+/// directory that can't be removed is only a warning and never leaves the
+/// teardown half done. A directory that isn't empty is kept with a warning
+/// too: the files in it were never on the volume.
+fn remove_mount_point(mount_point: &Path) {
+    if is_dry_run() {
+        println!("{}", t!("luks_umount.dry_run_rmdir", path = mount_point.display().to_string()));
+        return;
+    }
+    if !mount_point.exists() {
+        return;
+    }
+    
+    let removed = is_dir_empty(mount_point).map_err(|e| format!("{:#}", e)).and_then(|empty| {
+        if !empty {
+            return Ok(false);
+        }
+        std::fs::remove_dir(mount_point).map(|()| true).map_err(|e| e.to_string())
+    });
+    match removed {
+        Ok(true) => info!("{}", t!("luks_umount.removed_mount_point", path = mount_point.display().to_string())),
+        Ok(false) => warn!("{}", t!("luks_umount.rmdir_not_empty", path = mount_point.display().to_string())),
+        Err(e) => warn!("{}", t!("luks_umount.failed_rmdir", path = mount_point.display().to_string(), error = e)),
+    }
+}
+
+/// Fail unless the volume was mounted by the invoking user
+/// 
+/// Volumes without a recorded owner (no state file, or one written by an
+/// older release) are not restricted.
+fn ensure_owner(mount_point: &Path, state: Option<&MountState>) -> error::Result<()> {
+    let Some(owner) = state.and_then(|state| state.owner.as_ref()) else {
+        return Ok(());
+    };
+    let invoking = invoking_owner();
+    if owner.uid != invoking.uid {
+        error::bail!("{}", t!("luks_umount.not_owner",
+            path = mount_point.display().to_string(), owner = owner.to_string(), user = invoking.to_string()));
+    }
+    Ok(())
+}
+
+/// Unmount the volume at `mount_point` and close its mapper
+/// 
+/// With `force`, a filesystem that stays busy is unmounted lazily; callers
+/// should confirm that with the user first. See [`unmount_and_close_with`]
+/// for the other options.
+pub fn unmount_and_close(mount_point: &Path, force: bool) -> error::Result<()> {
+    unmount_and_close_with(mount_point, &UnmountCloseOptions { force, ..Default::default() })
+}
+
+/// Unmount the volume at `mount_point` and close its mapper
+/// 
+/// The mapper is found through the state file, or /proc/mounts for volumes
+/// mounted without one. Errors from each stage are passed on unchanged, so
+/// callers can match on them: [`LuksError::MountBusy`] when the filesystem
+/// is in use, [`LuksError::CommandFailed`] when cryptsetup can't close the
+/// mapping, and so on.
+/// 
+/// # Security
+/// - `mount_point` must be absolute; it is canonicalized before use
+/// - Nothing is touched if another filesystem is mounted over the volume
+/// - With `owner_only`, nothing is touched if another user mounted it
+/// - With `rmdir`, only a mount point recorded as created by luksctl is
+///   removed, and only while empty
+/// - The state file is only removed once the mapper is closed; with
+///   `keep_open` it stays for `luks_close`
+/// 
+/// [`LuksError::MountBusy`]: crate::error::LuksError::MountBusy
+/// [`LuksError::CommandFailed`]: crate::error::LuksError::CommandFailed
+pub fn unmount_and_close_with(mount_point: &Path, options: &UnmountCloseOptions) -> error::Result<()> {
+    ensure_absolute(mount_point)?;
+    ensure_no_null(mount_point)?;
+    let mount_point = mount_point.canonicalize()
+        .unwrap_or_else(|_| mount_point.to_path_buf());
+    let state = get_mount_mapping(&mount_point)?;
+
+    if options.owner_only && !options.force {
+        ensure_owner(&mount_point, state.as_ref())?;
+    }
+    if options.rmdir && !state.as_ref().is_some_and(|state| state.created_dir) {
+        error::bail!("{}", t!("luks_umount.rmdir_not_created", path = mount_point.display().to_string()));
+    }
+
+    if !is_mounted(&mount_point)? {
+        if !options.if_mounted {
+            error::bail!("{}", t!("luks_umount.mount_point_not_mounted", path = mount_point.display().to_string()));
+        }
+
+        // Nothing is mounted; finish any teardown a previous run left behind
+        info!("{}", t!("luks_umount.not_mounted_skipping", path = mount_point.display().to_string()));
+        if let Some(state) = state {
+            unmount_binds(&state.binds)?;
+            if options.keep_open {
+                info!("{}", t!("luks_umount.keeping_open", name = &state.mapper_name));
+            } else if mapper_exists(&state.mapper_name) {
+                info!("{}", t!("luks_umount.closing_luks"));
+                luks_close(&state.mapper_name)?;
+                info!("{}", t!("luks_umount.luks_locked"));
+            }
+            if !options.keep_open {
+                detach_loop_device(state.loop_device.as_deref())?;
+            }
+        }
+        if !options.keep_open {
+            remove_mount_mapping(&mount_point)?;
+        }
+        if options.rmdir {
+            remove_mount_point(&mount_point);
+        }
+        return Ok(());
+    }
+
+    // Find the mapper from our state file, falling back to /proc/mounts
+    let Some(mapper_name) = resolve_mapper_name(&mount_point)? else {
+        error::bail!("{}", t!("luks_umount.mapper_not_found", path = mount_point.display().to_string()));
+    };
+
+    // umount only removes the topmost of stacked mounts, which has to be
+    // the volume itself rather than something mounted over it
+    if let Some(top) = find_mount_entry(&mount_point)? {
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if canonical(Path::new(&top.device)) != canonical(&get_mapper_path(&mapper_name)) {
+            error::bail!("{}", t!("luks_umount.overmounted",
+                path = mount_point.display().to_string(), device = &top.device));
+        }
+    }
+
+    info!("{}", t!("luks_umount.unmounting", path = mount_point.display().to_string()));
+    info!("{}", t!("luks_umount.mapper_info", name = &mapper_name));
+
+    // Flush first: a lazy unmount in particular won't wait for dirty data
+    if options.sync {
+        sync_mount(&mount_point)?;
+    }
+
+    // Bind mounts of the volume and anything mounted inside it must go
+    // before the volume itself; without a state file the binds are found
+    // through the mapper in /proc/mounts
+    match state {
+        Some(ref state) => unmount_binds(&state.binds)?,
+        None => unmount_binds(&discover_binds(&mapper_name, &mount_point)?)?,
+    }
+    unmount_submounts(&mount_point)?;
+
+    // Unmount the filesystem; with force, lazy unmount is the last resort
+    if options.force && options.retries == 0 {
+        unmount_lazy(&mount_point)?;
+    } else if let Err(e) = unmount_with_retry(&mount_point, options.retries, options.retry_delay) {
+        if !options.force {
+            return Err(e);
+        }
+        warn!("{}", t!("luks_umount.falling_back_lazy", error = e.to_string()));
+        unmount_lazy(&mount_point)?;
+    }
+    info!("{}", t!("luks_umount.filesystem_unmounted"));
+
+    if options.keep_open {
+        // The loop device stays too: the open mapper is still using it
+        info!("{}", t!("luks_umount.keeping_open", name = &mapper_name));
+    } else {
+        // Close the LUKS device unless a previous run already did
+        if options.if_mounted && !mapper_exists(&mapper_name) {
+            info!("{}", t!("luks_umount.mapper_already_closed", name = &mapper_name));
+        } else {
+            info!("{}", t!("luks_umount.closing_luks"));
+            luks_close(&mapper_name)?;
+            info!("{}", t!("luks_umount.luks_locked"));
+        }
+
+        // Container files are detached from their loop device last
+        detach_loop_device(state.as_ref().and_then(|state| state.loop_device.as_deref()))?;
+
+        // Remove our state file
+        let _ = remove_mount_mapping(&mount_point);
+    }
+
+    // Last, so a directory that can't be removed leaves nothing else behind
+    if options.rmdir {
+        remove_mount_point(&mount_point);
+    }
+
+    Ok(())
+}
+
+/// Close the mapper `mapper_name` of a volume that is no longer mounted
+/// 
+/// The loop device of a container file is detached and the state file
+/// removed afterwards. A mapper that is already gone is not an error: its
+/// leftover state is still cleaned up.
+/// 
+/// # Security
+/// - Only mappers carrying the luksctl prefix are accepted
+/// - Refused while the mapper is mounted anywhere, bind mounts included
+pub fn close_volume(mapper_name: &str) -> error::Result<()> {
+    validate_mapper_name(mapper_name)?;
+
+    let mounts = find_mounts_by_device(&get_mapper_path(mapper_name))?;
+    if let Some(mount) = mounts.first() {
+        error::bail!("{}", t!("luks_close.mapper_mounted",
+            name = mapper_name, path = mount.mount_point.display().to_string()));
+    }
+
+    let state = find_state_by_mapper(mapper_name)?;
+    if mapper_exists(mapper_name) {
+        info!("{}", t!("luks_umount.closing_luks"));
+        luks_close(mapper_name)?;
+        info!("{}", t!("luks_umount.luks_locked"));
+    } else {
+        info!("{}", t!("luks_umount.mapper_already_closed", name = mapper_name));
+    }
+
+    if let Some(state) = state {
+        detach_loop_device(state.loop_device.as_deref())?;
+        remove_mount_mapping(&state.mount_point)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+    use crate::mapper::with_state_dir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_close_volume_rejects_unmanaged_mapper() {
+        let (result, commands) = MockRunner::default().capture(|| close_volume("cryptroot"));
+
+        assert!(result.is_err());
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_open_and_mount_needs_passphrase_or_key_file() {
+        let (result, commands) = MockRunner::default().capture(|| {
+            open_and_mount(Path::new("/dev/null"), Path::new("/mnt"), None, &OpenMountOptions::default())
+        });
+
+        assert!(result.is_err());
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_remove_mount_point_only_when_empty() {
+        let dir = std::env::temp_dir().join(format!("luksctl-rmdir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("stray"), "").unwrap();
+
+        remove_mount_point(&dir);
+        assert!(dir.is_dir());
+
+        std::fs::remove_file(dir.join("stray")).unwrap();
+        remove_mount_point(&dir);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_failed_rmdir_still_finishes_teardown() {
+        let state_dir = std::env::temp_dir().join(format!("luksctl-rmdir-state-{}", std::process::id()));
+        std::fs::create_dir_all(&state_dir).unwrap();
+        std::fs::set_permissions(&state_dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::env::set_var("LUKSCTL_STATE_DIR", &state_dir);
+
+        // Replaced by a file since it was created, so rmdir fails even as root
+        let mount_point = std::env::temp_dir().join(format!("luksctl-rmdir-file-{}", std::process::id()));
+        std::fs::write(&mount_point, "").unwrap();
+        // Only this thread sees the temporary state directory
+        with_state_dir(state_dir.clone(), || {
+            store_mount_mapping(&MountState {
+                version: STATE_VERSION,
+                mapper_name: "luks-rmdir-test".to_string(),
+                device: PathBuf::from("/dev/null"),
+                device_input: None,
+                mount_point: mount_point.clone(),
+                header: None,
+                mount_options: None,
+                loop_device: None,
+                binds: Vec::new(),
+                crypt_read_only: false,
+                fs_type: None,
+                mounted_at: None,
+                uid: None,
+                owner: None,
+                created_dir: true,
+            }).unwrap();
+
+            // A re-run after the unmount: the mapper is already gone
+            let options = UnmountCloseOptions { if_mounted: true, rmdir: true, ..Default::default() };
+            let (result, commands) = MockRunner::default()
+                .capture(|| unmount_and_close_with(&mount_point, &options));
+
+            result.unwrap();
+            assert!(commands.is_empty());
+            assert!(get_mount_mapping(&mount_point).unwrap().is_none());
+            assert!(mount_point.is_file());
+        });
+
+        std::fs::remove_file(&mount_point).unwrap();
+        std::fs::remove_dir_all(&state_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unmount_and_close_rejects_relative_or_unmounted() {
+        let (result, commands) = MockRunner::default()
+            .capture(|| unmount_and_close(Path::new("mnt/data"), false));
+        assert!(result.is_err());
+        assert!(commands.is_empty());
+
+        let unmounted = std::env::temp_dir().join("luksctl-never-mounted");
+        let (result, commands) = MockRunner::default()
+            .capture(|| unmount_and_close(&unmounted, false));
+        assert!(matches!(result, Err(error::LuksError::Other(_))));
+        assert!(commands.is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! End-to-end tests against a real LUKS volume on a loop device
+//!
+//! These tests need root, `cryptsetup`, `losetup`, `mkfs.ext4` and free loop
+//! devices, so they are ignored by default. Run them with:
+//!
+//! ```sh
+//! sudo -E cargo test --test loopback -- --ignored --test-threads=1
+//! ```
+//!
+//! Tools are found through `PATH`, and state files are written to a private
+//! directory through `LUKSCTL_STATE_DIR`, never to `/run/luksctl`.
+
+use secrecy::SecretString;
+use std::fs::{self, File, Permissions};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use luksctl::error::LuksError;
+use luksctl::loopdev::{attach_loop, detach_loop};
+use luksctl::luks::{is_mapper_active, luks_close, luks_open, LuksOpenOptions};
+use luksctl::mapper::{
+    get_mount_mapping, invoking_owner, remove_mount_mapping, store_mount_mapping, unix_timestamp, wait_for_mapper,
+    MountState, STATE_VERSION,
+};
+use luksctl::mount::{is_mounted, mount_device, unmount, MountOptions};
+
+const PASSPHRASE: &str = "luksctl-test-passphrase";
+const IMAGE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// A LUKS-formatted sparse image attached to a loop device
+///
+/// Everything is torn down on drop, best effort, so a failed assertion
+/// doesn't leave mappers or loop devices behind.
+struct LoopbackVolume {
+    dir: PathBuf,
+    loop_device: PathBuf,
+    mapper_name: String,
+}
+
+impl LoopbackVolume {
+    fn new(name: &str) -> Self {
+        assert!(nix::unistd::Uid::effective().is_root(), "loopback tests must run as root");
+
+        let dir = std::env::temp_dir().join(format!("luksctl-it-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        use_private_state_dir();
+
+        // Sparse image: only the header and filesystem metadata use disk space
+        let image = dir.join("volume.img");
+        File::create(&image).unwrap().set_len(IMAGE_SIZE).unwrap();
+
+        let loop_device = attach_loop(&image, false).unwrap();
+        let volume = Self { dir, loop_device, mapper_name: format!("luks-it-{}-{}", name, std::process::id()) };
+
+        // A cheap PBKDF keeps the tests fast; the key is read from stdin verbatim
+        run_with_input(
+            Command::new("cryptsetup")
+                .args(["luksFormat", "--batch-mode", "--type", "luks2"])
+                .args(["--pbkdf", "pbkdf2", "--pbkdf-force-iterations", "1000"])
+                .args(["--key-file", "-"])
+                .arg(&volume.loop_device),
+            PASSPHRASE.as_bytes(),
+        );
+        volume
+    }
+
+    fn mount_point(&self) -> PathBuf {
+        self.dir.join("mnt")
+    }
+
+    fn open(&self, passphrase: &str) -> Result<(), LuksError> {
+        let passphrase = SecretString::from(passphrase.to_string());
+        luks_open(&self.loop_device, &self.mapper_name, &passphrase, &LuksOpenOptions::default())
+    }
+}
+
+impl Drop for LoopbackVolume {
+    fn drop(&mut self) {
+        if is_mounted(&self.mount_point()).unwrap_or(false) {
+            let _ = unmount(&self.mount_point());
+        }
+        if is_mapper_active(&self.mapper_name).unwrap_or(false) {
+            let _ = luks_close(&self.mapper_name);
+        }
+        let _ = detach_loop(&self.loop_device);
+        let _ = remove_mount_mapping(&self.mount_point());
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Point luksctl at a private state directory shared by this test process
+fn use_private_state_dir() {
+    let state_dir = std::env::temp_dir().join(format!("luksctl-it-state-{}", std::process::id()));
+    fs::create_dir_all(&state_dir).unwrap();
+    fs::set_permissions(&state_dir, Permissions::from_mode(0o700)).unwrap();
+    std::env::set_var("LUKSCTL_STATE_DIR", &state_dir);
+}
+
+/// Run a command with `input` on stdin and assert it succeeds
+fn run_with_input(cmd: &mut Command, input: &[u8]) {
+    let mut child = cmd.stdin(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let status = child.wait().unwrap();
+    assert!(status.success(), "{:?} failed: {}", cmd, status);
+}
+
+fn mkfs_ext4(device: &Path) {
+    let status = Command::new("mkfs.ext4").arg("-q").arg(device).status().unwrap();
+    assert!(status.success(), "mkfs.ext4 failed: {}", status);
+}
+
+#[test]
+#[ignore = "needs root, cryptsetup and a free loop device"]
+fn test_open_mount_unmount_close() {
+    let volume = LoopbackVolume::new("cycle");
+    let mount_point = volume.mount_point();
+    fs::create_dir_all(&mount_point).unwrap();
+
+    volume.open(PASSPHRASE).unwrap();
+    let mapper_path = wait_for_mapper(&volume.mapper_name).unwrap();
+    assert!(is_mapper_active(&volume.mapper_name).unwrap());
+    mkfs_ext4(&mapper_path);
+
+    mount_device(&mapper_path, &mount_point, &MountOptions::default()).unwrap();
+    assert!(is_mounted(&mount_point).unwrap());
+
+    let state = MountState {
+        version: STATE_VERSION,
+        mapper_name: volume.mapper_name.clone(),
+        device: volume.loop_device.clone(),
+        device_input: None,
+        mount_point: mount_point.clone(),
+        header: None,
+        mount_options: Some(MountOptions::default()),
+        loop_device: None,
+        binds: Vec::new(),
+        crypt_read_only: false,
+        fs_type: Some("ext4".to_string()),
+        mounted_at: Some(unix_timestamp()),
+        uid: Some(0),
+        owner: Some(invoking_owner()),
+        created_dir: false,
+    };
+    store_mount_mapping(&state).unwrap();
+    assert_eq!(get_mount_mapping(&mount_point).unwrap(), Some(state));
+
+    unmount(&mount_point).unwrap();
+    assert!(!is_mounted(&mount_point).unwrap());
+    luks_close(&volume.mapper_name).unwrap();
+    assert!(!is_mapper_active(&volume.mapper_name).unwrap());
+
+    remove_mount_mapping(&mount_point).unwrap();
+    assert_eq!(get_mount_mapping(&mount_point).unwrap(), None);
+}
+
+#[test]
+#[ignore = "needs root, cryptsetup and a free loop device"]
+fn test_wrong_passphrase_is_reported() {
+    let volume = LoopbackVolume::new("wrongpass");
+
+    let err = volume.open("not the passphrase").unwrap_err();
+    assert!(matches!(err, LuksError::WrongPassphrase), "unexpected error: {err}");
+    assert!(!is_mapper_active(&volume.mapper_name).unwrap());
+}